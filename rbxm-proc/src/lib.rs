@@ -24,6 +24,26 @@ fn has_attr(attrs: &Vec<syn::Attribute>, name: &str) -> bool {
     attrs.iter().any(|attr| match_path(&attr.path, name))
 }
 
+/// Whether a type is written as `Option<CFrame>`, the only type with an optional encoding
+fn is_optional_cframe(ty: &syn::Type) -> bool {
+    let last_segment = |ty: &syn::Type| match ty {
+        syn::Type::Path(path) => path.path.segments.last().cloned(),
+        _ => None,
+    };
+
+    match last_segment(ty) {
+        Some(segment) if segment.ident == "Option" => match segment.arguments {
+            syn::PathArguments::AngleBracketed(args) => matches!(
+                args.args.first(),
+                Some(syn::GenericArgument::Type(inner))
+                    if last_segment(inner).map_or(false, |inner| inner.ident == "CFrame")
+            ),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
 #[proc_macro_derive(Inherits)]
 pub fn inherits(item: TokenStream) -> TokenStream {
     let item = parse_macro_input!(item as DeriveInput);
@@ -61,7 +81,7 @@ pub fn inherits(item: TokenStream) -> TokenStream {
     TokenStream::from(expanded)
 }
 
-#[proc_macro_derive(PropertyConvert, attributes(shared, propname))]
+#[proc_macro_derive(PropertyConvert, attributes(shared, optional, propname, extra, protected))]
 pub fn property_convert(item: TokenStream) -> TokenStream {
     let item = parse_macro_input!(item as DeriveInput);
     let item_name = &item.ident;
@@ -75,12 +95,42 @@ pub fn property_convert(item: TokenStream) -> TokenStream {
         _ => panic!("PropertyConvert requires named fields"),
     };
 
+    let mut protected = Vec::new();
+    let mut remap = Vec::new();
+    let mut checks = Vec::new();
     let (constructor, destructor): (Vec<_>, Vec<_>) = named_fields
         .named
         .iter()
         .map(|field| {
             let field_name = &field.ident;
+
+            // The extra properties bag is filled after all other fields, by `make_instance`
+            if has_attr(&field.attrs, "extra") {
+                remap.push(quote!(
+                    for prop in self.#field_name.values_mut() {
+                        changed |= crate::serde::internal::remap_property_refs(prop, remap);
+                    }
+                ));
+                return (
+                    quote!(#field_name: Default::default()),
+                    quote!(
+                        for (name, prop) in &self.#field_name {
+                            properties.entry(name.clone()).or_insert_with(|| prop.clone());
+                        }
+                    ),
+                );
+            }
+
             let shared = has_attr(&field.attrs, "shared");
+            let optional = has_attr(&field.attrs, "optional");
+            if optional && !is_optional_cframe(&field.ty) {
+                let err = syn::Error::new_spanned(
+                    &field.ty,
+                    "`#[optional]` is only supported on `Option<CFrame>` fields",
+                )
+                .to_compile_error();
+                return (quote!(#field_name: #err), quote!());
+            }
             let prop_name = field.attrs.iter().find(|attr| match_path(&attr.path, "propname")).map(|attr| {
                 let meta = if let syn::Meta::NameValue(value) = attr.parse_meta().unwrap() {
                     value
@@ -94,17 +144,33 @@ pub fn property_convert(item: TokenStream) -> TokenStream {
                 }
             }).unwrap_or(to_pascal_case(&field.ident.as_ref().unwrap()));
 
+            protected.push(if has_attr(&field.attrs, "protected") {
+                quote!(name == #prop_name)
+            } else {
+                quote!(crate::serde::internal::FieldToProperties::is_protected(&self.#field_name, name))
+            });
+            remap.push(quote!(
+                changed |= crate::serde::internal::FieldToProperties::remap_refs(&mut self.#field_name, remap);
+            ));
+            let field_ty = &field.ty;
+            checks.push(quote!(
+                <#field_ty as crate::serde::internal::FieldFromProperties>::check_properties(
+                    crate::serde::internal::FieldAttrs { prop_name: #prop_name, shared: #shared, optional: #optional },
+                    properties,
+                )?;
+            ));
+
             let (getter, setter) = (
                 quote!(
                     crate::serde::internal::FieldFromProperties::from_properties(
-                        crate::serde::internal::FieldAttrs { field_name: stringify!(#field_name), prop_name: #prop_name, shared: #shared },
+                        crate::serde::internal::FieldAttrs { prop_name: #prop_name, shared: #shared, optional: #optional },
                         properties,
                     )?
                 ),
                 quote!(
                     crate::serde::internal::FieldToProperties::to_properties(
                         self.#field_name.clone(),
-                        crate::serde::internal::FieldAttrs { field_name: stringify!(#field_name), prop_name: #prop_name, shared: #shared },
+                        crate::serde::internal::FieldAttrs { prop_name: #prop_name, shared: #shared, optional: #optional },
                         properties,
                     );
                 ),
@@ -121,12 +187,27 @@ pub fn property_convert(item: TokenStream) -> TokenStream {
                     #(#constructor),*
                 })
             }
+
+            fn check_properties(properties: &alloc::collections::BTreeMap<String, Property>) -> core::result::Result<(), crate::SerdeError> {
+                #(#checks)*
+                Ok(())
+            }
         }
 
         impl ToProperties for #item_name {
             fn to_properties(&self, properties: &mut alloc::collections::BTreeMap<String, Property>) {
                 #(#destructor;)*
             }
+
+            fn is_protected(&self, name: &str) -> bool {
+                false #(|| #protected)*
+            }
+
+            fn remap_refs(&mut self, remap: &mut dyn FnMut(crate::tree::TreeKey) -> Option<crate::tree::TreeKey>) -> bool {
+                let mut changed = false;
+                #(#remap)*
+                changed
+            }
         }
     };
 
@@ -203,6 +284,24 @@ pub fn enum_convert(item: TokenStream) -> TokenStream {
                     None => Err(crate::SerdeError::missing_property(alloc::string::String::from(attrs.prop_name))),
                 }
             }
+
+            fn check_properties(
+                attrs: crate::serde::internal::FieldAttrs,
+                properties: &alloc::collections::BTreeMap<alloc::string::String, crate::model::Property>
+            ) -> crate::serde::error::Result<()> {
+                let prop = crate::serde::internal::check_property(
+                    &attrs,
+                    properties,
+                    crate::model::property::PropertyType::Enum,
+                    |prop| matches!(prop, crate::model::Property::Enum(_)),
+                )?;
+                match prop {
+                    crate::model::Property::Enum(val) => Self::try_from(*val)
+                        .map(|_| ())
+                        .map_err(|_| crate::SerdeError::unknown_variant(*val)),
+                    _ => unreachable!(),
+                }
+            }
         }
 
         impl crate::serde::internal::FieldToProperties for #item_name {
@@ -222,8 +321,12 @@ pub fn enum_convert(item: TokenStream) -> TokenStream {
 struct InstanceResult {
     class_name: proc_macro2::TokenStream,
     name: proc_macro2::TokenStream,
+    base: proc_macro2::TokenStream,
     from_props: proc_macro2::TokenStream,
+    check: proc_macro2::TokenStream,
     to_props: proc_macro2::TokenStream,
+    protected: proc_macro2::TokenStream,
+    remap: proc_macro2::TokenStream,
 }
 
 impl InstanceResult {
@@ -234,15 +337,23 @@ impl InstanceResult {
         Vec<proc_macro2::TokenStream>,
         Vec<proc_macro2::TokenStream>,
         Vec<proc_macro2::TokenStream>,
+        Vec<proc_macro2::TokenStream>,
+        Vec<proc_macro2::TokenStream>,
+        Vec<proc_macro2::TokenStream>,
+        Vec<proc_macro2::TokenStream>,
     ) {
         results.into_iter().fold(
-            (Vec::new(), Vec::new(), Vec::new(), Vec::new()),
-            |(mut classes, mut names, mut from_props, mut to_props), this| {
+            (Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new()),
+            |(mut classes, mut names, mut bases, mut from_props, mut checks, mut to_props, mut protected, mut remap), this| {
                 classes.push(this.class_name);
                 names.push(this.name);
+                bases.push(this.base);
                 from_props.push(this.from_props);
+                checks.push(this.check);
                 to_props.push(this.to_props);
-                (classes, names, from_props, to_props)
+                protected.push(this.protected);
+                remap.push(this.remap);
+                (classes, names, bases, from_props, checks, to_props, protected, remap)
             },
         )
     }
@@ -274,20 +385,18 @@ pub fn instance_extra(item: TokenStream) -> TokenStream {
                             panic!("Instance didn't have a Name")
                         }
                     }),
-                    from_props: quote!(_ => {
-                        // eprintln!("Other ty: {}", kind);
-                        // eprintln!("    Properties: {:?}", properties);
-                        let kind = Instance::Other(
-                            String::from(kind),
-                            properties
-                                .iter()
-                                .map(|(key, val)| (key.clone(), val.clone()))
-                                .collect(),
-                        );
-                        properties.clear();
-                        kind
-                    }),
+                    base: quote!(#item_name::Other(..) => None),
+                    from_props: quote!(_ => Instance::Other(String::from(kind), properties)),
+                    check: quote!(_ => Ok(())),
                     to_props: quote!(#item_name::Other(_, attrs) => properties.extend(attrs.clone())),
+                    protected: quote!(#item_name::Other(..) => false),
+                    remap: quote!(#item_name::Other(_, attrs) => {
+                        let mut changed = false;
+                        for prop in attrs.values_mut() {
+                            changed |= crate::serde::internal::remap_property_refs(prop, remap);
+                        }
+                        changed
+                    }),
                 };
             }
 
@@ -305,23 +414,49 @@ pub fn instance_extra(item: TokenStream) -> TokenStream {
 
             let class_name = quote!(#item_name::#variant_name(..) => #class_name_str);
             let name = quote!(#item_name::#variant_name(data) => &data.name);
-            let from_props = if is_boxed {
-                quote!(#class_name_str => #item_name::#variant_name(alloc::boxed::Box::new(#variant_name::from_properties(&mut properties)?)))
+            let base = quote!(#item_name::#variant_name(data) => {
+                let base: &crate::model::instance::Base = data;
+                Some(base)
+            });
+            let data = if is_boxed {
+                quote!(alloc::boxed::Box::new(data))
             } else {
-                quote!(#class_name_str => #item_name::#variant_name(#variant_name::from_properties(&mut properties)?))
+                quote!(data)
             };
+            let from_props = quote!(#class_name_str => {
+                let mut data = #variant_name::from_properties(&mut properties)?;
+                data.extra_properties.append(&mut properties);
+                #item_name::#variant_name(#data)
+            });
+            let check = quote!(#class_name_str => #variant_name::check_properties(properties));
             let to_props = quote!(#item_name::#variant_name(data) => data.to_properties(&mut properties));
+            let inner = if is_boxed {
+                quote!(&**data)
+            } else {
+                quote!(data)
+            };
+            let protected = quote!(#item_name::#variant_name(data) => crate::serde::internal::ToProperties::is_protected(#inner, name));
+            let inner_mut = if is_boxed {
+                quote!(&mut **data)
+            } else {
+                quote!(data)
+            };
+            let remap = quote!(#item_name::#variant_name(data) => crate::serde::internal::ToProperties::remap_refs(#inner_mut, remap));
 
             InstanceResult {
                 class_name,
                 name,
+                base,
                 from_props,
-                to_props
+                check,
+                to_props,
+                protected,
+                remap,
             }
         })
         .collect();
 
-    let (class_names, names, from_props, to_props) = InstanceResult::unzip(results);
+    let (class_names, names, bases, from_props, checks, to_props, protected, remap) = InstanceResult::unzip(results);
 
     let expanded = quote! {
         impl #item_name {
@@ -345,18 +480,47 @@ pub fn instance_extra(item: TokenStream) -> TokenStream {
                 }
             }
 
+            /// Get the properties common to all instances, or `None` if the instance is of an
+            /// unrecognized type
+            #[must_use]
+            pub fn base(&self) -> Option<&crate::model::instance::Base> {
+                match self {
+                    #(#bases),*
+                }
+            }
+
             pub(crate) fn make_instance(kind: &str, mut properties: BTreeMap<String, Property>) -> Result<Instance, crate::SerdeError> {
-                let out = match kind {
+                // Properties an instance doesn't model are kept as its extra properties, so every
+                // property is always consumed
+                Ok(match kind {
                     #(#from_props),*
-                };
+                })
+            }
 
-                if properties.is_empty() {
-                    Ok(out)
-                } else {
-                    Err(crate::SerdeError::unconsumed_properties(
-                        out.class_name(),
-                        properties.into_iter().map(|(keys, _)| keys).collect(),
-                    ))
+            /// Check that `make_instance` wouldn't fail because `properties` don't fit the class,
+            /// without consuming them. Errors from malformed data may still only be found while
+            /// building the instance.
+            pub(crate) fn check_instance(kind: &str, properties: &BTreeMap<String, Property>) -> Result<(), crate::SerdeError> {
+                match kind {
+                    #(#checks),*
+                }
+            }
+
+            /// Whether a string property of this instance holds protected data such as script
+            /// source, which the XML format writes as a `ProtectedString`
+            pub(crate) fn is_protected(&self, name: &str) -> bool {
+                match self {
+                    #(#protected),*
+                }
+            }
+
+            /// Change the target of every reference this instance holds to another instance, in
+            /// place. `remap` is given each referenced key, and returns the new target or `None`
+            /// to clear the reference. Returns whether any reference was changed.
+            pub(crate) fn remap_refs(&mut self, mut remap: impl FnMut(crate::tree::TreeKey) -> Option<crate::tree::TreeKey>) -> bool {
+                let remap: &mut dyn FnMut(crate::tree::TreeKey) -> Option<crate::tree::TreeKey> = &mut remap;
+                match self {
+                    #(#remap),*
                 }
             }
 
@@ -16,4 +16,4 @@ pub use enums::*;
 pub use error::Error;
 pub use instance::Instance;
 pub use property::Property;
-pub use rbx_model::RbxModel;
+pub use rbx_model::{MetaConflict, RbxModel};
@@ -36,6 +36,101 @@ impl fmt::Debug for Attributes {
     }
 }
 
+/// The set of `CollectionService` tags applied to an instance. Tags are unique, and kept in the
+/// order they were added.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Tags {
+    backing: Vec<String>,
+}
+
+impl Tags {
+    /// Create a new empty set of tags
+    #[must_use]
+    pub const fn new() -> Tags {
+        Tags {
+            backing: Vec::new(),
+        }
+    }
+
+    /// Check whether a tag is in this set
+    #[must_use]
+    pub fn contains(&self, tag: &str) -> bool {
+        self.backing.iter().any(|item| item == tag)
+    }
+
+    /// Add a tag to the end of this set. Returns `false` if the tag was already present.
+    pub fn insert(&mut self, tag: String) -> bool {
+        if self.contains(&tag) {
+            false
+        } else {
+            self.backing.push(tag);
+            true
+        }
+    }
+
+    /// Remove a tag from this set. Returns `false` if the tag wasn't present.
+    pub fn remove(&mut self, tag: &str) -> bool {
+        let len = self.backing.len();
+        self.backing.retain(|item| item != tag);
+        self.backing.len() != len
+    }
+
+    /// Iterate over the tags in this set, in order
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.backing.iter().map(String::as_str)
+    }
+
+    /// Get the number of tags in this set
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.backing.len()
+    }
+
+    /// Check whether this set has no tags
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.backing.is_empty()
+    }
+
+    /// Read tags from their serialized form, a NUL-separated list of names. Returns `None` if
+    /// any name isn't valid UTF-8, as it couldn't be written back out unchanged.
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Option<Tags> {
+        Tags::split_bytes(bytes)
+            .map(|tag| String::from_utf8(tag.to_vec()).ok())
+            .collect()
+    }
+
+    /// Check whether the serialized form of some tags contains a tag, without reading them
+    pub(crate) fn bytes_contain(bytes: &[u8], tag: &str) -> bool {
+        Tags::split_bytes(bytes).any(|item| item == tag.as_bytes())
+    }
+
+    pub(crate) fn split_bytes(bytes: &[u8]) -> impl Iterator<Item = &[u8]> {
+        bytes.split(|&b| b == 0).filter(|tag| !tag.is_empty())
+    }
+
+    /// Write tags in their serialized form, a NUL-separated list of names
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        self.backing.join("\0").into_bytes()
+    }
+}
+
+impl FromIterator<String> for Tags {
+    fn from_iter<I: IntoIterator<Item = String>>(iter: I) -> Self {
+        let mut out = Tags::new();
+        for tag in iter {
+            out.insert(tag);
+        }
+        out
+    }
+}
+
+impl fmt::Debug for Tags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(&self.backing).finish()
+    }
+}
+
 /// A dimensional component representing a scale and an offset
 ///
 #[doc = doc_link!("datatype/UDim")]
@@ -450,6 +545,113 @@ impl Default for CFrame {
     }
 }
 
+/// A rotation represented as a unit quaternion. This is a more compact alternative to the
+/// rotation matrix of a [`CFrame`], and can be converted to and from one.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Quaternion {
+    /// X component
+    pub x: f32,
+    /// Y component
+    pub y: f32,
+    /// Z component
+    pub z: f32,
+    /// W component, the real part
+    pub w: f32,
+}
+
+impl Quaternion {
+    /// The identity rotation, which doesn't rotate at all
+    pub const IDENTITY: Quaternion = Quaternion::new(0.0, 0.0, 0.0, 1.0);
+
+    /// Create a new `Quaternion` from components
+    #[must_use]
+    pub const fn new(x: f32, y: f32, z: f32, w: f32) -> Quaternion {
+        Quaternion { x, y, z, w }
+    }
+
+    /// Create a quaternion from a rotation matrix, in the same layout as [`CFrame::angle`]. The
+    /// matrix should be orthonormal, as any scaling or skew can't be represented.
+    #[must_use]
+    pub fn from_matrix(angle: &[[f32; 3]; 3]) -> Quaternion {
+        let [[r00, r01, r02], [r10, r11, r12], [r20, r21, r22]] = *angle;
+        let trace = r00 + r11 + r22;
+
+        // Pick whichever component is largest to divide by, to keep the result accurate
+        if trace > 0.0 {
+            let s = num::Float::sqrt(trace + 1.0) * 2.0;
+            Quaternion::new((r21 - r12) / s, (r02 - r20) / s, (r10 - r01) / s, s / 4.0)
+        } else if r00 > r11 && r00 > r22 {
+            let s = num::Float::sqrt(1.0 + r00 - r11 - r22) * 2.0;
+            Quaternion::new(s / 4.0, (r01 + r10) / s, (r02 + r20) / s, (r21 - r12) / s)
+        } else if r11 > r22 {
+            let s = num::Float::sqrt(1.0 + r11 - r00 - r22) * 2.0;
+            Quaternion::new((r01 + r10) / s, s / 4.0, (r12 + r21) / s, (r02 - r20) / s)
+        } else {
+            let s = num::Float::sqrt(1.0 + r22 - r00 - r11) * 2.0;
+            Quaternion::new((r02 + r20) / s, (r12 + r21) / s, s / 4.0, (r10 - r01) / s)
+        }
+    }
+
+    /// Convert this quaternion into a rotation matrix, in the same layout as [`CFrame::angle`]
+    #[must_use]
+    pub fn to_matrix(&self) -> [[f32; 3]; 3] {
+        let Quaternion { x, y, z, w } = *self;
+        [
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - z * w),
+                2.0 * (x * z + y * w),
+            ],
+            [
+                2.0 * (x * y + z * w),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - x * w),
+            ],
+            [
+                2.0 * (x * z - y * w),
+                2.0 * (y * z + x * w),
+                1.0 - 2.0 * (x * x + y * y),
+            ],
+        ]
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Quaternion::IDENTITY
+    }
+}
+
+/// A point in space plus a rotation, with the rotation stored as a [`Quaternion`]. This is an
+/// alternate encoding of a [`CFrame`] used by some binary files, and converts to and from one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CFrameQuat {
+    /// The position in space this `CFrame` represents
+    pub position: Vector3,
+    /// The rotation this `CFrame` represents
+    pub rotation: Quaternion,
+}
+
+impl CFrameQuat {
+    /// Create a new `CFrameQuat` from components
+    #[must_use]
+    pub const fn new(position: Vector3, rotation: Quaternion) -> CFrameQuat {
+        CFrameQuat { position, rotation }
+    }
+}
+
+impl From<CFrameQuat> for CFrame {
+    fn from(val: CFrameQuat) -> CFrame {
+        CFrame::new(val.position, val.rotation.to_matrix())
+    }
+}
+
+impl From<CFrame> for CFrameQuat {
+    fn from(val: CFrame) -> CFrameQuat {
+        CFrameQuat::new(val.position, Quaternion::from_matrix(&val.angle))
+    }
+}
+
 /// A reference to another instance in the model, which may be null
 #[derive(Debug, Clone)]
 pub enum InstanceRef {
@@ -614,16 +816,6 @@ impl From<(u8, u8, u8)> for Color3Uint8 {
     }
 }
 
-/// A pivot-point representing the 'center of mass' of a model, which it rotates around
-#[derive(Debug, Clone, Default)]
-pub struct Pivot {
-    /// The position and orientation of the pivot in space
-    pub cframe: CFrame,
-    /// Unknown extra data
-    // FIXME(CraftSpider)
-    pub unknown: u8,
-}
-
 /// A set of physical properties, possibly user defined or not
 #[derive(Debug, Clone)]
 #[non_exhaustive]
@@ -664,6 +856,48 @@ pub struct FontFace {
     pub cached_face_id: String,
 }
 
+/// A single item of a named enum. Unlike [`Property::Enum`], this carries the name of the enum it
+/// belongs to, and is how enums are stored in [`Attributes`]
+///
+#[doc = doc_link!("datatype/EnumItem")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnumItem {
+    /// The name of the enum, such as `Material`
+    pub enum_type: String,
+    /// The value of this item within the enum
+    pub value: u32,
+}
+
+impl EnumItem {
+    /// Create a new `EnumItem` from an enum name and value
+    #[must_use]
+    pub const fn new(enum_type: String, value: u32) -> EnumItem {
+        EnumItem { enum_type, value }
+    }
+}
+
+/// A set of security capabilities, which restrict what a script or sandboxed
+/// [`Actor`](crate::model::instance::Actor) is allowed to do. Each bit is a single capability.
+///
+#[doc = doc_link!("datatype/SecurityCapabilities")]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct SecurityCapabilities(pub u64);
+
+/// A reference to some content, such as an image or mesh, used by a property. This can either be
+/// a URI pointing to an asset, or an object in the same model.
+///
+#[doc = doc_link!("datatype/Content")]
+#[derive(Debug, Clone, Default)]
+pub enum Content {
+    /// No content
+    #[default]
+    None,
+    /// A URI for an asset, such as `rbxassetid://1234`
+    Uri(String),
+    /// An object in the same model
+    Object(TreeKey),
+}
+
 /// A full triangle mesh, used for collision or display
 #[cfg(feature = "mesh-format")]
 #[derive(Debug, Clone)]
@@ -263,6 +263,40 @@ pub enum Instance {
     Other(String, BTreeMap<String, Property>),
 }
 
+impl Instance {
+    /// Check whether this instance has a `CollectionService` tag. Unlike going through
+    /// [`Instance::base`], this also checks the tags of unrecognized instances.
+    #[must_use]
+    pub fn has_tag(&self, tag: &str) -> bool {
+        match (self.base(), self) {
+            (Some(base), _) => base.has_tag(tag),
+            (None, Instance::Other(_, props)) => match props.get("Tags") {
+                Some(Property::BinaryString(bytes)) => Tags::bytes_contain(bytes, tag),
+                Some(Property::TextString(str)) => Tags::bytes_contain(str.as_bytes(), tag),
+                _ => false,
+            },
+            (None, _) => false,
+        }
+    }
+
+    /// Get the `CollectionService` tags of this instance, including those of unrecognized
+    /// instances. Tags of unrecognized instances which aren't valid UTF-8 are skipped.
+    pub(crate) fn tag_names(&self) -> Vec<&str> {
+        let bytes = match (self.base(), self) {
+            (Some(base), _) => return base.tags.iter().collect(),
+            (None, Instance::Other(_, props)) => match props.get("Tags") {
+                Some(Property::BinaryString(bytes)) => bytes.as_slice(),
+                Some(Property::TextString(str)) => str.as_bytes(),
+                _ => return Vec::new(),
+            },
+            (None, _) => return Vec::new(),
+        };
+        Tags::split_bytes(bytes)
+            .filter_map(|tag| core::str::from_utf8(tag).ok())
+            .collect()
+    }
+}
+
 /// Information common to all instances, presumably part of Instance itself.
 ///
 #[doc = doc_link!("class/Instance")]
@@ -271,9 +305,8 @@ pub enum Instance {
 pub struct Base {
     /// The name of this instance
     pub name: String,
-    /// Custom tags applied to the instance
-    // FIXME(CraftSpider) This is most likely actually a list of some kind
-    pub tags: String,
+    /// `CollectionService` tags applied to the instance
+    pub tags: Tags,
     /// The ID of the asset source for this instance
     pub source_asset_id: i64,
     /// Serialized custom attributes on the instance
@@ -281,16 +314,37 @@ pub struct Base {
     pub attributes: Attributes,
     /// A UUID identifying this instance in a world. Generally not present in model files
     pub unique_id: Option<Uuid>,
+    /// Properties present on the instance that aren't otherwise modelled. These are preserved
+    /// and written back out unchanged, unless a modelled property has the same name.
+    #[extra]
+    pub extra_properties: BTreeMap<String, Property>,
 }
 
 impl Base {
+    /// Add a tag to this instance. Returns `false` if the instance already had the tag.
+    pub fn add_tag(&mut self, tag: impl Into<String>) -> bool {
+        self.tags.insert(tag.into())
+    }
+
+    /// Remove a tag from this instance. Returns `false` if the instance didn't have the tag.
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        self.tags.remove(tag)
+    }
+
+    /// Check whether this instance has a tag
+    #[must_use]
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.contains(tag)
+    }
+
     fn new_named(name: String) -> Base {
         Base {
             name,
-            tags: String::new(),
+            tags: Tags::new(),
             source_asset_id: 0,
             attributes: Attributes::default(),
             unique_id: None,
+            extra_properties: BTreeMap::new(),
         }
     }
 }
@@ -1614,7 +1668,8 @@ pub struct Model {
     pub model_mesh_cframe: CFrame,
     pub primary_part: InstanceRef,
     pub needs_pivot_migration: bool,
-    pub world_pivot_data: Pivot,
+    #[optional]
+    pub world_pivot_data: Option<CFrame>,
 }
 
 impl Model {
@@ -1637,7 +1692,7 @@ impl Model {
             model_mesh_cframe: CFrame::default(),
             primary_part: InstanceRef::Null,
             needs_pivot_migration: false,
-            world_pivot_data: Pivot::default(),
+            world_pivot_data: None,
         }
     }
 }
@@ -1652,6 +1707,7 @@ impl Default for Model {
 pub struct ModuleScript {
     pub lua_source_container: LuaSourceContainer,
     pub linked_source: String,
+    #[protected]
     pub source: String,
 }
 
@@ -2101,6 +2157,7 @@ pub struct ScreenGui {
 #[derive(Debug, Clone, Inherits, PropertyConvert)]
 pub struct Script {
     pub base_script: BaseScript,
+    #[protected]
     pub source: String,
 }
 
@@ -50,6 +50,8 @@ pub enum PropertyType {
     Vector3,
     /// A [`CFrame`]
     CFrame,
+    /// A [`CFrameQuat`]
+    CFrameQuat,
     /// Any enumeration, see [`crate::model::enums`]
     Enum,
     /// An [`InstanceRef`]
@@ -68,12 +70,20 @@ pub enum PropertyType {
     PhysicalProperties,
     /// A [`Color3Uint8`]
     Color3Uint8,
-    /// A [`Pivot`]
-    Pivot,
+    /// An optional [`CFrame`]
+    OptionalCFrame,
     /// A [`Uuid`]
     Uuid,
     /// A [`FontFace`]
     Font,
+    /// A set of [`SecurityCapabilities`]
+    SecurityCapabilities,
+    /// An [`EnumItem`]
+    EnumItem,
+    /// The absence of a value
+    Nil,
+    /// A [`Content`]
+    Content,
 }
 
 impl PropertyType {
@@ -100,6 +110,7 @@ impl PropertyType {
             PropertyType::Vector2 => "Vector2",
             PropertyType::Vector3 => "Vector3",
             PropertyType::CFrame => "CFrame",
+            PropertyType::CFrameQuat => "CFrameQuat",
             PropertyType::Enum => "Enum",
             PropertyType::InstanceRef => "InstanceRef",
             PropertyType::Vector3Int16 => "Vector3Int16",
@@ -109,9 +120,13 @@ impl PropertyType {
             PropertyType::Rect => "Rect",
             PropertyType::PhysicalProperties => "PhysicalProperties",
             PropertyType::Color3Uint8 => "Color3Uint8",
-            PropertyType::Pivot => "Pivot",
+            PropertyType::OptionalCFrame => "OptionalCFrame",
             PropertyType::Uuid => "Uuid",
             PropertyType::Font => "Font",
+            PropertyType::SecurityCapabilities => "SecurityCapabilities",
+            PropertyType::EnumItem => "EnumItem",
+            PropertyType::Nil => "Nil",
+            PropertyType::Content => "Content",
         }
     }
 }
@@ -160,7 +175,8 @@ pub enum Property {
     Vector3(Vector3),
     /// See [`CFrame`]
     CFrame(CFrame),
-    // Quaternion, // Not implemented properly by RBX?
+    /// See [`CFrameQuat`]
+    CFrameQuat(CFrameQuat),
     /// The discriminant for an enum, see [`model::enums`][crate::model::enums]. Which enum is not
     /// provided and must be known ahead of time.
     Enum(i32),
@@ -180,12 +196,22 @@ pub enum Property {
     PhysicalProperties(PhysicalProperties),
     /// See [`Color3Uint8`]
     Color3Uint8(Color3Uint8),
-    /// See [`Pivot`]
-    Pivot(Pivot),
+    /// A [`CFrame`] which may be unset, such as the pivot of a model
+    OptionalCFrame(Option<CFrame>),
     /// A Universal Unique Identifier, or UUID
     Uuid(Uuid),
     /// See [`FontFace`]
     Font(FontFace),
+    /// See [`SecurityCapabilities`]
+    SecurityCapabilities(SecurityCapabilities),
+    /// See [`EnumItem`]. Only found in [`Attributes`], other enum properties use [`Property::Enum`].
+    /// Only attributes keep the enum type; as a property this is written as its value alone, and
+    /// reads back as a [`Property::Enum`].
+    EnumItem(EnumItem),
+    /// No value. Only found in [`Attributes`]
+    Nil,
+    /// See [`Content`]
+    Content(Content),
 }
 
 impl Property {
@@ -211,6 +237,7 @@ impl Property {
             Property::Vector2(..) => PropertyType::Vector2,
             Property::Vector3(..) => PropertyType::Vector3,
             Property::CFrame(..) => PropertyType::CFrame,
+            Property::CFrameQuat(..) => PropertyType::CFrameQuat,
             Property::Enum(..) => PropertyType::Enum,
             Property::InstanceRef(..) => PropertyType::InstanceRef,
             Property::Vector3Int16(..) => PropertyType::Vector3Int16,
@@ -220,9 +247,13 @@ impl Property {
             Property::Rect(..) => PropertyType::Rect,
             Property::PhysicalProperties(..) => PropertyType::PhysicalProperties,
             Property::Color3Uint8(..) => PropertyType::Color3Uint8,
-            Property::Pivot(..) => PropertyType::Pivot,
+            Property::OptionalCFrame(..) => PropertyType::OptionalCFrame,
             Property::Uuid(..) => PropertyType::Uuid,
             Property::Font(..) => PropertyType::Font,
+            Property::SecurityCapabilities(..) => PropertyType::SecurityCapabilities,
+            Property::EnumItem(..) => PropertyType::EnumItem,
+            Property::Nil => PropertyType::Nil,
+            Property::Content(..) => PropertyType::Content,
         }
     }
 }
@@ -1,9 +1,13 @@
 use crate::model::{Error, Instance};
-use crate::tree::{NodeRef, Tree};
+use crate::serde::chunk::UnknownChunk;
+use crate::serde::format::Format;
+use crate::tree::{NodeRef, Tree, TreeKey};
 
-use alloc::collections::BTreeMap;
+use alloc::collections::{BTreeMap, BTreeSet};
 use alloc::string::{String, ToString};
+use alloc::vec;
 use alloc::vec::Vec;
+use core::cell::RefCell;
 
 enum PathSegment<'a> {
     Index(usize),
@@ -29,11 +33,38 @@ fn split_path(path: &str) -> Result<Vec<PathSegment<'_>>, Error> {
         .collect()
 }
 
+/// How [`RbxModel::merge`] combines meta values both models have
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MetaConflict {
+    /// Keep the value already in the model being merged into
+    Keep,
+    /// Replace the value with the one from the model being merged in
+    Replace,
+}
+
+/// The instances with each `CollectionService` tag, in tree order, along with the generation of
+/// the tree it was built from
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TagIndex {
+    generation: Option<u64>,
+    tagged: BTreeMap<String, Vec<TreeKey>>,
+}
+
 /// A full Roblox model
+///
+/// Cloning a model copies every instance, keeping the same keys.
+///
+/// # Panics
+///
+/// Cloning panics if any instance in the model is currently mutably borrowed
 #[derive(Debug, Clone)]
 pub struct RbxModel {
     pub(crate) meta: BTreeMap<String, String>,
     pub(crate) nodes: Tree<Instance>,
+    pub(crate) format: Format,
+    pub(crate) services: BTreeSet<TreeKey>,
+    pub(crate) unknown_chunks: Vec<UnknownChunk>,
+    pub(crate) tag_index: RefCell<TagIndex>,
 }
 
 impl RbxModel {
@@ -43,6 +74,10 @@ impl RbxModel {
         RbxModel {
             meta: BTreeMap::new(),
             nodes: Tree::new(),
+            format: Format::default(),
+            services: BTreeSet::new(),
+            unknown_chunks: Vec::new(),
+            tag_index: RefCell::default(),
         }
     }
 
@@ -111,6 +146,210 @@ impl RbxModel {
     pub fn tree(&self) -> &Tree<Instance> {
         &self.nodes
     }
+
+    /// Get the file format this model was read from, and will be written as by
+    /// [`to_writer`](crate::serde::to_writer). Newly created models default to binary.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Set the file format this model will be written as
+    pub fn set_format(&mut self, format: Format) {
+        self.format = format;
+    }
+
+    /// Check whether an instance is marked as a service. Place files mark top-level services
+    /// such as `Workspace` and `Lighting`, and these markers are kept when a binary model is read
+    /// and written back out.
+    pub fn is_service(&self, key: TreeKey) -> bool {
+        self.services.contains(&key)
+    }
+
+    /// Mark or unmark an instance as a service
+    pub fn set_service(&mut self, key: TreeKey, is_service: bool) {
+        if is_service {
+            self.services.insert(key);
+        } else {
+            self.services.remove(&key);
+        }
+    }
+
+    /// Remove an instance and all its descendants from the model, returning them in depth-first
+    /// order. Any references to the removed instances from the rest of the model are cleared, and
+    /// the removed instances stop being marked as services.
+    ///
+    /// # Errors
+    ///
+    /// If the instance doesn't exist, or any instance in the model is currently borrowed
+    pub fn destroy(&mut self, key: TreeKey) -> Result<Vec<Instance>, Error> {
+        let mut removed_keys = BTreeSet::new();
+        let mut stack = vec![key];
+        while let Some(key) = stack.pop() {
+            removed_keys.insert(key);
+            stack.extend(self.nodes.child_keys_of(key));
+        }
+
+        // Borrow everything that's kept up front, so nothing is removed if that fails
+        let mut kept = self
+            .nodes
+            .unordered_iter_mut()
+            .filter(|node| !matches!(node, Ok(node) if removed_keys.contains(&node.key())))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let removed = self.nodes.destroy(key)?;
+        self.services.retain(|key| !removed_keys.contains(key));
+
+        for node in &mut kept {
+            node.remap_refs(|target| (!removed_keys.contains(&target)).then_some(target));
+        }
+
+        Ok(removed)
+    }
+
+    /// Copy an instance and all its descendants within this model, either as a child of `parent`
+    /// or as a new root. References between the copied instances are changed to point at the
+    /// copies, while references to anything else are kept as-is. This mirrors `Instance:Clone`.
+    /// Returns the key of the copy.
+    ///
+    /// # Errors
+    ///
+    /// If the instance or parent doesn't exist, or any copied instance is currently mutably
+    /// borrowed
+    pub fn clone_subtree(
+        &mut self,
+        key: TreeKey,
+        parent: Option<TreeKey>,
+    ) -> Result<TreeKey, Error> {
+        let pairs = self.nodes.copy_subtree(&self.nodes, key, parent)?;
+        self.remap_copied(&pairs, Some);
+        Ok(pairs[0].1)
+    }
+
+    /// Copy an instance and all its descendants from another model into this one, either as a
+    /// child of `parent` or as a new root. References between the copied instances are changed
+    /// to point at the copies, and references to instances outside the copied subtree are
+    /// cleared, as they have no equivalent in this model. Returns the key of the copy.
+    ///
+    /// # Errors
+    ///
+    /// If the instance doesn't exist in `other`, the parent doesn't exist in this model, or any
+    /// copied instance is currently mutably borrowed
+    pub fn import_subtree(
+        &mut self,
+        other: &RbxModel,
+        key: TreeKey,
+        parent: Option<TreeKey>,
+    ) -> Result<TreeKey, Error> {
+        let pairs = self.nodes.copy_subtree(&other.nodes, key, parent)?;
+        self.remap_copied(&pairs, |_| None);
+        Ok(pairs[0].1)
+    }
+
+    /// Move every root of another model into this one, either as children of `parent` or as new
+    /// roots, keeping their order. References between the moved instances are changed to point
+    /// at their new keys, and instances marked as services stay marked. Meta values are combined,
+    /// with `meta` deciding which value wins when both models have one. Returns the new keys of
+    /// the moved roots.
+    ///
+    /// This model's unknown chunks are kept, as with any other edit, while those of `other` are
+    /// dropped. Chunks such as `SIGN` describe the file they were read from, which isn't the file
+    /// this model will be written to.
+    ///
+    /// # Errors
+    ///
+    /// If the parent doesn't exist in this model. Nothing is changed in that case.
+    pub fn merge(
+        &mut self,
+        other: RbxModel,
+        parent: Option<TreeKey>,
+        meta: MetaConflict,
+    ) -> Result<Vec<TreeKey>, Error> {
+        let old_roots = other.nodes.root_keys().collect::<Vec<_>>();
+        let pairs = self.nodes.append(other.nodes, parent)?;
+        self.remap_copied(&pairs, |_| None);
+
+        let moved = pairs.iter().copied().collect::<BTreeMap<_, _>>();
+        self.services.extend(
+            other
+                .services
+                .iter()
+                .filter_map(|old_key| moved.get(old_key).copied()),
+        );
+
+        for (key, value) in other.meta {
+            match meta {
+                MetaConflict::Keep => {
+                    self.meta.entry(key).or_insert(value);
+                }
+                MetaConflict::Replace => {
+                    self.meta.insert(key, value);
+                }
+            }
+        }
+
+        Ok(old_roots.iter().map(|old_key| moved[old_key]).collect())
+    }
+
+    /// Point references between copied or moved instances at their new keys, using `external`
+    /// to decide where references to instances outside the pairs end up
+    fn remap_copied(
+        &self,
+        pairs: &[(TreeKey, TreeKey)],
+        mut external: impl FnMut(TreeKey) -> Option<TreeKey>,
+    ) {
+        let copies = pairs.iter().copied().collect::<BTreeMap<_, _>>();
+
+        for &(_, new_key) in pairs {
+            let mut node = self
+                .nodes
+                .try_get_mut(new_key)
+                .expect("Newly copied nodes shouldn't be borrowed");
+            node.remap_refs(|target| copies.get(&target).copied().or_else(|| external(target)));
+        }
+    }
+
+    /// Get every instance with a `CollectionService` tag, in tree order, similar to
+    /// `CollectionService:GetTagged`. This is answered from an index of tags, which is built the
+    /// first time it's needed and rebuilt after the tree changes or any instance is mutably
+    /// borrowed.
+    ///
+    /// # Panics
+    ///
+    /// If any instance is currently mutably borrowed while the index is built, or a tagged
+    /// instance is when the iterator reaches it
+    pub fn tagged(&self, tag: &str) -> impl Iterator<Item = NodeRef<'_, '_, Instance>> {
+        let mut index = self.tag_index.borrow_mut();
+        let generation = self.nodes.generation();
+        if index.generation != Some(generation) {
+            index.tagged.clear();
+            for key in self.nodes.depth_first_keys() {
+                let node = self.nodes.try_get(key).expect("Couldn't borrow node");
+                for name in node.tag_names() {
+                    let keys = index.tagged.entry(name.to_string()).or_default();
+                    if keys.last() != Some(&key) {
+                        keys.push(key);
+                    }
+                }
+            }
+            index.generation = Some(generation);
+        }
+
+        let keys = index.tagged.get(tag).cloned().unwrap_or_default();
+        keys.into_iter()
+            .map(|key| self.nodes.try_get(key).expect("Couldn't borrow node"))
+    }
+
+    /// Get the chunks from a binary file which this crate doesn't understand, such as `SIGN`.
+    /// These are written back out when the model is saved as binary, so that reading and writing
+    /// a model doesn't lose them.
+    pub fn unknown_chunks(&self) -> &[UnknownChunk] {
+        &self.unknown_chunks
+    }
+
+    /// Get a mutable reference to the unknown chunks of this model
+    pub fn unknown_chunks_mut(&mut self) -> &mut Vec<UnknownChunk> {
+        &mut self.unknown_chunks
+    }
 }
 
 impl Default for RbxModel {
@@ -118,6 +357,10 @@ impl Default for RbxModel {
         let mut out = RbxModel {
             meta: BTreeMap::default(),
             nodes: Tree::new(),
+            format: Format::default(),
+            services: BTreeSet::new(),
+            unknown_chunks: Vec::new(),
+            tag_index: RefCell::default(),
         };
         out.meta
             .insert("ExplicitAutoJoints".to_string(), "true".to_string());
@@ -129,6 +372,7 @@ impl Default for RbxModel {
 mod tests {
     use super::*;
     use crate::model::instance::{Model, Part};
+    use crate::model::{InstanceRef, Property};
 
     #[test]
     fn test_get_path() {
@@ -174,6 +418,272 @@ mod tests {
         assert_eq!(err, Error::AmbiguousPath);
     }
 
+    #[test]
+    fn test_tagged() {
+        let model = RbxModel::new();
+        let tree = model.tree();
+
+        let mut tagged = Model::new();
+        assert!(tagged.add_tag("Door"));
+        assert!(tagged.add_tag("Locked"));
+        assert!(!tagged.add_tag("Door"));
+        let root = tree.add_root(Instance::Model(tagged));
+
+        let mut part = Part::new_named(String::from("Handle"));
+        part.add_tag("Door");
+        tree.new_child(Instance::Part(part), root);
+        tree.new_child(Instance::Part(Part::new()), root);
+
+        let mut props = BTreeMap::new();
+        props.insert(
+            String::from("Name"),
+            Property::TextString(String::from("Custom")),
+        );
+        props.insert(
+            String::from("Tags"),
+            Property::BinaryString(b"Locked\0Door".to_vec()),
+        );
+        tree.new_child(Instance::Other(String::from("Custom"), props), root);
+
+        let names = model
+            .tagged("Door")
+            .map(|node| node.name().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Model", "Handle", "Custom"]);
+        assert_eq!(model.tagged("Locked").count(), 2);
+        assert_eq!(model.tagged("Missing").count(), 0);
+
+        let mut tree_root = tree.try_get_mut(root).unwrap();
+        if let Instance::Model(model) = &mut *tree_root {
+            assert!(model.remove_tag("Door"));
+            assert!(!model.remove_tag("Door"));
+            assert_eq!(model.tags.iter().collect::<Vec<_>>(), ["Locked"]);
+        }
+        drop(tree_root);
+        // The index is rebuilt after an instance has been changed
+        assert_eq!(model.tagged("Door").count(), 2);
+    }
+
+    #[test]
+    fn test_destroy() {
+        let mut model = RbxModel::new();
+        let tree = model.tree();
+
+        let root = tree.add_root(Instance::Model(Model::new()));
+        tree.new_child(
+            Instance::Part(Part::new_named(String::from("Handle"))),
+            root,
+        );
+        let part = model.get_path("Model/Handle").unwrap().key();
+        tree.new_child(Instance::Part(Part::new()), part);
+
+        let mut props = BTreeMap::new();
+        props.insert(
+            String::from("Name"),
+            Property::TextString(String::from("Pointer")),
+        );
+        props.insert(
+            String::from("Value"),
+            Property::InstanceRef(InstanceRef::Item(part)),
+        );
+        tree.new_child(Instance::Other(String::from("CustomPointer"), props), root);
+
+        if let Instance::Model(data) = &mut *tree.try_get_mut(root).unwrap() {
+            data.primary_part = InstanceRef::Item(part);
+        }
+        model.set_service(part, true);
+
+        let removed = model.destroy(part).unwrap();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(removed[0].name(), "Handle");
+        assert_eq!(model.tree().len(), 2);
+        assert!(!model.is_service(part));
+
+        match &*model.get_path("Model").unwrap() {
+            Instance::Model(data) => assert!(matches!(data.primary_part, InstanceRef::Null)),
+            _ => panic!("Expected a Model"),
+        }
+        match &*model.get_path("Model/Pointer").unwrap() {
+            Instance::Other(_, props) => assert!(matches!(
+                props["Value"],
+                Property::InstanceRef(InstanceRef::Null)
+            )),
+            _ => panic!("Expected an unrecognized instance"),
+        }
+
+        assert_eq!(model.destroy(part).unwrap_err(), Error::NotFound);
+    }
+
+    #[test]
+    fn test_destroy_malformed_refs() {
+        use crate::model::Content;
+
+        let mut model = RbxModel::new();
+        let tree = model.tree();
+        let part = tree.add_root(Instance::Part(Part::new()));
+
+        // A recognized class missing its properties, as lenient deserialization leaves it
+        let mut props = BTreeMap::new();
+        props.insert(
+            String::from("Name"),
+            Property::TextString(String::from("Folder")),
+        );
+        props.insert(
+            String::from("Target"),
+            Property::InstanceRef(InstanceRef::Item(part)),
+        );
+        tree.add_root(Instance::Other(String::from("Folder"), props));
+
+        // References in extra properties are found as well
+        let mut holder = Model::new();
+        holder.extra_properties.insert(
+            String::from("Texture"),
+            Property::Content(Content::Object(part)),
+        );
+        tree.add_root(Instance::Model(holder));
+
+        model.destroy(part).unwrap();
+
+        let folder = model.get_path("Folder").unwrap();
+        match &*folder {
+            Instance::Other(_, props) => assert!(matches!(
+                props["Target"],
+                Property::InstanceRef(InstanceRef::Null)
+            )),
+            _ => panic!("Expected an unrecognized instance"),
+        }
+        let holder = model.get_path("Model").unwrap();
+        match &*holder {
+            Instance::Model(data) => assert!(matches!(
+                data.extra_properties["Texture"],
+                Property::Content(Content::None)
+            )),
+            _ => panic!("Expected a Model"),
+        }
+    }
+
+    #[test]
+    fn test_copy_subtree() {
+        let mut model = RbxModel::new();
+        let tree = model.tree();
+
+        let root = tree.add_root(Instance::Model(Model::new()));
+        let outside = tree.add_root(Instance::Part(Part::new_named(String::from("Outside"))));
+        if let Instance::Model(data) = &mut *tree.try_get_mut(root).unwrap() {
+            data.primary_part = InstanceRef::Item(outside);
+        }
+
+        let mut props = BTreeMap::new();
+        props.insert(
+            String::from("Name"),
+            Property::TextString(String::from("Pointer")),
+        );
+        props.insert(
+            String::from("Value"),
+            Property::InstanceRef(InstanceRef::Item(root)),
+        );
+        tree.new_child(Instance::Other(String::from("CustomPointer"), props), root);
+
+        let copy = model.clone_subtree(root, None).unwrap();
+        assert_eq!(model.tree().len(), 5);
+
+        let pointer_value = |model: &RbxModel, key: TreeKey| {
+            let child = model.tree().child_keys_of(key).next().unwrap();
+            match &*model.tree().try_get(child).unwrap() {
+                Instance::Other(_, props) => match props["Value"] {
+                    Property::InstanceRef(InstanceRef::Item(key)) => Some(key),
+                    _ => None,
+                },
+                _ => panic!("Expected an unrecognized instance"),
+            }
+        };
+        let primary_part =
+            |model: &RbxModel, key: TreeKey| match &*model.tree().try_get(key).unwrap() {
+                Instance::Model(data) => match data.primary_part {
+                    InstanceRef::Item(key) => Some(key),
+                    _ => None,
+                },
+                _ => panic!("Expected a Model"),
+            };
+
+        // Internal references follow the copy, external ones are kept
+        assert_eq!(pointer_value(&model, root), Some(root));
+        assert_eq!(pointer_value(&model, copy), Some(copy));
+        assert_eq!(primary_part(&model, copy), Some(outside));
+
+        // Importing into another model clears external references
+        let mut other = RbxModel::new();
+        let parent = other.tree().add_root(Instance::Model(Model::new()));
+        let imported = other.import_subtree(&model, root, Some(parent)).unwrap();
+
+        assert_eq!(other.tree().len(), 3);
+        assert_eq!(other.tree().parent_key_of(imported), Some(parent));
+        assert_eq!(pointer_value(&other, imported), Some(imported));
+        assert_eq!(primary_part(&other, imported), None);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut model = RbxModel::default();
+        let place = model.tree().add_root(Instance::Model(Model::new()));
+
+        let mut other = RbxModel::new();
+        other
+            .meta_mut()
+            .insert(String::from("ExplicitAutoJoints"), String::from("false"));
+        other
+            .meta_mut()
+            .insert(String::from("Team"), String::from("Combat"));
+        let tree = other.tree();
+        let first = tree.add_root(Instance::Model(Model::new()));
+        let second = tree.add_root(Instance::Part(Part::new_named(String::from("Handle"))));
+        if let Instance::Model(data) = &mut *tree.try_get_mut(first).unwrap() {
+            data.primary_part = InstanceRef::Item(second);
+        }
+        other.set_service(second, true);
+        other.unknown_chunks_mut().push(UnknownChunk {
+            name: String::from("SIGN"),
+            data: vec![1, 2, 3],
+            after: None,
+        });
+        model.unknown_chunks_mut().push(UnknownChunk {
+            name: String::from("SIGN"),
+            data: vec![4, 5, 6],
+            after: None,
+        });
+
+        assert_eq!(
+            model
+                .clone()
+                .merge(other.clone(), Some(second), MetaConflict::Keep)
+                .unwrap_err(),
+            Error::NotFound
+        );
+
+        let roots = model
+            .clone()
+            .merge(other.clone(), None, MetaConflict::Replace)
+            .unwrap();
+        assert_eq!(roots.len(), 2);
+
+        let roots = model.merge(other, Some(place), MetaConflict::Keep).unwrap();
+        assert_eq!(model.tree().len(), 3);
+        assert_eq!(model.tree().child_keys_of(place).collect::<Vec<_>>(), roots);
+        assert!(model.is_service(roots[1]));
+        assert_eq!(model.meta()["ExplicitAutoJoints"], "true");
+        assert_eq!(model.meta()["Team"], "Combat");
+        assert_eq!(model.unknown_chunks().len(), 1);
+        assert_eq!(model.unknown_chunks()[0].data, [4, 5, 6]);
+
+        let copy = model.tree().try_get(roots[0]).unwrap();
+        match &*copy {
+            Instance::Model(data) => {
+                assert!(matches!(data.primary_part, InstanceRef::Item(key) if key == roots[1]))
+            }
+            _ => panic!("Expected a Model"),
+        }
+    }
+
     #[test]
     fn test_invalid_path() {
         let model = RbxModel::new();
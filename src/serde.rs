@@ -1,16 +1,18 @@
 //! The ability to serialize/deserialize an RBXM file
 
+pub mod chunk;
 pub mod de;
 pub mod encoding;
 pub mod error;
+pub mod format;
 pub(crate) mod internal;
 pub mod io;
 pub mod ser;
+pub mod xml;
 
+pub use error::{Error, ErrorContext, ErrorKind, Result};
+pub use format::{
+    from_bytes, from_reader, to_bytes, to_writer, to_writer_format, Format, FormatKind,
+};
 #[cfg(feature = "std")]
-pub use de::from_file;
-pub use de::from_reader;
-pub use error::{Error, ErrorKind, Result};
-#[cfg(feature = "std")]
-pub use ser::to_file;
-pub use ser::to_writer;
+pub use format::{from_file, to_file};
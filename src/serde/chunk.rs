@@ -0,0 +1,1450 @@
+//! A low-level reader and writer for the chunks making up a binary model. This allows inspecting
+//! or generating a file chunk by chunk, without building a full
+//! [`RbxModel`](crate::model::RbxModel).
+//!
+//! # Example
+//!
+//! ```ignore
+//! use rbxm::serde::chunk::{ChunkReader, ChunkView};
+//!
+//! let data = std::fs::read("./examples/BrickBase.rbxm")?;
+//! for chunk in ChunkReader::new(&*data)? {
+//!     let chunk = chunk?;
+//!     if let ChunkView::Inst(inst) = chunk.view()? {
+//!         println!("{}: {} instances", inst.class_name, inst.referents.len());
+//!     }
+//! }
+//! ```
+
+use crate::model::*;
+use crate::serde::de::DeserializerOptions;
+use crate::serde::encoding::{
+    chomp_bytes, chomp_len, decode_cumulative, decode_i32, encode_cumulative, Chomp,
+    ChompInterleaved, ChompInterleavedTransform, ChompTransform, Print, PrintInterleaved,
+    PrintInterleavedTransform, PrintTransform,
+};
+use crate::serde::internal::shared_string_hash;
+use crate::serde::io::{Read, Write};
+use crate::serde::ser::SerializerOptions;
+use crate::serde::{Error, Result};
+
+pub use crate::serde::internal::{RawContent, RawProperty};
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use uuid::Uuid;
+
+// "<roblox!" followed by 0x89FF (unknown), 0x0D0A (crlf), 0x1A0A (sublf?), 0x0000 (null)
+pub(crate) const BINARY_MAGIC_START: [u8; 16] = [
+    0x3C, 0x72, 0x6F, 0x62, 0x6C, 0x6F, 0x78, 0x21, 0x89, 0xFF, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00,
+];
+
+// "</roblox>"
+pub(crate) const BINARY_MAGIC_END: [u8; 9] = [0x3C, 0x2F, 0x72, 0x6F, 0x62, 0x6C, 0x6F, 0x78, 0x3E];
+
+/// The magic number at the start of a zstd frame, used to recognize zstd-compressed blocks
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+/// Read a length-prefixed byte string, checking its length against the configured limit before
+/// reading it
+fn chomp_blob<R: Read>(reader: &mut R, options: &DeserializerOptions) -> Result<Vec<u8>> {
+    let len = chomp_len(reader)?;
+    options.check_string(len)?;
+    chomp_bytes(reader, len)
+}
+
+/// Read a length-prefixed UTF-8 string, checking its length against the configured limit before
+/// reading it
+fn chomp_string<R: Read>(reader: &mut R, options: &DeserializerOptions) -> Result<String> {
+    String::from_utf8(chomp_blob(reader, options)?).map_err(|_| Error::invalid_string())
+}
+
+fn chomp_properties<R: Read>(
+    reader: &mut R,
+    num_props: usize,
+    prop_ty: u8,
+    options: &DeserializerOptions,
+) -> Result<Vec<RawProperty>> {
+    let mut properties = Vec::with_capacity(num_props);
+    for _ in 0..num_props {
+        let prop = match prop_ty {
+            1 => RawProperty::RawString(chomp_blob(reader, options)?),
+            2 => RawProperty::Bool(bool::chomp(reader)?),
+            3 => RawProperty::Int32(i32::chomp_transformed(reader)?),
+            4 => RawProperty::Float(f32::chomp_transformed(reader)?),
+            5 => RawProperty::Double(f64::chomp(reader)?),
+            6 => {
+                properties.extend(
+                    UDim::chomp_interleaved(reader, num_props)?
+                        .into_iter()
+                        .map(RawProperty::UDim),
+                );
+                break;
+            }
+            7 => {
+                properties.extend(
+                    UDim2::chomp_interleaved(reader, num_props)?
+                        .into_iter()
+                        .map(RawProperty::UDim2),
+                );
+                break;
+            }
+            8 => {
+                properties.extend(
+                    Ray::chomp_interleaved(reader, num_props)?
+                        .into_iter()
+                        .map(RawProperty::Ray),
+                );
+                break;
+            }
+            9 => RawProperty::Face(Faces::chomp(reader)?),
+            10 => RawProperty::Axis(Axes::chomp(reader)?),
+            11 => {
+                properties.extend(
+                    BrickColor::chomp_interleaved(reader, num_props)?
+                        .into_iter()
+                        .map(RawProperty::BrickColor),
+                );
+                break;
+            }
+            12 => RawProperty::Color3(Color3::chomp_transformed(reader)?),
+            13 => RawProperty::Vector2(Vector2::chomp(reader)?),
+            14 => RawProperty::Vector3(Vector3::chomp(reader)?),
+            16 => {
+                properties.extend(
+                    CFrame::chomp_interleaved(reader, num_props)?
+                        .into_iter()
+                        .map(RawProperty::CFrame),
+                );
+                break;
+            }
+            17 => {
+                properties.extend(
+                    CFrameQuat::chomp_interleaved(reader, num_props)?
+                        .into_iter()
+                        .map(RawProperty::CFrameQuat),
+                );
+                break;
+            }
+            18 => {
+                properties.extend(
+                    i32::chomp_interleaved(reader, num_props)?
+                        .into_iter()
+                        .map(RawProperty::Enum),
+                );
+                break;
+            }
+            19 => {
+                let mut ids = i32::chomp_interleaved(reader, num_props)?;
+                ids.iter_mut().for_each(|i| *i = decode_i32(*i as u32));
+                decode_cumulative(&mut ids);
+                properties.extend(ids.into_iter().map(RawProperty::InstanceRef));
+                break;
+            }
+            20 => RawProperty::Vector3Int16(Vector3Int16::chomp(reader)?),
+            21 => RawProperty::NumberSequence(NumberSequence::chomp(reader)?),
+            22 => RawProperty::ColorSequence(ColorSequence::chomp(reader)?),
+            23 => RawProperty::NumberRange(NumberRange::chomp(reader)?),
+            24 => {
+                properties.extend(
+                    Rect::chomp_interleaved(reader, num_props)?
+                        .into_iter()
+                        .map(RawProperty::Rect),
+                );
+                break;
+            }
+            25 => RawProperty::PhysicalProperties(PhysicalProperties::chomp(reader)?),
+            26 => RawProperty::Color3Uint8(Color3Uint8::chomp(reader)?),
+            27 => RawProperty::Int64(i64::chomp(reader)?),
+            28 => {
+                properties.extend(
+                    i32::chomp_interleaved(reader, num_props)?
+                        .into_iter()
+                        .map(RawProperty::RawSharedString),
+                );
+                break;
+            }
+            30 => {
+                properties.extend(
+                    <Option<CFrame>>::chomp_interleaved(reader, num_props)?
+                        .into_iter()
+                        .map(RawProperty::OptionalCFrame),
+                );
+                break;
+            }
+            31 => RawProperty::Uuid(Uuid::chomp(reader)?),
+            32 => RawProperty::Font(FontFace::chomp(reader)?),
+            33 => {
+                properties.extend(
+                    SecurityCapabilities::chomp_interleaved(reader, num_props)?
+                        .into_iter()
+                        .map(RawProperty::SecurityCapabilities),
+                );
+                break;
+            }
+            34 => {
+                properties.extend(
+                    RawContent::chomp_interleaved(reader, num_props)?
+                        .into_iter()
+                        .map(RawProperty::Content),
+                );
+                break;
+            }
+            _ => {
+                return Err(Error::unknown_property(prop_ty));
+            }
+        };
+
+        properties.push(prop);
+    }
+
+    Ok(properties)
+}
+
+/// Write the values of a property column, which must all be of the same type
+fn print_properties<W: Write>(writer: &mut W, properties: &[RawProperty]) -> Result<()> {
+    for prop in properties {
+        match prop {
+            RawProperty::RawString(blob) => <&[u8]>::print(writer, blob)?,
+            RawProperty::Bool(val) => bool::print(writer, *val)?,
+            RawProperty::Int32(val) => i32::print_transformed(writer, *val)?,
+            RawProperty::Float(val) => f32::print_transformed(writer, *val)?,
+            RawProperty::Double(val) => f64::print(writer, *val)?,
+            RawProperty::UDim(..) => {
+                UDim::print_interleaved(
+                    writer,
+                    &properties
+                        .iter()
+                        .cloned()
+                        .map(|i| {
+                            if let RawProperty::UDim(u) = i {
+                                u
+                            } else {
+                                unreachable!()
+                            }
+                        })
+                        .collect::<Vec<_>>(),
+                )?;
+                break;
+            }
+            RawProperty::UDim2(..) => {
+                UDim2::print_interleaved(
+                    writer,
+                    &properties
+                        .iter()
+                        .cloned()
+                        .map(|i| {
+                            if let RawProperty::UDim2(u) = i {
+                                u
+                            } else {
+                                unreachable!()
+                            }
+                        })
+                        .collect::<Vec<_>>(),
+                )?;
+                break;
+            }
+            RawProperty::Ray(..) => {
+                Ray::print_interleaved(
+                    writer,
+                    &properties
+                        .iter()
+                        .cloned()
+                        .map(|i| {
+                            if let RawProperty::Ray(r) = i {
+                                r
+                            } else {
+                                unreachable!()
+                            }
+                        })
+                        .collect::<Vec<_>>(),
+                )?;
+                break;
+            }
+            RawProperty::Face(val) => Faces::print(writer, val.clone())?,
+            RawProperty::Axis(val) => Axes::print(writer, val.clone())?,
+            RawProperty::BrickColor(..) => {
+                BrickColor::print_interleaved(
+                    writer,
+                    &properties
+                        .iter()
+                        .cloned()
+                        .map(|i| {
+                            if let RawProperty::BrickColor(bc) = i {
+                                bc
+                            } else {
+                                unreachable!()
+                            }
+                        })
+                        .collect::<Vec<_>>(),
+                )?;
+                break;
+            }
+            RawProperty::Color3(val) => Color3::print_transformed(writer, val.clone())?,
+            RawProperty::Vector2(val) => Vector2::print(writer, val.clone())?,
+            RawProperty::Vector3(val) => Vector3::print(writer, val.clone())?,
+            RawProperty::CFrame(..) => {
+                CFrame::print_interleaved(
+                    writer,
+                    &properties
+                        .iter()
+                        .cloned()
+                        .map(|i| {
+                            if let RawProperty::CFrame(c) = i {
+                                c
+                            } else {
+                                unreachable!()
+                            }
+                        })
+                        .collect::<Vec<_>>(),
+                )?;
+                break;
+            }
+            RawProperty::CFrameQuat(..) => {
+                CFrameQuat::print_interleaved(
+                    writer,
+                    &properties
+                        .iter()
+                        .map(|i| {
+                            if let RawProperty::CFrameQuat(c) = i {
+                                c.clone()
+                            } else {
+                                unreachable!()
+                            }
+                        })
+                        .collect::<Vec<_>>(),
+                )?;
+                break;
+            }
+            RawProperty::Enum(..) => {
+                i32::print_interleaved(
+                    writer,
+                    &properties
+                        .iter()
+                        .cloned()
+                        .map(|i| {
+                            if let RawProperty::Enum(e) = i {
+                                e
+                            } else {
+                                unreachable!()
+                            }
+                        })
+                        .collect::<Vec<_>>(),
+                )?;
+                break;
+            }
+            RawProperty::InstanceRef(..) => {
+                let mut ids = properties
+                    .iter()
+                    .map(|i| {
+                        if let RawProperty::InstanceRef(e) = i {
+                            *e
+                        } else {
+                            unreachable!()
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                encode_cumulative(&mut ids);
+                i32::print_interleaved_transformed(writer, &ids)?;
+                break;
+            }
+            RawProperty::Vector3Int16(val) => Vector3Int16::print(writer, val.clone())?,
+            RawProperty::NumberSequence(val) => {
+                NumberSequence::print(writer, val.clone())?;
+            }
+            RawProperty::ColorSequence(val) => {
+                ColorSequence::print(writer, val.clone())?;
+            }
+            RawProperty::NumberRange(val) => NumberRange::print(writer, val.clone())?,
+            RawProperty::Rect(..) => {
+                Rect::print_interleaved(
+                    writer,
+                    &properties
+                        .iter()
+                        .cloned()
+                        .map(|i| {
+                            if let RawProperty::Rect(c) = i {
+                                c
+                            } else {
+                                unreachable!()
+                            }
+                        })
+                        .collect::<Vec<_>>(),
+                )?;
+                break;
+            }
+            RawProperty::PhysicalProperties(val) => {
+                PhysicalProperties::print(writer, val.clone())?;
+            }
+            RawProperty::Color3Uint8(val) => Color3Uint8::print(writer, val.clone())?,
+            RawProperty::Int64(val) => i64::print(writer, *val)?,
+            RawProperty::RawSharedString(..) => {
+                i32::print_interleaved(
+                    writer,
+                    &properties
+                        .iter()
+                        .cloned()
+                        .map(|i| {
+                            if let RawProperty::RawSharedString(e) = i {
+                                e
+                            } else {
+                                unreachable!()
+                            }
+                        })
+                        .collect::<Vec<_>>(),
+                )?;
+                break;
+            }
+            RawProperty::OptionalCFrame(..) => {
+                <Option<CFrame>>::print_interleaved(
+                    writer,
+                    &properties
+                        .iter()
+                        .cloned()
+                        .map(|i| {
+                            if let RawProperty::OptionalCFrame(c) = i {
+                                c
+                            } else {
+                                unreachable!()
+                            }
+                        })
+                        .collect::<Vec<_>>(),
+                )?;
+                break;
+            }
+            RawProperty::Uuid(uuid) => Uuid::print(writer, *uuid)?,
+            RawProperty::Font(font) => FontFace::print(writer, font.clone())?,
+            RawProperty::SecurityCapabilities(..) => {
+                SecurityCapabilities::print_interleaved(
+                    writer,
+                    &properties
+                        .iter()
+                        .map(|i| {
+                            if let RawProperty::SecurityCapabilities(c) = i {
+                                *c
+                            } else {
+                                unreachable!()
+                            }
+                        })
+                        .collect::<Vec<_>>(),
+                )?;
+                break;
+            }
+            RawProperty::Content(..) => {
+                RawContent::print_interleaved(
+                    writer,
+                    &properties
+                        .iter()
+                        .map(|i| {
+                            if let RawProperty::Content(c) = i {
+                                c.clone()
+                            } else {
+                                unreachable!()
+                            }
+                        })
+                        .collect::<Vec<_>>(),
+                )?;
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The header at the start of a binary file, before any chunks
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FileHeader {
+    /// The number of distinct classes the file claims to contain
+    pub num_classes: i32,
+    /// The number of instances the file claims to contain
+    pub num_instances: i32,
+}
+
+/// The header of a single chunk, as stored in the file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkHeader {
+    /// The name of the chunk, such as `INST` or `PROP`, without any trailing null bytes
+    pub name: String,
+    /// The position of this chunk among all chunks in the file
+    pub index: usize,
+    /// The byte offset of the start of this chunk in the file
+    pub offset: u64,
+    /// The length of the chunk data as stored, or zero if it's stored uncompressed
+    pub compressed_len: usize,
+    /// The length of the chunk data once decompressed
+    pub uncompressed_len: usize,
+}
+
+/// A single chunk read from a binary file, with its data decompressed
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    /// The header this chunk was stored with
+    pub header: ChunkHeader,
+    /// The decompressed contents of the chunk
+    pub data: Vec<u8>,
+    /// The number of instances of the class a `PROP` chunk belongs to, which is needed to know
+    /// how many values it holds
+    class_size: Option<usize>,
+}
+
+impl Chunk {
+    /// Decode the contents of this chunk into a typed view
+    pub fn view(&self) -> Result<ChunkView> {
+        self.view_with_options(&DeserializerOptions::default())
+    }
+
+    /// Decode the contents of this chunk into a typed view, applying the string length limit from
+    /// a set of deserialization options
+    pub fn view_with_options(&self, options: &DeserializerOptions) -> Result<ChunkView> {
+        self.view_body(options)
+            .map_err(|err| self.with_context(err))
+    }
+
+    fn with_context(&self, err: Error) -> Error {
+        err.with_chunk(self.header.index, &self.header.name)
+            .with_offset(self.header.offset)
+    }
+
+    fn view_body(&self, options: &DeserializerOptions) -> Result<ChunkView> {
+        let reader = &mut (&self.data as &[u8]);
+
+        let view = match &*self.header.name {
+            "META" => {
+                let num_pairs = chomp_len(reader)?;
+                let mut entries = Vec::new();
+                for _ in 0..num_pairs {
+                    let key = chomp_string(reader, options)?;
+                    let value = chomp_string(reader, options)?;
+                    entries.push((key, value));
+                }
+                ChunkView::Meta(MetaChunk { entries })
+            }
+            "SSTR" => {
+                if i32::chomp(reader)? != 0 {
+                    return Err(Error::invalid_data("unexpected SSTR version"));
+                }
+                let num_strs = chomp_len(reader)?;
+                let mut strings = Vec::new();
+                for _ in 0..num_strs {
+                    let hash = <[u8; 16]>::chomp(reader)?;
+                    let data = chomp_blob(reader, options)?;
+                    strings.push(SharedStringEntry { hash, data });
+                }
+                ChunkView::Sstr(SstrChunk { strings })
+            }
+            "INST" => {
+                let class_index = i32::chomp(reader)?;
+                let class_name = chomp_string(reader, options)
+                    .map_err(|err| err.with_class_index(class_index))?;
+                let context = |err: Error| {
+                    err.with_class_index(class_index)
+                        .with_class_name(&class_name)
+                };
+
+                let is_service = bool::chomp(reader).map_err(context)?;
+                let instance_count = chomp_len(reader).map_err(context)?;
+                let mut referents =
+                    i32::chomp_interleaved_transformed(reader, instance_count).map_err(context)?;
+                decode_cumulative(&mut referents);
+
+                // Service classes are followed by a marker for whether each instance is a service
+                let service_markers = if is_service {
+                    let markers = referents
+                        .iter()
+                        .map(|_| bool::chomp(reader))
+                        .collect::<Result<_>>()
+                        .map_err(context)?;
+                    Some(markers)
+                } else {
+                    None
+                };
+
+                ChunkView::Inst(InstChunk {
+                    class_index,
+                    class_name,
+                    referents,
+                    service_markers,
+                })
+            }
+            "PROP" => {
+                let class_index = i32::chomp(reader)?;
+                let context = |err: Error| err.with_class_index(class_index);
+
+                let name = chomp_string(reader, options).map_err(context)?;
+                let context = |err: Error| context(err.with_property(&name));
+
+                let type_id = u8::chomp(reader).map_err(context)?;
+                let num_props = self
+                    .class_size
+                    .ok_or_else(|| context(Error::unknown_class(class_index)))?;
+                let values =
+                    chomp_properties(reader, num_props, type_id, options).map_err(context)?;
+
+                if !reader.is_empty() {
+                    return Err(context(Error::invalid_data(
+                        "property didn't consume whole block",
+                    )));
+                }
+
+                ChunkView::Prop(PropChunk {
+                    class_index,
+                    name,
+                    type_id,
+                    values,
+                })
+            }
+            "PRNT" => {
+                if u8::chomp(reader)? != 0 {
+                    return Err(Error::invalid_data("unexpected PRNT version"));
+                }
+                let len = chomp_len(reader)?;
+                let mut children = i32::chomp_interleaved_transformed(reader, len)?;
+                let mut parents = i32::chomp_interleaved_transformed(reader, len)?;
+
+                decode_cumulative(&mut children);
+                decode_cumulative(&mut parents);
+
+                ChunkView::Prnt(PrntChunk {
+                    links: children.into_iter().zip(parents).collect(),
+                })
+            }
+            "END" => ChunkView::End,
+            _ => ChunkView::Unknown,
+        };
+
+        Ok(view)
+    }
+}
+
+/// The decoded contents of a chunk, depending on its name
+#[derive(Debug, Clone)]
+pub enum ChunkView {
+    /// A `META` chunk, holding file metadata
+    Meta(MetaChunk),
+    /// An `SSTR` chunk, holding the shared strings referenced by properties
+    Sstr(SstrChunk),
+    /// An `INST` chunk, declaring a class and the instances belonging to it
+    Inst(InstChunk),
+    /// A `PROP` chunk, holding a single property for every instance of a class
+    Prop(PropChunk),
+    /// A `PRNT` chunk, holding the parent of each instance
+    Prnt(PrntChunk),
+    /// The `END` chunk, which marks the end of the file
+    End,
+    /// A chunk this crate doesn't know how to decode. Its raw data is still available on the
+    /// [`Chunk`].
+    Unknown,
+}
+
+/// The contents of a `META` chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetaChunk {
+    /// The key-value pairs, in the order they're stored
+    pub entries: Vec<(String, String)>,
+}
+
+/// A single shared string from an `SSTR` chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedStringEntry {
+    /// The MD5 hash stored with the string. Some tools write this as all zeroes.
+    pub hash: [u8; 16],
+    /// The contents of the string
+    pub data: Vec<u8>,
+}
+
+impl SharedStringEntry {
+    /// Create a new shared string, computing the hash of its contents
+    pub fn new(data: Vec<u8>) -> SharedStringEntry {
+        SharedStringEntry {
+            hash: shared_string_hash(&data),
+            data,
+        }
+    }
+}
+
+/// The contents of an `SSTR` chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SstrChunk {
+    /// The shared strings, indexed by their position
+    pub strings: Vec<SharedStringEntry>,
+}
+
+/// The contents of an `INST` chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstChunk {
+    /// The index identifying this class in `PROP` chunks
+    pub class_index: i32,
+    /// The name of the class
+    pub class_name: String,
+    /// The referent of each instance of this class
+    pub referents: Vec<i32>,
+    /// For service classes, whether each instance is a service. `None` if the class isn't
+    /// marked as a service.
+    pub service_markers: Option<Vec<bool>>,
+}
+
+/// The contents of a `PROP` chunk
+#[derive(Debug, Clone)]
+pub struct PropChunk {
+    /// The index of the class this property belongs to
+    pub class_index: i32,
+    /// The name of the property
+    pub name: String,
+    /// The type ID the values are stored as
+    pub type_id: u8,
+    /// The value for each instance of the class, in the same order as the referents in its
+    /// [`InstChunk`]
+    pub values: Vec<RawProperty>,
+}
+
+/// The contents of a `PRNT` chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrntChunk {
+    /// Pairs of child and parent referents. A parent of `-1` means the child is a root.
+    pub links: Vec<(i32, i32)>,
+}
+
+/// A chunk this crate doesn't know how to decode, kept on a model so that it can be written back
+/// out unchanged
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownChunk {
+    /// The name of the chunk
+    pub name: String,
+    /// The decompressed contents of the chunk
+    pub data: Vec<u8>,
+    /// The name of the last known chunk before this one, or `None` if it came before all of
+    /// them. The chunk is written back after the last chunk of that kind.
+    pub after: Option<String>,
+}
+
+/// An iterator over the chunks of a binary file. Iteration stops after the `END` chunk, or after
+/// the first error.
+pub struct ChunkReader<R> {
+    reader: R,
+    options: DeserializerOptions,
+    header: FileHeader,
+    class_sizes: BTreeMap<i32, usize>,
+    offset: u64,
+    index: usize,
+    decompressed: usize,
+    done: bool,
+}
+
+impl<R: Read> ChunkReader<R> {
+    /// Create a new chunk reader, reading the file header from the start of the reader
+    pub fn new(reader: R) -> Result<ChunkReader<R>> {
+        ChunkReader::with_options(reader, DeserializerOptions::default())
+    }
+
+    /// Create a new chunk reader with a set of options. Only the decompressed size and string
+    /// length limits apply when reading chunks.
+    pub fn with_options(mut reader: R, options: DeserializerOptions) -> Result<ChunkReader<R>> {
+        let magic = <[u8; 16]>::chomp(&mut reader)?;
+
+        if magic != BINARY_MAGIC_START {
+            return Err(Error::bad_magic());
+        }
+
+        let num_classes = i32::chomp(&mut reader)?;
+        let num_instances = i32::chomp(&mut reader)?;
+
+        let unknown = (i32::chomp(&mut reader)?, i32::chomp(&mut reader)?);
+
+        if unknown != (0, 0) {
+            return Err(Error::invalid_data("reserved header bytes weren't zero"));
+        }
+
+        Ok(ChunkReader {
+            reader,
+            options,
+            header: FileHeader {
+                num_classes,
+                num_instances,
+            },
+            class_sizes: BTreeMap::new(),
+            offset: 32,
+            index: 0,
+            decompressed: 0,
+            done: false,
+        })
+    }
+
+    /// Get the header read from the start of the file
+    pub fn header(&self) -> FileHeader {
+        self.header
+    }
+
+    fn chomp_chunk(&mut self) -> Result<Chunk> {
+        let offset = self.offset;
+        let index = self.index;
+        self.index += 1;
+
+        let name = self
+            .chomp_chunk_name()
+            .map_err(|err| err.with_offset(offset))?;
+        let context = |err: Error| err.with_chunk(index, &name).with_offset(offset);
+
+        let (compressed_len, uncompressed_len, data) = self.chomp_chunk_data().map_err(context)?;
+
+        let class_size = if name == "PROP" {
+            i32::chomp(&mut &*data)
+                .ok()
+                .and_then(|class_index| self.class_sizes.get(&class_index).copied())
+        } else {
+            None
+        };
+
+        let chunk = Chunk {
+            header: ChunkHeader {
+                name,
+                index,
+                offset,
+                compressed_len,
+                uncompressed_len,
+            },
+            data,
+            class_size,
+        };
+
+        match &*chunk.header.name {
+            "INST" => {
+                if let ChunkView::Inst(inst) = chunk.view_with_options(&self.options)? {
+                    self.class_sizes
+                        .insert(inst.class_index, inst.referents.len());
+                }
+            }
+            "END" => {
+                if chunk.header.compressed_len != 0 || chunk.data != BINARY_MAGIC_END {
+                    return Err(chunk.with_context(Error::bad_magic()));
+                }
+                self.done = true;
+            }
+            _ => (),
+        }
+
+        Ok(chunk)
+    }
+
+    fn chomp_chunk_name(&mut self) -> Result<String> {
+        let data = <[u8; 4]>::chomp(&mut self.reader)?;
+        self.offset += 4;
+
+        let first_zero = data.iter().copied().position(|b| b == 0).unwrap_or(4);
+
+        Ok(core::str::from_utf8(&data[..first_zero])
+            .map_err(|_| Error::invalid_string())?
+            .to_string())
+    }
+
+    fn chomp_chunk_data(&mut self) -> Result<(usize, usize, Vec<u8>)> {
+        let compressed = chomp_len(&mut self.reader)?;
+        let uncompressed = chomp_len(&mut self.reader)?;
+
+        if i32::chomp(&mut self.reader)? != 0 {
+            return Err(Error::invalid_data(
+                "reserved block header bytes weren't zero",
+            ));
+        }
+
+        self.decompressed = self.decompressed.saturating_add(uncompressed);
+        self.options.check_decompressed(self.decompressed)?;
+
+        // A compressed length of zero means the block is stored uncompressed
+        if compressed == 0 {
+            let data = chomp_bytes(&mut self.reader, uncompressed)?;
+            self.offset += 12 + uncompressed as u64;
+            return Ok((compressed, uncompressed, data));
+        }
+
+        let data = chomp_bytes(&mut self.reader, compressed)?;
+        self.offset += 12 + compressed as u64;
+
+        let out = if data.starts_with(&ZSTD_MAGIC) {
+            decompress_zstd(&data, uncompressed)?
+        } else {
+            // LZ4 can't expand data by more than a factor of 255, so anything claiming more is
+            // corrupt and would only cause an oversized allocation
+            if uncompressed / 255 > compressed {
+                return Err(Error::invalid_length(uncompressed as i64));
+            }
+            lz4_flex::block::decompress(&data, uncompressed).map_err(|_| Error::invalid_lz4())?
+        };
+
+        if out.len() != uncompressed {
+            return Err(Error::invalid_data(
+                "block didn't decompress to its stated size",
+            ));
+        }
+
+        Ok((compressed, uncompressed, out))
+    }
+}
+
+impl<R: Read> Iterator for ChunkReader<R> {
+    type Item = Result<Chunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let chunk = self.chomp_chunk();
+        if chunk.is_err() {
+            self.done = true;
+        }
+        Some(chunk)
+    }
+}
+
+/// A builder writing a binary file chunk by chunk, without going through an
+/// [`RbxModel`](crate::model::RbxModel). Chunks are checked for consistency with each other as
+/// they're written, and against the header's counts on [`ChunkWriter::finish`], so that the
+/// result can be read back.
+///
+/// A valid file has any `META` and `SSTR` chunks first, then every `INST` chunk, then the `PROP`
+/// chunks, and finally a `PRNT` chunk. The `END` chunk is written by [`ChunkWriter::finish`].
+/// Writing chunks out of that order, or finishing without a `PRNT` chunk, is an error.
+pub struct ChunkWriter<W> {
+    writer: W,
+    header: FileHeader,
+    options: SerializerOptions,
+    class_sizes: BTreeMap<i32, usize>,
+    stage: WriterStage,
+}
+
+/// How far through the required order of chunks a [`ChunkWriter`] is
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum WriterStage {
+    Header,
+    Inst,
+    Prop,
+    Prnt,
+}
+
+impl<W: Write> ChunkWriter<W> {
+    /// Create a new chunk writer, writing the file header to the start of the writer
+    pub fn new(writer: W, header: FileHeader) -> Result<ChunkWriter<W>> {
+        ChunkWriter::with_options(writer, header, SerializerOptions::default())
+    }
+
+    /// Create a new chunk writer with a set of options. Only the compression options apply when
+    /// writing chunks.
+    pub fn with_options(
+        mut writer: W,
+        header: FileHeader,
+        options: SerializerOptions,
+    ) -> Result<ChunkWriter<W>> {
+        writer.write_all(&BINARY_MAGIC_START)?;
+        i32::print(&mut writer, header.num_classes)?;
+        i32::print(&mut writer, header.num_instances)?;
+        i32::print(&mut writer, 0)?;
+        i32::print(&mut writer, 0)?;
+
+        Ok(ChunkWriter {
+            writer,
+            header,
+            options,
+            class_sizes: BTreeMap::new(),
+            stage: WriterStage::Header,
+        })
+    }
+
+    /// Check a chunk belonging to `stage` can be written next, without moving on to it
+    fn check_stage(&self, stage: WriterStage) -> Result<()> {
+        if self.stage > stage || self.stage == WriterStage::Prnt {
+            Err(Error::invalid_data("chunk written out of order"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Write a `META` chunk
+    pub fn write_meta(&mut self, meta: MetaChunk) -> Result<&mut Self> {
+        self.check_stage(WriterStage::Header)?;
+        let mut data = Vec::new();
+        i32::print(&mut data, meta.entries.len() as i32)?;
+        for (key, value) in meta.entries {
+            String::print(&mut data, key)?;
+            String::print(&mut data, value)?;
+        }
+        self.write_compressed("META", &data)
+    }
+
+    /// Write an `SSTR` chunk
+    pub fn write_sstr(&mut self, sstr: SstrChunk) -> Result<&mut Self> {
+        self.check_stage(WriterStage::Header)?;
+        let mut data = Vec::new();
+        i32::print(&mut data, 0)?;
+        i32::print(&mut data, sstr.strings.len() as i32)?;
+        for entry in sstr.strings {
+            data.write_all(&entry.hash)?;
+            <&[u8]>::print(&mut data, &entry.data)?;
+        }
+        self.write_compressed("SSTR", &data)
+    }
+
+    /// Write an `INST` chunk, declaring a class. Each class index may only be declared once, and
+    /// any service markers must have one entry per referent.
+    pub fn write_inst(&mut self, inst: InstChunk) -> Result<&mut Self> {
+        let InstChunk {
+            class_index,
+            class_name,
+            mut referents,
+            service_markers,
+        } = inst;
+
+        self.check_stage(WriterStage::Inst)?;
+        if self.class_sizes.contains_key(&class_index) {
+            return Err(Error::invalid_data("class declared more than once")
+                .with_class_index(class_index)
+                .with_class_name(&class_name));
+        }
+        if service_markers
+            .as_ref()
+            .is_some_and(|markers| markers.len() != referents.len())
+        {
+            return Err(Error::invalid_data(
+                "service markers don't match the instances of their class",
+            )
+            .with_class_index(class_index)
+            .with_class_name(&class_name));
+        }
+
+        self.class_sizes.insert(class_index, referents.len());
+        self.stage = WriterStage::Inst;
+
+        let mut data = Vec::new();
+        i32::print(&mut data, class_index)?;
+        String::print(&mut data, class_name)?;
+        bool::print(&mut data, service_markers.is_some())?;
+        i32::print(&mut data, referents.len() as i32)?;
+
+        encode_cumulative(&mut referents);
+        i32::print_interleaved_transformed(&mut data, &referents)?;
+
+        for marker in service_markers.into_iter().flatten() {
+            bool::print(&mut data, marker)?;
+        }
+
+        self.write_compressed("INST", &data)
+    }
+
+    /// Write a `PROP` chunk. Its class must already have been declared by an `INST` chunk, and
+    /// it must have one value of its type for every instance of that class.
+    pub fn write_prop(&mut self, prop: PropChunk) -> Result<&mut Self> {
+        let context = |err: Error| {
+            err.with_class_index(prop.class_index)
+                .with_property(&prop.name)
+        };
+
+        self.check_stage(WriterStage::Prop).map_err(context)?;
+        let class_size = *self
+            .class_sizes
+            .get(&prop.class_index)
+            .ok_or_else(|| context(Error::unknown_class(prop.class_index)))?;
+
+        if prop.values.len() != class_size {
+            return Err(context(Error::invalid_data(
+                "property doesn't have a value for every instance of its class",
+            )));
+        }
+        if prop
+            .values
+            .iter()
+            .any(|value| value.encode_ty() != prop.type_id)
+        {
+            return Err(context(Error::invalid_data(
+                "property values don't match the property type",
+            )));
+        }
+
+        let mut data = Vec::new();
+        i32::print(&mut data, prop.class_index)?;
+        String::print(&mut data, prop.name.clone())?;
+        u8::print(&mut data, prop.type_id)?;
+        print_properties(&mut data, &prop.values)?;
+
+        self.stage = WriterStage::Prop;
+        self.write_compressed("PROP", &data)
+    }
+
+    /// Write a `PRNT` chunk
+    pub fn write_prnt(&mut self, prnt: PrntChunk) -> Result<&mut Self> {
+        self.check_stage(WriterStage::Prnt)?;
+        let (mut children, mut parents): (Vec<_>, Vec<_>) = prnt.links.into_iter().unzip();
+        encode_cumulative(&mut children);
+        encode_cumulative(&mut parents);
+
+        let mut data = Vec::new();
+        u8::print(&mut data, 0)?;
+        i32::print(&mut data, children.len() as i32)?;
+        i32::print_interleaved_transformed(&mut data, &children)?;
+        i32::print_interleaved_transformed(&mut data, &parents)?;
+
+        self.stage = WriterStage::Prnt;
+        self.write_compressed("PRNT", &data)
+    }
+
+    /// Write a chunk with a name of up to four bytes and arbitrary contents. This can be used to
+    /// write chunks this crate doesn't otherwise know about, at any point before
+    /// [`ChunkWriter::finish`]. Chunks this crate does know about must be written with their own
+    /// method, so that their order can be checked.
+    pub fn write_raw(&mut self, name: &str, data: &[u8]) -> Result<&mut Self> {
+        if matches!(name, "META" | "SSTR" | "INST" | "PROP" | "PRNT" | "END") {
+            return Err(Error::invalid_data(
+                "known chunk written without its own method",
+            ));
+        }
+        self.write_compressed(name, data)
+    }
+
+    fn write_compressed(&mut self, name: &str, data: &[u8]) -> Result<&mut Self> {
+        let compressed = self.options.compression_for(name).compress(data);
+        self.write_chunk(name, data, compressed.as_deref())?;
+        Ok(self)
+    }
+
+    /// Write the `END` chunk, and return the underlying writer. A `PRNT` chunk must have been
+    /// written first, and the classes and instances written must match the counts in the header.
+    pub fn finish(mut self) -> Result<W> {
+        if self.stage != WriterStage::Prnt {
+            return Err(Error::invalid_data("file finished without a PRNT chunk"));
+        }
+        let num_instances = self.class_sizes.values().sum::<usize>();
+        if usize::try_from(self.header.num_classes) != Ok(self.class_sizes.len())
+            || usize::try_from(self.header.num_instances) != Ok(num_instances)
+        {
+            return Err(Error::invalid_data(
+                "header counts don't match the classes and instances written",
+            ));
+        }
+        // The end of the file is never compressed
+        self.write_chunk("END", &BINARY_MAGIC_END, None)?;
+        Ok(self.writer)
+    }
+
+    fn write_chunk(&mut self, name: &str, data: &[u8], compressed: Option<&[u8]>) -> Result<()> {
+        let mut padded_name = [0; 4];
+        padded_name
+            .get_mut(..name.len())
+            .ok_or_else(|| Error::invalid_data("chunk names can't be longer than four bytes"))?
+            .copy_from_slice(name.as_bytes());
+
+        // Uncompressed chunks are marked by a compressed length of zero
+        self.writer.write_all(&padded_name)?;
+        i32::print(&mut self.writer, compressed.map_or(0, <[u8]>::len) as i32)?;
+        i32::print(&mut self.writer, data.len() as i32)?;
+        i32::print(&mut self.writer, 0)?;
+        self.writer.write_all(compressed.unwrap_or(data))?;
+
+        Ok(())
+    }
+}
+
+/// Decompress zstd frames, growing the output as data is decoded rather than trusting the
+/// stated size up front. Fails as soon as the output passes that size.
+#[cfg(feature = "zstd")]
+fn decompress_zstd(mut data: &[u8], uncompressed: usize) -> Result<Vec<u8>> {
+    use ruzstd::decoding::{BlockDecodingStrategy, FrameDecoder};
+    use ruzstd::io::Read as _;
+
+    let mut decoder = FrameDecoder::new();
+    let mut out = Vec::new();
+    while !data.is_empty() {
+        decoder.init(&mut data).map_err(|_| Error::invalid_zstd())?;
+        loop {
+            decoder
+                .decode_blocks(&mut data, BlockDecodingStrategy::UptoBytes(64 * 1024))
+                .map_err(|_| Error::invalid_zstd())?;
+
+            let start = out.len();
+            let available = decoder.can_collect();
+            if start + available > uncompressed {
+                return Err(Error::invalid_data(
+                    "block didn't decompress to its stated size",
+                ));
+            }
+            out.resize(start + available, 0);
+            let read = decoder
+                .read(&mut out[start..])
+                .map_err(|_| Error::invalid_zstd())?;
+            out.truncate(start + read);
+
+            if decoder.is_finished() && decoder.can_collect() == 0 {
+                break;
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn decompress_zstd(_: &[u8], _: usize) -> Result<Vec<u8>> {
+    Err(Error::missing_feature("zstd"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serde::ser::to_bytes;
+
+    #[test]
+    fn test_chunk_reader() {
+        let model = RbxModel::new();
+        let mut props = BTreeMap::new();
+        props.insert(
+            String::from("Name"),
+            Property::TextString(String::from("Root")),
+        );
+        props.insert(String::from("Value"), Property::Int32(10));
+        let root = model
+            .tree()
+            .add_root(Instance::Other(String::from("Custom"), props.clone()));
+        model
+            .tree()
+            .try_get_mut(root)
+            .unwrap()
+            .new_child(Instance::Other(String::from("Custom"), props));
+        let data = to_bytes(&model).unwrap();
+
+        let reader = ChunkReader::new(&*data).unwrap();
+        assert_eq!(
+            reader.header(),
+            FileHeader {
+                num_classes: 1,
+                num_instances: 2,
+            }
+        );
+
+        let chunks = reader.collect::<Result<Vec<_>>>().unwrap();
+        let names = chunks
+            .iter()
+            .map(|chunk| &*chunk.header.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["META", "INST", "PROP", "PROP", "PRNT", "END"]);
+
+        match chunks[1].view().unwrap() {
+            ChunkView::Inst(inst) => {
+                assert_eq!(inst.class_name, "Custom");
+                assert_eq!(inst.referents, [0, 1]);
+                assert_eq!(inst.service_markers, None);
+            }
+            view => panic!("Expected INST chunk, got {:?}", view),
+        }
+
+        let value = chunks[2..4]
+            .iter()
+            .filter_map(|chunk| match chunk.view().unwrap() {
+                ChunkView::Prop(prop) if prop.name == "Value" => Some(prop),
+                _ => None,
+            })
+            .next()
+            .unwrap();
+        assert_eq!(value.type_id, 3);
+        assert!(matches!(
+            &*value.values,
+            [RawProperty::Int32(10), RawProperty::Int32(10)]
+        ));
+
+        match chunks[4].view().unwrap() {
+            ChunkView::Prnt(prnt) => assert_eq!(prnt.links, [(0, -1), (1, 0)]),
+            view => panic!("Expected PRNT chunk, got {:?}", view),
+        }
+    }
+
+    #[test]
+    fn test_instance_ref_encoding() {
+        // References are stored as the transformed difference from the previous one
+        let refs = [3, -1, 4, 4];
+        let mut bytes = Vec::new();
+        print_properties(&mut bytes, &refs.map(RawProperty::InstanceRef)).unwrap();
+
+        let deltas = i32::chomp_interleaved_transformed(&mut &*bytes, 4).unwrap();
+        assert_eq!(deltas, [3, -4, 5, 0]);
+
+        let props = chomp_properties(&mut &*bytes, 4, 19, &DeserializerOptions::default()).unwrap();
+        assert!(props
+            .iter()
+            .zip(refs)
+            .all(|(prop, id)| matches!(prop, RawProperty::InstanceRef(val) if *val == id)));
+    }
+
+    #[test]
+    fn test_chunk_writer() {
+        let header = FileHeader {
+            num_classes: 1,
+            num_instances: 2,
+        };
+        let mut writer = ChunkWriter::new(Vec::new(), header).unwrap();
+        writer
+            .write_inst(InstChunk {
+                class_index: 0,
+                class_name: String::from("Custom"),
+                referents: vec![0, 1],
+                service_markers: None,
+            })
+            .unwrap();
+
+        // Properties must match their class and type
+        let err = writer
+            .write_prop(PropChunk {
+                class_index: 0,
+                name: String::from("Value"),
+                type_id: 3,
+                values: vec![RawProperty::Int32(10)],
+            })
+            .map(drop)
+            .unwrap_err();
+        assert_eq!(err.context.property_name.as_deref(), Some("Value"));
+        let err = writer
+            .write_prop(PropChunk {
+                class_index: 1,
+                name: String::from("Value"),
+                type_id: 3,
+                values: vec![],
+            })
+            .map(drop)
+            .unwrap_err();
+        assert!(matches!(err.kind, crate::serde::ErrorKind::UnknownClass(1)));
+
+        writer
+            .write_prop(PropChunk {
+                class_index: 0,
+                name: String::from("Name"),
+                type_id: 1,
+                values: vec![
+                    RawProperty::RawString(b"Root".to_vec()),
+                    RawProperty::RawString(b"Child".to_vec()),
+                ],
+            })
+            .unwrap()
+            .write_prnt(PrntChunk {
+                links: vec![(0, -1), (1, 0)],
+            })
+            .unwrap();
+
+        let data = writer.finish().unwrap();
+        let model = crate::serde::de::from_bytes(&data).unwrap();
+        assert_eq!(model.get_path("Root/Child").unwrap().name(), "Child");
+    }
+
+    #[test]
+    fn test_chunk_writer_order() {
+        let header = FileHeader {
+            num_classes: 1,
+            num_instances: 1,
+        };
+        let inst = InstChunk {
+            class_index: 0,
+            class_name: String::from("Custom"),
+            referents: vec![0],
+            service_markers: None,
+        };
+        let prop = PropChunk {
+            class_index: 0,
+            name: String::from("Name"),
+            type_id: 1,
+            values: vec![RawProperty::RawString(b"Root".to_vec())],
+        };
+
+        // Nothing may be finished without a PRNT chunk
+        let mut writer = ChunkWriter::new(Vec::new(), header).unwrap();
+        writer.write_inst(inst.clone()).unwrap();
+        assert!(writer.finish().is_err());
+
+        // Chunks can't go back to an earlier stage
+        let mut writer = ChunkWriter::new(Vec::new(), header).unwrap();
+        writer
+            .write_inst(inst.clone())
+            .unwrap()
+            .write_prop(prop.clone())
+            .unwrap();
+        assert!(writer.write_inst(inst).is_err());
+        assert!(writer.write_meta(MetaChunk { entries: vec![] }).is_err());
+        assert!(writer.write_raw("INST", &[]).is_err());
+        writer
+            .write_raw("SIGN", &[])
+            .unwrap()
+            .write_prnt(PrntChunk {
+                links: vec![(0, -1)],
+            })
+            .unwrap();
+        assert!(writer.write_prop(prop).is_err());
+        assert!(writer
+            .write_prnt(PrntChunk {
+                links: vec![(0, -1)],
+            })
+            .is_err());
+        writer.write_raw("SIGN", &[]).unwrap();
+        assert!(writer.finish().is_ok());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_stated_size() {
+        let data = vec![7; 300 * 1024];
+        let compressed =
+            ruzstd::encoding::compress_to_vec(&*data, ruzstd::encoding::CompressionLevel::Fastest);
+
+        assert_eq!(decompress_zstd(&compressed, data.len()).unwrap(), data);
+        // A small frame claiming far more data doesn't allocate it up front
+        assert_eq!(
+            decompress_zstd(&compressed, i32::MAX as usize).unwrap(),
+            data
+        );
+        assert!(decompress_zstd(&compressed, data.len() - 1).is_err());
+    }
+
+    #[test]
+    fn test_chunk_writer_header() {
+        let header = FileHeader {
+            num_classes: 1,
+            num_instances: 2,
+        };
+        let inst = InstChunk {
+            class_index: 0,
+            class_name: String::from("Custom"),
+            referents: vec![i32::MIN, i32::MAX],
+            service_markers: None,
+        };
+        let prnt = PrntChunk {
+            links: vec![(i32::MIN, -1), (i32::MAX, i32::MIN)],
+        };
+
+        // A class may only be declared once
+        let mut writer = ChunkWriter::new(Vec::new(), header).unwrap();
+        writer.write_inst(inst.clone()).unwrap();
+        assert!(writer.write_inst(inst.clone()).is_err());
+
+        // The header must match what was written
+        let mut writer = ChunkWriter::new(
+            Vec::new(),
+            FileHeader {
+                num_classes: 1,
+                num_instances: 1,
+            },
+        )
+        .unwrap();
+        writer
+            .write_inst(inst.clone())
+            .unwrap()
+            .write_prnt(prnt.clone())
+            .unwrap();
+        assert!(writer.finish().is_err());
+
+        let mut writer = ChunkWriter::new(Vec::new(), header).unwrap();
+        writer.write_inst(inst).unwrap().write_prnt(prnt).unwrap();
+        let data = writer.finish().unwrap();
+
+        let referents = ChunkReader::new(&*data)
+            .unwrap()
+            .find_map(|chunk| match chunk.unwrap().view().unwrap() {
+                ChunkView::Inst(inst) => Some(inst.referents),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(referents, [i32::MIN, i32::MAX]);
+    }
+}
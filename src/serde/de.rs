@@ -1,458 +1,529 @@
 //! The deserialization implementation for an RBXM
 
 use crate::model::*;
-use crate::serde::encoding::{
-    decode_cumulative, decode_i32, Chomp, ChompInterleaved, ChompInterleavedTransform,
-    ChompTransform,
-};
-use crate::serde::internal::RawProperty;
+use crate::serde::chunk::{ChunkReader, ChunkView, UnknownChunk};
+use crate::serde::format::Format;
+use crate::serde::internal::{shared_string_hash, RawContent, RawProperty};
 use crate::serde::io::Read;
-use crate::serde::{Error, Result};
+use crate::serde::{Error, ErrorKind, Result};
 use crate::tree::Tree;
 
 use alloc::collections::BTreeMap;
 use alloc::collections::BTreeSet;
-use alloc::string::{String, ToString};
-use alloc::vec;
+use alloc::string::String;
 use alloc::vec::Vec;
-use uuid::Uuid;
-
-fn chomp_properties<R: Read>(
-    reader: &mut R,
-    num_props: usize,
-    prop_ty: u8,
-) -> Result<Vec<RawProperty>> {
-    let mut properties = Vec::with_capacity(num_props);
-    for _ in 0..num_props {
-        let prop = match prop_ty {
-            1 => RawProperty::RawString(<Vec<u8>>::chomp(reader)?),
-            2 => RawProperty::Bool(bool::chomp(reader)?),
-            3 => RawProperty::Int32(i32::chomp_transformed(reader)?),
-            4 => RawProperty::Float(f32::chomp_transformed(reader)?),
-            5 => RawProperty::Double(f64::chomp(reader)?),
-            6 => {
-                properties.extend(
-                    UDim::chomp_interleaved(reader, num_props)?
-                        .into_iter()
-                        .map(RawProperty::UDim),
-                );
-                break;
-            }
-            7 => {
-                properties.extend(
-                    UDim2::chomp_interleaved(reader, num_props)?
-                        .into_iter()
-                        .map(RawProperty::UDim2),
-                );
-                break;
-            }
-            8 => {
-                properties.extend(
-                    Ray::chomp_interleaved(reader, num_props)?
-                        .into_iter()
-                        .map(RawProperty::Ray),
-                );
-                break;
-            }
-            9 => RawProperty::Face(Faces::chomp(reader)?),
-            10 => RawProperty::Axis(Axes::chomp(reader)?),
-            11 => {
-                properties.extend(
-                    BrickColor::chomp_interleaved(reader, num_props)?
-                        .into_iter()
-                        .map(RawProperty::BrickColor),
-                );
-                break;
-            }
-            12 => RawProperty::Color3(Color3::chomp_transformed(reader)?),
-            13 => RawProperty::Vector2(Vector2::chomp(reader)?),
-            14 => RawProperty::Vector3(Vector3::chomp(reader)?),
-            16 => {
-                properties.extend(
-                    CFrame::chomp_interleaved(reader, num_props)?
-                        .into_iter()
-                        .map(RawProperty::CFrame),
-                );
-                break;
-            }
-            17 => unimplemented!("Quaternions not supported"),
-            18 => {
-                properties.extend(
-                    i32::chomp_interleaved(reader, num_props)?
-                        .into_iter()
-                        .map(RawProperty::Enum),
-                );
-                break;
-            }
-            19 => {
-                let mut ids = i32::chomp_interleaved(reader, num_props)?;
-                ids.iter_mut().for_each(|i| *i = decode_i32(*i as u32));
-                decode_cumulative(&mut ids);
-                properties.extend(ids.into_iter().map(RawProperty::InstanceRef));
-                break;
-            }
-            20 => RawProperty::Vector3Int16(Vector3Int16::chomp(reader)?),
-            21 => RawProperty::NumberSequence(NumberSequence::chomp(reader)?),
-            22 => RawProperty::ColorSequence(ColorSequence::chomp(reader)?),
-            23 => RawProperty::NumberRange(NumberRange::chomp(reader)?),
-            24 => {
-                properties.extend(
-                    Rect::chomp_interleaved(reader, num_props)?
-                        .into_iter()
-                        .map(RawProperty::Rect),
-                );
-                break;
-            }
-            25 => RawProperty::PhysicalProperties(PhysicalProperties::chomp(reader)?),
-            26 => RawProperty::Color3Uint8(Color3Uint8::chomp(reader)?),
-            27 => RawProperty::Int64(i64::chomp(reader)?),
-            28 => {
-                properties.extend(
-                    i32::chomp_interleaved(reader, num_props)?
-                        .into_iter()
-                        .map(RawProperty::RawSharedString),
-                );
-                break;
-            }
-            30 => {
-                properties.extend(
-                    Pivot::chomp_interleaved(reader, num_props)?
-                        .into_iter()
-                        .map(RawProperty::Pivot),
-                );
-                break;
-            }
-            31 => RawProperty::Uuid(Uuid::chomp(reader)?),
-            32 => RawProperty::Font(FontFace::chomp(reader)?),
-            _ => {
-                return Err(Error::unknown_property(prop_ty));
-            }
-        };
-
-        properties.push(prop);
-    }
-
-    Ok(properties)
-}
+use core::cell::RefCell;
 
+/// Intermediate model information shared by all file formats, before instances are built and
+/// references are resolved
 #[derive(Default, Debug)]
-struct RawInfo {
-    meta: BTreeMap<String, String>,
-    shared_strs: Vec<Vec<u8>>,
-    class_ids: BTreeMap<i32, Vec<i32>>,
-    instances: BTreeMap<i32, String>,
-    raw_props: BTreeMap<i32, BTreeMap<String, RawProperty>>,
-    parent_info: BTreeMap<i32, i32>,
-    child_info: BTreeMap<i32, Vec<i32>>,
+pub(crate) struct RawInfo {
+    pub(crate) meta: BTreeMap<String, String>,
+    pub(crate) shared_strs: Vec<Vec<u8>>,
+    pub(crate) class_ids: BTreeMap<i32, Vec<i32>>,
+    pub(crate) instances: BTreeMap<i32, String>,
+    pub(crate) raw_props: BTreeMap<i32, BTreeMap<String, RawProperty>>,
+    pub(crate) parent_info: BTreeMap<i32, i32>,
+    pub(crate) child_info: BTreeMap<i32, Vec<i32>>,
+    pub(crate) services: BTreeSet<i32>,
+    pub(crate) unknown_chunks: Vec<UnknownChunk>,
+    pub(crate) warnings: Vec<Error>,
 }
 
-/// Necessary state for deserializing a value
-pub struct Deserializer<R> {
-    reader: R,
-    raw_info: RawInfo,
+impl RawInfo {
+    /// Report a shared string whose stored hash doesn't match its contents. This fails strict
+    /// deserialization, and is otherwise reported as a warning.
+    pub(crate) fn hash_mismatch(
+        &mut self,
+        index: usize,
+        options: &DeserializerOptions,
+    ) -> Result<()> {
+        let err = Error::shared_string_hash_mismatch(index);
+        match options.strictness {
+            Strictness::Strict => Err(err),
+            Strictness::Lenient => {
+                self.warnings.push(err);
+                Ok(())
+            }
+        }
+    }
 }
 
-impl<R: Read> Deserializer<R> {
-    // "<roblox!" followed by 0x89FF (unknown), 0x0D0A (crlf), 0x1A0A (sublf?), 0x0000 (null)
-    const BINARY_MAGIC_START: [u8; 16] = [
-        0x3C, 0x72, 0x6F, 0x62, 0x6C, 0x6F, 0x78, 0x21, 0x89, 0xFF, 0x0D, 0x0A, 0x1A, 0x0A, 0x00,
-        0x00,
-    ];
+/// How strictly instances must match their expected structure during deserialization
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Strictness {
+    /// Any instance with missing or mistyped properties fails deserialization
+    #[default]
+    Strict,
+    /// Instances with missing or mistyped properties fall back to
+    /// [`Instance::Other`], keeping all their properties. The error that would have been
+    /// returned is reported as a warning instead.
+    Lenient,
+}
 
-    // "</roblox>"
-    const BINARY_MAGIC_END: [u8; 9] = [0x3C, 0x2F, 0x72, 0x6F, 0x62, 0x6C, 0x6F, 0x78, 0x3E];
+/// Options controlling how a model is deserialized.
+///
+/// The limits default to `None`, meaning unlimited. Setting them allows safely reading models
+/// from untrusted sources, as input exceeding a limit fails before the memory for it is
+/// allocated.
+#[derive(Debug, Clone, Default)]
+pub struct DeserializerOptions {
+    /// How to handle instances that don't match their expected structure
+    pub strictness: Strictness,
+    /// The maximum total size of decompressed data, in bytes. Input which is read whole before
+    /// being parsed, such as XML or input to format detection, counts towards it in full.
+    pub max_decompressed_bytes: Option<usize>,
+    /// The maximum number of instances in the model
+    pub max_instances: Option<usize>,
+    /// The maximum number of distinct classes in the model
+    pub max_classes: Option<usize>,
+    /// The maximum length of any single string, in bytes
+    pub max_string_length: Option<usize>,
+    /// The maximum depth of the instance tree, where root instances have a depth of 1. XML
+    /// documents are always limited to 256 levels of nested elements, whatever this is set to.
+    pub max_tree_depth: Option<usize>,
+    /// Check each shared string against the hash stored with it. Binary files with an all-zero
+    /// hash, as written by some tools, aren't checked.
+    pub verify_shared_string_hashes: bool,
+}
 
-    /// Create a new deserializer from a reader and if necessary any other state
-    pub fn new(reader: R) -> Deserializer<R> {
-        Deserializer {
-            reader,
-            raw_info: RawInfo::default(),
+impl DeserializerOptions {
+    pub(crate) fn check_decompressed(&self, total: usize) -> Result<()> {
+        match self.max_decompressed_bytes {
+            Some(limit) if total > limit => Err(Error::decompressed_size_exceeded(limit)),
+            _ => Ok(()),
         }
     }
 
-    /// Deserialize a model from the input stream
-    pub fn deserialize(mut self) -> Result<RbxModel> {
-        let magic = <[u8; 16]>::chomp(&mut self.reader)?;
+    /// Read the whole of an input, failing without reading further once it's larger than the
+    /// decompressed size limit
+    pub(crate) fn read_limited<R: Read>(&self, reader: &mut R) -> Result<Vec<u8>> {
+        // Read at most one byte past the limit, enough to tell that it was exceeded
+        let limit = self
+            .max_decompressed_bytes
+            .map_or(usize::MAX, |limit| limit.saturating_add(1));
+        let mut data = Vec::new();
+        reader.read_to_limit(&mut data, limit)?;
+        self.check_decompressed(data.len())?;
+        Ok(data)
+    }
 
-        if magic != Self::BINARY_MAGIC_START {
-            return Err(Error::bad_magic());
+    pub(crate) fn check_instances(&self, count: usize) -> Result<()> {
+        match self.max_instances {
+            Some(limit) if count > limit => Err(Error::too_many_instances(limit)),
+            _ => Ok(()),
         }
+    }
 
-        let num_classes = i32::chomp(&mut self.reader)?;
-        let num_instances = i32::chomp(&mut self.reader)?;
-
-        let unknown = (i32::chomp(&mut self.reader)?, i32::chomp(&mut self.reader)?);
-
-        debug_assert_eq!(unknown, (0, 0));
-
-        while self.chomp_block()? {}
-
-        let magic_end = <[u8; 9]>::chomp(&mut self.reader)?;
-
-        if magic_end != Self::BINARY_MAGIC_END {
-            return Err(Error::bad_magic());
+    pub(crate) fn check_classes(&self, count: usize) -> Result<()> {
+        match self.max_classes {
+            Some(limit) if count > limit => Err(Error::too_many_classes(limit)),
+            _ => Ok(()),
         }
+    }
 
-        debug_assert_eq!(self.raw_info.class_ids.len(), num_classes as usize);
-        debug_assert_eq!(self.raw_info.instances.len(), num_instances as usize);
+    pub(crate) fn check_string(&self, len: usize) -> Result<()> {
+        match self.max_string_length {
+            Some(limit) if len > limit => Err(Error::string_too_long(limit)),
+            _ => Ok(()),
+        }
+    }
 
-        self.make_model()
+    pub(crate) fn check_depth(&self, depth: usize) -> Result<()> {
+        match self.max_tree_depth {
+            Some(limit) if depth > limit => Err(Error::tree_too_deep(limit)),
+            _ => Ok(()),
+        }
     }
+}
 
-    fn make_model(self) -> Result<RbxModel> {
-        let RawInfo {
-            meta,
-            shared_strs,
-            class_ids: _,
-            instances,
-            mut raw_props,
-            parent_info,
-            child_info,
-        } = self.raw_info;
+/// Whether an error building an instance was caused by its properties not matching the expected
+/// structure, rather than invalid data
+fn is_structure_error(err: &Error) -> bool {
+    matches!(
+        err.kind,
+        ErrorKind::MissingProperty(..)
+            | ErrorKind::WrongPropertyType(..)
+            | ErrorKind::UnknownVariant(..)
+            | ErrorKind::InvalidString
+    )
+}
 
-        let mut id_key = BTreeMap::new();
+/// Ensure no instance is its own ancestor, which would make the model impossible to traverse,
+/// and that the tree isn't nested deeper than the configured limit
+fn check_tree(parent_info: &BTreeMap<i32, i32>, options: &DeserializerOptions) -> Result<()> {
+    // The depth of every instance checked so far, with roots at a depth of 1
+    let mut depths = BTreeMap::new();
+    for &start in parent_info.keys() {
+        let mut path = Vec::new();
+        let mut on_path = BTreeSet::new();
+        let mut current = start;
+        let base_depth = loop {
+            if current == -1 {
+                break 0;
+            }
+            if let Some(&depth) = depths.get(&current) {
+                break depth;
+            }
+            if !on_path.insert(current) {
+                return Err(Error::inconsistent_tree().with_instance(current));
+            }
+            path.push(current);
+            current = parent_info.get(&current).copied().unwrap_or(-1);
+        };
 
-        let tree = Tree::new();
+        for (idx, &id) in path.iter().rev().enumerate() {
+            let depth = base_depth + idx + 1;
+            options
+                .check_depth(depth)
+                .map_err(|err| err.with_instance(id))?;
+            depths.insert(id, depth);
+        }
+    }
+    Ok(())
+}
 
-        for &id in instances.keys() {
+/// Build a full model from raw information, constructing each instance and resolving references
+/// between them. Returns the model along with any warnings produced.
+pub(crate) fn make_model(
+    raw_info: RawInfo,
+    options: &DeserializerOptions,
+) -> Result<(RbxModel, Vec<Error>)> {
+    let RawInfo {
+        meta,
+        shared_strs,
+        class_ids: _,
+        instances,
+        mut raw_props,
+        parent_info,
+        child_info,
+        services,
+        unknown_chunks,
+        mut warnings,
+    } = raw_info;
+
+    let mut id_key = BTreeMap::new();
+
+    let tree = Tree::new();
+
+    // Instances start out as roots, so create the real roots first in the order they're listed
+    let root_order = child_info.get(&-1).into_iter().flatten();
+    for &id in root_order.chain(instances.keys()) {
+        if instances.contains_key(&id) && !id_key.contains_key(&id) {
             id_key.insert(
                 id,
                 tree.add_root(Instance::Other(String::new(), BTreeMap::new())),
             );
         }
+    }
 
-        // Do reference resolution, and populate information
-        instances
-            .iter()
-            .try_for_each::<_, Result<()>>(|(&id, class_name)| {
-                let raw_props = raw_props
-                    .remove(&id)
-                    .ok_or_else(|| Error::unknown_instance(id))?;
-
-                let props = raw_props
-                    .into_iter()
-                    .map(|(name, value)| {
-                        let prop = match value {
-                            RawProperty::RawString(blob) => String::from_utf8(blob).map_or_else(
-                                |err| Property::BinaryString(err.into_bytes()),
-                                Property::TextString,
-                            ),
-                            RawProperty::RawSharedString(shared_id) => {
-                                let blob = &shared_strs[shared_id as usize];
-                                String::from_utf8(blob.clone()).map_or_else(
-                                    |err| Property::SharedBinaryString(err.into_bytes()),
-                                    Property::SharedTextString,
-                                )
-                            }
-                            RawProperty::InstanceRef(ref_id) => {
-                                let inst_ref = if ref_id == -1 {
-                                    InstanceRef::Null
-                                } else {
-                                    let key = id_key
-                                        .get(&ref_id)
-                                        .ok_or_else(|| Error::unknown_instance(ref_id))?;
-                                    InstanceRef::Item(*key)
-                                };
-
-                                Property::InstanceRef(inst_ref)
-                            }
-                            prop => prop.into_real(),
-                        };
-
-                        Ok((name, prop))
-                    })
-                    .collect::<Result<_>>()?;
-
-                let mut inst = tree.try_get_mut(id_key[&id]).unwrap();
-                *inst = Instance::make_instance(class_name, props)?;
+    // Do reference resolution, and populate information
+    instances
+        .iter()
+        .try_for_each::<_, Result<()>>(|(&id, class_name)| {
+            let context = |err: Error| err.with_instance(id).with_class_name(class_name);
 
-                Ok(())
-            })?;
+            let raw_props = raw_props
+                .remove(&id)
+                .ok_or_else(|| context(Error::unknown_instance(id)))?;
+
+            let props = raw_props
+                .into_iter()
+                .map(|(name, value)| {
+                    let prop = match value {
+                        RawProperty::RawString(blob) => String::from_utf8(blob).map_or_else(
+                            |err| Property::BinaryString(err.into_bytes()),
+                            Property::TextString,
+                        ),
+                        RawProperty::RawSharedString(shared_id) => {
+                            let blob = usize::try_from(shared_id)
+                                .ok()
+                                .and_then(|idx| shared_strs.get(idx))
+                                .ok_or_else(|| {
+                                    context(
+                                        Error::unknown_shared_string(shared_id)
+                                            .with_property(&name),
+                                    )
+                                })?;
+                            String::from_utf8(blob.clone()).map_or_else(
+                                |err| Property::SharedBinaryString(err.into_bytes()),
+                                Property::SharedTextString,
+                            )
+                        }
+                        RawProperty::InstanceRef(ref_id) => {
+                            let inst_ref = if ref_id == -1 {
+                                InstanceRef::Null
+                            } else {
+                                let key = id_key.get(&ref_id).ok_or_else(|| {
+                                    context(Error::unknown_instance(ref_id).with_property(&name))
+                                })?;
+                                InstanceRef::Item(*key)
+                            };
+
+                            Property::InstanceRef(inst_ref)
+                        }
+                        RawProperty::Content(RawContent::Object(ref_id)) => {
+                            let key = id_key.get(&ref_id).ok_or_else(|| {
+                                context(Error::unknown_instance(ref_id).with_property(&name))
+                            })?;
+                            Property::Content(Content::Object(*key))
+                        }
+                        prop => prop.into_real(),
+                    };
+
+                    Ok((name, prop))
+                })
+                .collect::<Result<_>>()?;
+
+            let inst = match options.strictness {
+                Strictness::Strict => {
+                    Instance::make_instance(class_name, props).map_err(context)?
+                }
+                // Check before building, so the properties are still whole to fall back on
+                Strictness::Lenient => match Instance::check_instance(class_name, &props) {
+                    Ok(()) => Instance::make_instance(class_name, props).map_err(context)?,
+                    Err(err) if is_structure_error(&err) => {
+                        warnings.push(context(err));
+                        Instance::Other(class_name.clone(), props)
+                    }
+                    Err(err) => return Err(context(err)),
+                },
+            };
+
+            *tree
+                .try_get_mut(id_key[&id])
+                .map_err(|_| Error::inconsistent_tree())? = inst;
+
+            Ok(())
+        })?;
+
+    check_tree(&parent_info, options)?;
 
-        for (child, parent) in parent_info.into_iter().filter(|&(_, parent)| parent != -1) {
-            let parent_key = *id_key
-                .get(&parent)
-                .ok_or_else(|| Error::unknown_instance(parent))?;
+    // Children are added in the order they're listed, which keeps sibling order
+    for (&parent, children) in child_info.iter().filter(|&(&parent, _)| parent != -1) {
+        let parent_key = *id_key
+            .get(&parent)
+            .ok_or_else(|| Error::unknown_instance(parent))?;
+        for &child in children {
             let child_key = *id_key
                 .get(&child)
                 .ok_or_else(|| Error::unknown_instance(child))?;
-            let mut parent = tree.try_get_mut(parent_key).unwrap();
-            let child = tree.try_get(child_key).unwrap();
+            tree.set_child(parent_key, child_key);
+        }
+    }
 
-            parent.add_child(&child);
+    for (child, parent) in parent_info {
+        let child_key = *id_key
+            .get(&child)
+            .ok_or_else(|| Error::unknown_instance(child))?;
+        let expected_parent = match parent {
+            -1 => None,
+            parent => Some(
+                *id_key
+                    .get(&parent)
+                    .ok_or_else(|| Error::unknown_instance(parent))?,
+            ),
+        };
+
+        if tree.parent_key_of(child_key) != expected_parent {
+            return Err(Error::inconsistent_tree().with_instance(child));
         }
+    }
 
-        for (parent, children) in child_info.into_iter().filter(|&(parent, _)| parent != -1) {
-            let parent_key = *id_key
-                .get(&parent)
-                .ok_or_else(|| Error::unknown_instance(parent))?;
-            let parent = tree.try_get(parent_key).unwrap();
+    let services = services
+        .into_iter()
+        .map(|id| {
+            id_key
+                .get(&id)
+                .copied()
+                .ok_or_else(|| Error::unknown_instance(id))
+        })
+        .collect::<Result<_>>()?;
+
+    let model = RbxModel {
+        meta,
+        nodes: tree,
+        format: Format::default(),
+        services,
+        unknown_chunks,
+        tag_index: RefCell::default(),
+    };
+
+    Ok((model, warnings))
+}
 
-            let expected_children = children
-                .into_iter()
-                .map(|i| {
-                    id_key
-                        .get(&i)
-                        .copied()
-                        .ok_or_else(|| Error::unknown_instance(i))
-                })
-                .collect::<Result<BTreeSet<_>>>()?;
+/// Necessary state for deserializing a value
+pub struct Deserializer<R> {
+    reader: R,
+    raw_info: RawInfo,
+    options: DeserializerOptions,
+    class_names: BTreeMap<i32, String>,
+}
 
-            let real_children = parent
-                .children()
-                .into_iter()
-                .map(|r| r.map(|r| r.key()))
-                .collect::<core::result::Result<BTreeSet<_>, _>>()
-                .unwrap();
+impl<R: Read> Deserializer<R> {
+    /// Create a new deserializer from a reader and if necessary any other state
+    pub fn new(reader: R) -> Deserializer<R> {
+        Deserializer::with_options(reader, DeserializerOptions::default())
+    }
 
-            if expected_children != real_children {
-                return Err(Error::inconsistent_tree());
-            }
+    /// Create a new deserializer from a reader, with a set of options controlling how the model
+    /// is read
+    pub fn with_options(reader: R, options: DeserializerOptions) -> Deserializer<R> {
+        Deserializer {
+            reader,
+            raw_info: RawInfo::default(),
+            options,
+            class_names: BTreeMap::new(),
         }
+    }
 
-        Ok(RbxModel { meta, nodes: tree })
+    /// Deserialize a model from the input stream
+    pub fn deserialize(self) -> Result<RbxModel> {
+        self.deserialize_with_warnings().map(|(model, _)| model)
     }
 
-    fn chomp_block(&mut self) -> Result<bool> {
-        let name = self.chomp_blockname()?;
+    /// Deserialize a model from the input stream, also returning any warnings produced. Warnings
+    /// are only produced by non-strict deserialization.
+    pub fn deserialize_with_warnings(mut self) -> Result<(RbxModel, Vec<Error>)> {
+        let mut chunks = ChunkReader::with_options(self.reader, self.options.clone())?;
+        let header = chunks.header();
+
+        // Unknown chunks are kept along with the last known chunk before them, so they can be
+        // written back in the same place
+        let mut after = None;
+        loop {
+            let chunk = chunks.next().ok_or_else(Error::bad_magic)??;
+            let class_names = &self.class_names;
+            let view = chunk.view_with_options(&self.options).map_err(|err| {
+                match err
+                    .context
+                    .class_index
+                    .and_then(|idx| class_names.get(&idx))
+                {
+                    Some(class_name) => err.with_class_name(class_name),
+                    None => err,
+                }
+            })?;
 
-        if name == "END" {
-            let end_data = <[u8; 12]>::chomp(&mut self.reader)?;
-            assert_eq!(end_data, [0, 0, 0, 0, 9, 0, 0, 0, 0, 0, 0, 0]);
-            return Ok(false);
+            match view {
+                ChunkView::End => break,
+                ChunkView::Unknown => self.raw_info.unknown_chunks.push(UnknownChunk {
+                    name: chunk.header.name,
+                    data: chunk.data,
+                    after: after.clone(),
+                }),
+                view => {
+                    Self::add_chunk(
+                        &mut self.raw_info,
+                        &mut self.class_names,
+                        &self.options,
+                        view,
+                    )
+                    .map_err(|err| {
+                        err.with_chunk(chunk.header.index, &chunk.header.name)
+                            .with_offset(chunk.header.offset)
+                    })?;
+                    after = Some(chunk.header.name);
+                }
+            }
         }
 
-        let data = self.chomp_lz4()?;
-        let block_reader = &mut (&data as &[u8]);
-
-        match &*name {
-            "SSTR" => {
-                let unknown = i32::chomp(block_reader)?;
-                assert_eq!(unknown, 0);
-                let num_strs = i32::chomp(block_reader)?;
-
-                for _ in 0..num_strs {
-                    let unknown1 = i32::chomp(block_reader)?;
-                    let unknown2 = i32::chomp(block_reader)?;
-                    let unknown3 = i32::chomp(block_reader)?;
-                    let unknown4 = i32::chomp(block_reader)?;
-                    assert_eq!(unknown1, 0);
-                    assert_eq!(unknown2, 0);
-                    assert_eq!(unknown3, 0);
-                    assert_eq!(unknown4, 0);
-
-                    self.raw_info
-                        .shared_strs
-                        .push(<Vec<u8>>::chomp(block_reader)?);
+        if usize::try_from(header.num_classes) != Ok(self.raw_info.class_ids.len())
+            || usize::try_from(header.num_instances) != Ok(self.raw_info.instances.len())
+        {
+            return Err(Error::invalid_data(
+                "header counts don't match the classes and instances present",
+            ));
+        }
+
+        make_model(self.raw_info, &self.options)
+    }
+
+    fn add_chunk(
+        raw_info: &mut RawInfo,
+        class_names: &mut BTreeMap<i32, String>,
+        options: &DeserializerOptions,
+        view: ChunkView,
+    ) -> Result<()> {
+        match view {
+            ChunkView::Meta(meta) => raw_info.meta.extend(meta.entries),
+            ChunkView::Sstr(sstr) => {
+                for entry in sstr.strings {
+                    if options.verify_shared_string_hashes
+                        && entry.hash != [0; 16]
+                        && entry.hash != shared_string_hash(&entry.data)
+                    {
+                        let index = raw_info.shared_strs.len();
+                        raw_info.hash_mismatch(index, options)?;
+                    }
+
+                    raw_info.shared_strs.push(entry.data);
                 }
             }
-            "META" => {
-                let num_pairs = i32::chomp(block_reader)?;
-                for _ in 0..num_pairs {
-                    let key = String::chomp(block_reader)?;
-                    let value = String::chomp(block_reader)?;
-                    self.raw_info.meta.insert(key, value);
+            ChunkView::Inst(inst) => {
+                let context = |err: Error| {
+                    err.with_class_index(inst.class_index)
+                        .with_class_name(&inst.class_name)
+                };
+
+                if !raw_info.class_ids.contains_key(&inst.class_index) {
+                    options
+                        .check_classes(raw_info.class_ids.len() + 1)
+                        .map_err(context)?;
                 }
-            }
-            "INST" => {
-                let index = i32::chomp(block_reader)?;
-                let class_name = String::chomp(block_reader)?;
-                let _is_service = bool::chomp(block_reader)?;
-                let instance_count = i32::chomp(block_reader)?;
-                let mut instance_ids =
-                    i32::chomp_interleaved_transformed(block_reader, instance_count as usize)?;
-
-                decode_cumulative(&mut instance_ids);
-
-                for id in &instance_ids {
-                    self.raw_info.instances.insert(*id, class_name.clone());
+                options
+                    .check_instances(
+                        raw_info
+                            .instances
+                            .len()
+                            .saturating_add(inst.referents.len()),
+                    )
+                    .map_err(context)?;
+
+                let markers = inst.service_markers.iter().flatten();
+                for (&id, _) in inst
+                    .referents
+                    .iter()
+                    .zip(markers)
+                    .filter(|(_, &marker)| marker)
+                {
+                    raw_info.services.insert(id);
                 }
 
-                self.raw_info.class_ids.insert(index, instance_ids);
-            }
-            "PROP" => {
-                let class_index = i32::chomp(block_reader)?;
-                let property_name = String::chomp(block_reader)?;
-                let prop_ty = u8::chomp(block_reader)?;
+                for &id in &inst.referents {
+                    raw_info.instances.insert(id, inst.class_name.clone());
+                }
 
-                let class_ids = self
-                    .raw_info
+                raw_info.class_ids.insert(inst.class_index, inst.referents);
+                class_names.insert(inst.class_index, inst.class_name);
+            }
+            ChunkView::Prop(prop) => {
+                let class_ids = raw_info
                     .class_ids
-                    .get(&class_index)
-                    .ok_or_else(|| Error::unknown_class(class_index))?;
+                    .get(&prop.class_index)
+                    .ok_or_else(|| Error::unknown_class(prop.class_index))?;
 
-                let num_props = class_ids.len();
-
-                let properties = chomp_properties(block_reader, num_props, prop_ty)?;
-
-                debug_assert_eq!(
-                    *block_reader,
-                    [],
-                    "Property {prop_ty} didn't consume whole buffer"
-                );
-
-                for (inst_id, property) in class_ids.iter().zip(properties.into_iter()) {
-                    self.raw_info
+                for (inst_id, property) in class_ids.iter().zip(prop.values) {
+                    raw_info
                         .raw_props
                         .entry(*inst_id)
                         .or_default()
-                        .insert(property_name.clone(), property);
+                        .insert(prop.name.clone(), property);
                 }
             }
-            "PRNT" => {
-                let unknown = u8::chomp(block_reader)?;
-                assert_eq!(unknown, 0);
-                let len = i32::chomp(block_reader)?;
-                let mut instance_referents =
-                    i32::chomp_interleaved_transformed(block_reader, len as usize)?;
-                let mut parent_referents =
-                    i32::chomp_interleaved_transformed(block_reader, len as usize)?;
-
-                decode_cumulative(&mut instance_referents);
-                decode_cumulative(&mut parent_referents);
-
-                for (child_id, parent_id) in instance_referents
-                    .into_iter()
-                    .zip(parent_referents.into_iter())
-                {
-                    self.raw_info.parent_info.insert(child_id, parent_id);
-                    self.raw_info
+            ChunkView::Prnt(prnt) => {
+                for (child_id, parent_id) in prnt.links {
+                    raw_info.parent_info.insert(child_id, parent_id);
+                    raw_info
                         .child_info
                         .entry(parent_id)
                         .or_default()
                         .push(child_id);
                 }
             }
-            _ => return Err(Error::unknown_block(name)),
+            ChunkView::End | ChunkView::Unknown => (),
         }
-        Ok(true)
-    }
-
-    fn chomp_blockname(&mut self) -> Result<String> {
-        let data = <[u8; 4]>::chomp(&mut self.reader)?;
-
-        let first_zero = data.iter().copied().position(|b| b == 0).unwrap_or(4);
-
-        Ok(core::str::from_utf8(&data[..first_zero])
-            .map_err(|_| Error::invalid_string())?
-            .to_string())
-    }
-
-    fn chomp_lz4(&mut self) -> Result<Vec<u8>> {
-        let compressed = i32::chomp(&mut self.reader)?;
-        let uncompressed = i32::chomp(&mut self.reader)? as usize;
-
-        assert_eq!(i32::chomp(&mut self.reader)?, 0i32);
-
-        let mut data = vec![0; compressed as usize];
-        self.reader.read_exact(&mut data)?;
-
-        let out =
-            lz4_flex::block::decompress(&data, uncompressed).map_err(|_| Error::invalid_lz4())?;
-
-        assert_eq!(out.len(), uncompressed);
-
-        Ok(out)
+        Ok(())
     }
 }
 
@@ -476,6 +547,303 @@ pub fn from_bytes(bytes: &[u8]) -> Result<RbxModel> {
 #[cfg(all(test, feature = "std"))]
 mod tests {
     use super::*;
+    use crate::serde::ser::to_bytes;
+
+    #[test]
+    fn test_invalid_tags() {
+        use crate::model::instance::Model;
+
+        let mut props = Instance::Model(Model::new()).break_instance();
+        props.insert(
+            String::from("Tags"),
+            Property::BinaryString(b"Door\0\xFF".to_vec()),
+        );
+        let model = RbxModel::new();
+        model
+            .tree()
+            .add_root(Instance::Other(String::from("Model"), props));
+        let data = to_bytes(&model).unwrap();
+
+        // Tags that aren't valid UTF-8 can't be kept as a `Tags`, so they aren't altered to fit
+        let err = from_bytes(&data).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::InvalidString));
+        assert_eq!(err.context.property_name.as_deref(), Some("Tags"));
+
+        let options = DeserializerOptions {
+            strictness: Strictness::Lenient,
+            ..DeserializerOptions::default()
+        };
+        let (model, warnings) = Deserializer::with_options(&*data, options)
+            .deserialize_with_warnings()
+            .unwrap();
+        assert_eq!(warnings.len(), 1);
+        let root = model.get_path("Model").unwrap();
+        match &*root {
+            Instance::Other(_, props) => assert!(matches!(
+                &props["Tags"],
+                Property::BinaryString(tags) if tags == b"Door\0\xFF"
+            )),
+            _ => panic!("Expected an unrecognized instance"),
+        }
+        assert!(root.has_tag("Door"));
+    }
+
+    #[test]
+    fn test_lenient() {
+        let model = RbxModel::new();
+        let mut props = BTreeMap::new();
+        props.insert(
+            String::from("Name"),
+            Property::TextString(String::from("Folder")),
+        );
+        model
+            .tree()
+            .add_root(Instance::Other(String::from("Folder"), props));
+        let data = to_bytes(&model).unwrap();
+
+        let err = from_bytes(&data).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::MissingProperty(_)));
+        assert_eq!(err.context.class_name.as_deref(), Some("Folder"));
+        assert_eq!(err.context.instance_id, Some(0));
+
+        let options = DeserializerOptions {
+            strictness: Strictness::Lenient,
+            ..DeserializerOptions::default()
+        };
+        let (model, warnings) = Deserializer::with_options(&*data, options)
+            .deserialize_with_warnings()
+            .unwrap();
+
+        assert_eq!(warnings.len(), 1);
+        assert!(matches!(warnings[0].kind, ErrorKind::MissingProperty(_)));
+        let folder = model.get_path("Folder").unwrap();
+        match &*folder {
+            Instance::Other(class, props) => {
+                assert_eq!(class, "Folder");
+                assert!(
+                    matches!(props.get("Name"), Some(Property::TextString(name)) if name == "Folder")
+                );
+            }
+            _ => panic!("Expected an Other instance"),
+        }
+    }
+
+    #[test]
+    fn test_error_context() {
+        let model = RbxModel::default();
+        model
+            .tree()
+            .add_root(Instance::Other(String::from("Custom"), BTreeMap::new()));
+        let data = to_bytes(&model).unwrap();
+
+        // Cut the file off partway through the `END` chunk, which is followed by `</roblox>`
+        let end_offset = data.len() - 25;
+        let err = from_bytes(&data[..data.len() - 15]).unwrap_err();
+
+        assert_eq!(err.context.chunk_name.as_deref(), Some("END"));
+        assert_eq!(err.context.offset, Some(end_offset as u64));
+    }
+
+    #[test]
+    fn test_limits() {
+        let model = RbxModel::default();
+        let mut props = BTreeMap::new();
+        props.insert(
+            String::from("Name"),
+            Property::TextString(String::from("Root")),
+        );
+        let root = model
+            .tree()
+            .add_root(Instance::Other(String::from("Custom"), props.clone()));
+        model
+            .tree()
+            .try_get_mut(root)
+            .unwrap()
+            .new_child(Instance::Other(String::from("Other"), props));
+        let data = to_bytes(&model).unwrap();
+
+        let read = |options: DeserializerOptions| {
+            Deserializer::with_options(&*data, options)
+                .deserialize()
+                .map_err(|err| err.kind)
+        };
+
+        assert!(matches!(
+            read(DeserializerOptions {
+                max_decompressed_bytes: Some(16),
+                ..DeserializerOptions::default()
+            }),
+            Err(ErrorKind::DecompressedSizeExceeded(16))
+        ));
+        assert!(matches!(
+            read(DeserializerOptions {
+                max_instances: Some(1),
+                ..DeserializerOptions::default()
+            }),
+            Err(ErrorKind::TooManyInstances(1))
+        ));
+        assert!(matches!(
+            read(DeserializerOptions {
+                max_classes: Some(1),
+                ..DeserializerOptions::default()
+            }),
+            Err(ErrorKind::TooManyClasses(1))
+        ));
+        assert!(matches!(
+            read(DeserializerOptions {
+                max_string_length: Some(3),
+                ..DeserializerOptions::default()
+            }),
+            Err(ErrorKind::StringTooLong(3))
+        ));
+        assert!(matches!(
+            read(DeserializerOptions {
+                max_tree_depth: Some(1),
+                ..DeserializerOptions::default()
+            }),
+            Err(ErrorKind::TreeTooDeep(1))
+        ));
+
+        let model = read(DeserializerOptions {
+            max_decompressed_bytes: Some(data.len() * 4),
+            max_instances: Some(2),
+            max_classes: Some(2),
+            max_string_length: Some(32),
+            max_tree_depth: Some(2),
+            ..DeserializerOptions::default()
+        })
+        .unwrap();
+        assert!(model.get_path("Root/Root").is_ok());
+    }
+
+    #[test]
+    fn test_shared_string_hashes() {
+        use crate::serde::ser::{Compression, Serializer, SerializerOptions};
+
+        let model = RbxModel::default();
+        let mut props = BTreeMap::new();
+        props.insert(
+            String::from("Name"),
+            Property::TextString(String::from("Root")),
+        );
+        props.insert(
+            String::from("Data"),
+            Property::SharedBinaryString(vec![0xFF, 0x00, 0xFF]),
+        );
+        model
+            .tree()
+            .add_root(Instance::Other(String::from("Custom"), props));
+
+        let mut data = Vec::new();
+        let options = SerializerOptions {
+            compression: Compression::None,
+            ..SerializerOptions::default()
+        };
+        Serializer::with_options(&mut data, options)
+            .serialize(&model)
+            .unwrap();
+
+        // The hash of the first string follows the SSTR block header, version and count
+        let hash = data.windows(4).position(|w| w == b"SSTR").unwrap() + 24;
+        assert_eq!(
+            data[hash..hash + 16],
+            shared_string_hash(&[0xFF, 0x00, 0xFF])
+        );
+
+        let verify = |strictness| DeserializerOptions {
+            strictness,
+            verify_shared_string_hashes: true,
+            ..DeserializerOptions::default()
+        };
+        assert!(
+            Deserializer::with_options(&*data, verify(Strictness::Strict))
+                .deserialize()
+                .is_ok()
+        );
+
+        data[hash] ^= 1;
+        assert!(from_bytes(&data).is_ok());
+
+        let err = Deserializer::with_options(&*data, verify(Strictness::Strict))
+            .deserialize()
+            .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::SharedStringHashMismatch(0)));
+
+        let (_, warnings) = Deserializer::with_options(&*data, verify(Strictness::Lenient))
+            .deserialize_with_warnings()
+            .unwrap();
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_negative_length() {
+        let mut data = crate::serde::chunk::BINARY_MAGIC_START.to_vec();
+        data.extend_from_slice(&[0; 16]);
+        data.extend_from_slice(b"META");
+        data.extend_from_slice(&(-1i32).to_le_bytes());
+        data.extend_from_slice(&[0; 8]);
+
+        let err = from_bytes(&data).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::InvalidLength(-1)));
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        use crate::serde::ser::{Compression, Serializer, SerializerOptions};
+
+        let model = RbxModel::default();
+        let mut props = BTreeMap::new();
+        props.insert(
+            String::from("Name"),
+            Property::TextString(String::from("Root")),
+        );
+        props.insert(String::from("Value"), Property::Int32(10));
+        let root = model
+            .tree()
+            .add_root(Instance::Other(String::from("Custom"), props.clone()));
+        props.insert(
+            String::from("Target"),
+            Property::InstanceRef(InstanceRef::Item(root)),
+        );
+        model
+            .tree()
+            .try_get_mut(root)
+            .unwrap()
+            .new_child(Instance::Other(String::from("Custom"), props));
+
+        let mut data = Vec::new();
+        let options = SerializerOptions {
+            compression: Compression::None,
+            ..SerializerOptions::default()
+        };
+        Serializer::with_options(&mut data, options)
+            .serialize(&model)
+            .unwrap();
+
+        for len in 0..data.len() {
+            assert!(from_bytes(&data[..len]).is_err());
+        }
+
+        // Corrupt data may or may not still parse, but must never panic
+        for idx in 0..data.len() {
+            for byte in [0x00, 0x80, 0xFF] {
+                let mut corrupt = data.clone();
+                corrupt[idx] = byte;
+                let _ = from_bytes(&corrupt);
+            }
+        }
+
+        // Runs of corrupt bytes can push encoded integers to their extremes
+        for len in [4, 8] {
+            for idx in 0..=data.len() - len {
+                for byte in [0x7F, 0x80, 0xFF] {
+                    let mut corrupt = data.clone();
+                    corrupt[idx..idx + len].fill(byte);
+                    let _ = from_bytes(&corrupt);
+                }
+            }
+        }
+    }
 
     #[test]
     fn test_files() {
@@ -4,6 +4,7 @@
 mod chomp;
 mod print;
 
+pub(crate) use chomp::{chomp_bytes, chomp_len};
 pub use chomp::{Chomp, ChompInterleaved, ChompInterleavedTransform, ChompTransform};
 pub use print::{Print, PrintInterleaved, PrintInterleavedTransform, PrintTransform};
 
@@ -16,8 +17,7 @@ pub const fn decode_i32(mut raw: u32) -> i32 {
     raw >>= 1;
     let mut out = i32::from_ne_bytes(raw.to_ne_bytes());
     if sign == 1 {
-        out += 1;
-        out = -out;
+        out = out.wrapping_add(1).wrapping_neg();
     }
     out
 }
@@ -27,8 +27,7 @@ pub const fn decode_i32(mut raw: u32) -> i32 {
 pub const fn encode_i32(mut val: i32) -> u32 {
     let sign = (val < 0) as u32;
     if val < 0 {
-        val = -val;
-        val -= 1;
+        val = val.wrapping_neg().wrapping_sub(1);
     }
     let mut out = u32::from_ne_bytes(val.to_ne_bytes());
     out <<= 1;
@@ -57,25 +56,25 @@ pub fn encode_f32(val: f32) -> u32 {
 }
 
 /// Decode a slice of integers as 'cumulative'. Each value should be the sum of all previous
-/// values, effectively.
+/// values, effectively. Sums wrap on overflow, as they do in Roblox's encoder.
 pub fn decode_cumulative(mut slice: &mut [i32]) {
     for _ in 1..slice.len() {
         let (first, second) = slice
             .split_first_mut()
             .expect("Can't happen: we range from 1 to len");
-        second[0] += *first;
+        second[0] = second[0].wrapping_add(*first);
         slice = second;
     }
 }
 
 /// Encode a slice of integers as 'cumulative'. Each value is replaced with its difference from the
-/// previous value.
+/// previous value, wrapping on overflow.
 pub fn encode_cumulative(mut slice: &mut [i32]) {
     for _ in (1..slice.len()).rev() {
         let (last, previous) = slice
             .split_last_mut()
             .expect("Can't happen: we range from len to 1");
-        *last -= previous.last().expect("Can't happen");
+        *last = last.wrapping_sub(*previous.last().expect("Can't happen"));
         slice = previous;
     }
 }
@@ -94,6 +93,7 @@ mod tests {
             decode_i32(0b0000_0000_0000_0000_1000_0000_0000_0001),
             -16385
         );
+        assert_eq!(decode_i32(u32::MAX), i32::MIN);
     }
 
     #[test]
@@ -106,6 +106,7 @@ mod tests {
             encode_i32(-16385),
             0b0000_0000_0000_0000_1000_0000_0000_0001
         );
+        assert_eq!(encode_i32(i32::MIN), u32::MAX);
     }
 
     #[test]
@@ -127,6 +128,10 @@ mod tests {
         let mut array = [0, 1, 1, 5];
         decode_cumulative(&mut array);
         assert_eq!(array, [0, 1, 2, 7]);
+
+        let mut array = [i32::MAX, 1];
+        decode_cumulative(&mut array);
+        assert_eq!(array, [i32::MAX, i32::MIN]);
     }
 
     #[test]
@@ -134,5 +139,9 @@ mod tests {
         let mut array = [0, 1, 2, 7];
         encode_cumulative(&mut array);
         assert_eq!(array, [0, 1, 1, 5]);
+
+        let mut array = [i32::MAX, i32::MIN];
+        encode_cumulative(&mut array);
+        assert_eq!(array, [i32::MAX, 1]);
     }
 }
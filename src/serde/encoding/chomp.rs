@@ -1,7 +1,8 @@
 // Consume ('chomp') some data from an input
 
 use crate::model::*;
-use crate::serde::encoding::{decode_f32, decode_i32};
+use crate::serde::encoding::{decode_cumulative, decode_f32, decode_i32};
+use crate::serde::internal::RawContent;
 use crate::serde::io::Read;
 use crate::serde::{Error, Result};
 
@@ -11,6 +12,36 @@ use alloc::vec;
 use alloc::vec::Vec;
 use uuid::Uuid;
 
+/// The most memory allocated up front for a value read with an untrusted length. Larger values
+/// are read in pieces, so a corrupt length fails once the input runs out rather than allocating
+/// the full amount.
+const MAX_PREALLOC: usize = 64 * 1024;
+
+/// Read a length or count, which must not be negative
+pub(crate) fn chomp_len<R: Read>(reader: &mut R) -> Result<usize> {
+    let len = i32::chomp(reader)?;
+    usize::try_from(len).map_err(|_| Error::invalid_length(len.into()))
+}
+
+/// Read an exact number of bytes, only growing the buffer as data is actually available
+pub(crate) fn chomp_bytes<R: Read>(reader: &mut R, len: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(len.min(MAX_PREALLOC));
+    while out.len() < len {
+        let start = out.len();
+        out.resize(start + (len - start).min(MAX_PREALLOC), 0);
+        reader.read_exact(&mut out[start..])?;
+    }
+    Ok(out)
+}
+
+/// Read the bytes backing `count` interleaved 4-byte values
+fn chomp_interleaved_bytes<R: Read>(reader: &mut R, count: usize, width: usize) -> Result<Vec<u8>> {
+    let len = count
+        .checked_mul(width)
+        .ok_or_else(|| Error::invalid_length(count as i64))?;
+    chomp_bytes(reader, len)
+}
+
 /// Types that can be read from a stream that is assumed to match the RBXM encoding format
 pub trait Chomp<R: Read>: Sized {
     /// Consume type from stream
@@ -60,6 +91,14 @@ impl<R: Read> Chomp<R> for u16 {
     }
 }
 
+impl<R: Read> Chomp<R> for u32 {
+    fn chomp(reader: &mut R) -> Result<Self> {
+        let mut data = [0; 4];
+        reader.read_exact(&mut data)?;
+        Ok(u32::from_le_bytes(data))
+    }
+}
+
 impl<R: Read> Chomp<R> for i16 {
     fn chomp(reader: &mut R) -> Result<Self> {
         let mut data = [0; 2];
@@ -86,8 +125,7 @@ impl<R: Read> ChompTransform<R> for i32 {
 
 impl<R: Read> ChompInterleaved<R> for i32 {
     fn chomp_interleaved(reader: &mut R, count: usize) -> Result<Vec<Self>> {
-        let mut data = vec![0; count * 4];
-        reader.read_exact(&mut data)?;
+        let data = chomp_interleaved_bytes(reader, count, 4)?;
 
         let mut out = vec![0; count];
         for i in 0..count {
@@ -104,8 +142,7 @@ impl<R: Read> ChompInterleaved<R> for i32 {
 
 impl<R: Read> ChompInterleavedTransform<R> for i32 {
     fn chomp_interleaved_transformed(reader: &mut R, count: usize) -> Result<Vec<Self>> {
-        let mut data = vec![0; count * 4];
-        reader.read_exact(&mut data)?;
+        let data = chomp_interleaved_bytes(reader, count, 4)?;
 
         let mut out = vec![0; count];
         for i in 0..count {
@@ -145,8 +182,7 @@ impl<R: Read> ChompTransform<R> for f32 {
 
 impl<R: Read> ChompInterleaved<R> for f32 {
     fn chomp_interleaved(reader: &mut R, count: usize) -> Result<Vec<Self>> {
-        let mut data = vec![0; count * 4];
-        reader.read_exact(&mut data)?;
+        let data = chomp_interleaved_bytes(reader, count, 4)?;
 
         let mut out = vec![0f32; count];
         for i in 0..count {
@@ -180,18 +216,16 @@ impl<R: Read, const N: usize> Chomp<R> for [u8; N] {
 
 impl<R: Read> Chomp<R> for String {
     fn chomp(reader: &mut R) -> Result<Self> {
-        let len = i32::chomp(reader)?;
-        let mut str = vec![0; len as usize];
-        reader.read_exact(&mut str)?;
+        let len = chomp_len(reader)?;
+        let str = chomp_bytes(reader, len)?;
         String::from_utf8(str).map_err(|_| Error::invalid_string())
     }
 }
 
 impl<R: Read> Chomp<R> for Vec<u8> {
     fn chomp(reader: &mut R) -> Result<Self> {
-        let len = i32::chomp(reader)?;
-        let mut str = vec![0; len as usize];
-        reader.read_exact(&mut str)?;
+        let len = chomp_len(reader)?;
+        let str = chomp_bytes(reader, len)?;
         Ok(str)
     }
 }
@@ -406,9 +440,65 @@ impl<R: Read> Chomp<R> for Vector3Int16 {
     }
 }
 
+/// Get one of the axis-aligned rotations which are stored as an ID rather than a full matrix
+fn special_angle(angle_type: u8) -> Result<[[f32; 3]; 3]> {
+    let angle = match angle_type {
+        // TODO: Double check these. They may be flipped around, due to reading order wrong
+        //       previously
+        2 => [[1f32, 0f32, 0f32], [0f32, 1f32, 0f32], [0f32, 0f32, 1f32]],
+        3 => [[1f32, 0f32, 0f32], [0f32, 0f32, -1f32], [0f32, 1f32, 0f32]],
+        5 => [[1f32, 0f32, 0f32], [0f32, -1f32, 0f32], [0f32, 0f32, -1f32]],
+        6 => [[1f32, 0f32, 0f32], [0f32, 0f32, 1f32], [0f32, -1f32, 0f32]],
+        7 => [[0f32, 1f32, 0f32], [1f32, 0f32, 0f32], [0f32, 0f32, -1f32]],
+        9 => [[0f32, 1f32, 0f32], [0f32, 0f32, 1f32], [1f32, 0f32, 0f32]],
+        10 => [[0f32, -1f32, 0f32], [1f32, 0f32, 0f32], [0f32, 0f32, 1f32]],
+        12 => [[0f32, 0f32, -1f32], [1f32, 0f32, 0f32], [0f32, -1f32, 0f32]],
+        13 => [[0f32, 0f32, 1f32], [1f32, 0f32, 0f32], [0f32, 1f32, 0f32]],
+        14 => [[0f32, 0f32, -1f32], [0f32, 1f32, 0f32], [1f32, 0f32, 0f32]],
+        16 => [[0f32, -1f32, 0f32], [0f32, 0f32, -1f32], [1f32, 0f32, 0f32]],
+        17 => [[0f32, 0f32, 1f32], [0f32, -1f32, 0f32], [1f32, 0f32, 0f32]],
+        20 => [[-1f32, 0f32, 0f32], [0f32, 1f32, 0f32], [0f32, 0f32, -1f32]],
+        21 => [[-1f32, 0f32, 0f32], [0f32, 0f32, 1f32], [0f32, 1f32, 0f32]],
+        23 => [[-1f32, 0f32, 0f32], [0f32, -1f32, 0f32], [0f32, 0f32, 1f32]],
+        24 => [
+            [-1f32, 0f32, 0f32],
+            [0f32, 0f32, -1f32],
+            [0f32, -1f32, 0f32],
+        ],
+        25 => [[0f32, 1f32, 0f32], [-1f32, 0f32, 0f32], [0f32, 0f32, 1f32]],
+        27 => [[0f32, 0f32, -1f32], [-1f32, 0f32, 0f32], [0f32, 1f32, 0f32]],
+        28 => [
+            [0f32, -1f32, 0f32],
+            [-1f32, 0f32, 0f32],
+            [0f32, 0f32, -1f32],
+        ],
+        30 => [[0f32, 0f32, 1f32], [-1f32, 0f32, 0f32], [0f32, -1f32, 0f32]],
+        31 => [[0f32, 1f32, 0f32], [0f32, 0f32, -1f32], [-1f32, 0f32, 0f32]],
+        32 => [[0f32, 0f32, 1f32], [0f32, 1f32, 0f32], [-1f32, 0f32, 0f32]],
+        34 => [[0f32, -1f32, 0f32], [0f32, 0f32, 1f32], [-1f32, 0f32, 0f32]],
+        35 => [
+            [0f32, 0f32, -1f32],
+            [0f32, -1f32, 0f32],
+            [-1f32, 0f32, 0f32],
+        ],
+        _ => return Err(Error::unknown_cframe(angle_type)),
+    };
+    Ok(angle)
+}
+
+/// Read the interleaved positions following the rotations of a set of `CFrame`s
+fn chomp_positions<R: Read>(reader: &mut R, count: usize) -> Result<Vec<Vector3>> {
+    let xs = f32::chomp_interleaved(reader, count)?;
+    let ys = f32::chomp_interleaved(reader, count)?;
+    let zs = f32::chomp_interleaved(reader, count)?;
+    Ok((0..count)
+        .map(|i| Vector3::new(xs[i], ys[i], zs[i]))
+        .collect())
+}
+
 impl<R: Read> ChompInterleaved<R> for CFrame {
     fn chomp_interleaved(reader: &mut R, count: usize) -> Result<Vec<Self>> {
-        let mut angles = Vec::with_capacity(count);
+        let mut angles = Vec::with_capacity(count.min(MAX_PREALLOC));
         for _ in 0..count {
             let angle_type = u8::chomp(reader)?;
             let angle: [[f32; 3]; 3] = match angle_type {
@@ -421,70 +511,109 @@ impl<R: Read> ChompInterleaved<R> for CFrame {
                     }
                     data
                 }
-                // TODO: Double check these. They may be flipped around, due to reading order wrong
-                //       previously
-                2 => [[1f32, 0f32, 0f32], [0f32, 1f32, 0f32], [0f32, 0f32, 1f32]],
-                3 => [[1f32, 0f32, 0f32], [0f32, 0f32, -1f32], [0f32, 1f32, 0f32]],
-                5 => [[1f32, 0f32, 0f32], [0f32, -1f32, 0f32], [0f32, 0f32, -1f32]],
-                6 => [[1f32, 0f32, 0f32], [0f32, 0f32, 1f32], [0f32, -1f32, 0f32]],
-                7 => [[0f32, 1f32, 0f32], [1f32, 0f32, 0f32], [0f32, 0f32, -1f32]],
-                9 => [[0f32, 1f32, 0f32], [0f32, 0f32, 1f32], [1f32, 0f32, 0f32]],
-                10 => [[0f32, -1f32, 0f32], [1f32, 0f32, 0f32], [0f32, 0f32, 1f32]],
-                12 => [[0f32, 0f32, -1f32], [1f32, 0f32, 0f32], [0f32, -1f32, 0f32]],
-                13 => [[0f32, 0f32, 1f32], [1f32, 0f32, 0f32], [0f32, 1f32, 0f32]],
-                14 => [[0f32, 0f32, -1f32], [0f32, 1f32, 0f32], [1f32, 0f32, 0f32]],
-                16 => [[0f32, -1f32, 0f32], [0f32, 0f32, -1f32], [1f32, 0f32, 0f32]],
-                17 => [[0f32, 0f32, 1f32], [0f32, -1f32, 0f32], [1f32, 0f32, 0f32]],
-                20 => [[-1f32, 0f32, 0f32], [0f32, 1f32, 0f32], [0f32, 0f32, -1f32]],
-                21 => [[-1f32, 0f32, 0f32], [0f32, 0f32, 1f32], [0f32, 1f32, 0f32]],
-                23 => [[-1f32, 0f32, 0f32], [0f32, -1f32, 0f32], [0f32, 0f32, 1f32]],
-                24 => [
-                    [-1f32, 0f32, 0f32],
-                    [0f32, 0f32, -1f32],
-                    [0f32, -1f32, 0f32],
-                ],
-                25 => [[0f32, 1f32, 0f32], [-1f32, 0f32, 0f32], [0f32, 0f32, 1f32]],
-                27 => [[0f32, 0f32, -1f32], [-1f32, 0f32, 0f32], [0f32, 1f32, 0f32]],
-                28 => [
-                    [0f32, -1f32, 0f32],
-                    [-1f32, 0f32, 0f32],
-                    [0f32, 0f32, -1f32],
-                ],
-                30 => [[0f32, 0f32, 1f32], [-1f32, 0f32, 0f32], [0f32, -1f32, 0f32]],
-                31 => [[0f32, 1f32, 0f32], [0f32, 0f32, -1f32], [-1f32, 0f32, 0f32]],
-                32 => [[0f32, 0f32, 1f32], [0f32, 1f32, 0f32], [-1f32, 0f32, 0f32]],
-                34 => [[0f32, -1f32, 0f32], [0f32, 0f32, 1f32], [-1f32, 0f32, 0f32]],
-                35 => [
-                    [0f32, 0f32, -1f32],
-                    [0f32, -1f32, 0f32],
-                    [-1f32, 0f32, 0f32],
-                ],
-                _ => return Err(Error::unknown_cframe(angle_type)),
+                _ => special_angle(angle_type)?,
             };
 
             angles.push(angle);
         }
-        let mut positions = Vec::with_capacity(count);
-        let xs = f32::chomp_interleaved(reader, count)?;
-        let ys = f32::chomp_interleaved(reader, count)?;
-        let zs = f32::chomp_interleaved(reader, count)?;
-        for i in 0..count {
-            positions.push(Vector3::new(xs[i], ys[i], zs[i]));
+        let positions = chomp_positions(reader, count)?;
+
+        Ok(positions
+            .into_iter()
+            .zip(angles)
+            .map(|(position, angle)| CFrame { position, angle })
+            .collect())
+    }
+}
+
+impl<R: Read> ChompInterleaved<R> for CFrameQuat {
+    fn chomp_interleaved(reader: &mut R, count: usize) -> Result<Vec<Self>> {
+        let mut rotations = Vec::with_capacity(count.min(MAX_PREALLOC));
+        for _ in 0..count {
+            let angle_type = u8::chomp(reader)?;
+            let rotation = match angle_type {
+                0 => Quaternion::new(
+                    f32::chomp(reader)?,
+                    f32::chomp(reader)?,
+                    f32::chomp(reader)?,
+                    f32::chomp(reader)?,
+                ),
+                _ => Quaternion::from_matrix(&special_angle(angle_type)?),
+            };
+
+            rotations.push(rotation);
         }
+        let positions = chomp_positions(reader, count)?;
+
+        Ok(positions
+            .into_iter()
+            .zip(rotations)
+            .map(|(position, rotation)| CFrameQuat { position, rotation })
+            .collect())
+    }
+}
+
+impl<R: Read> ChompInterleaved<R> for SecurityCapabilities {
+    fn chomp_interleaved(reader: &mut R, count: usize) -> Result<Vec<Self>> {
+        let data = chomp_interleaved_bytes(reader, count, 8)?;
 
         let mut out = Vec::with_capacity(count);
-        for (position, angle) in positions.into_iter().zip(angles.into_iter()) {
-            out.push(CFrame { position, angle });
+        for i in 0..count {
+            let mut bytes = [0; 8];
+            for j in 0..8 {
+                bytes[j] = data[i + j * count];
+            }
+            out.push(SecurityCapabilities(u64::from_be_bytes(bytes)));
         }
-
         Ok(out)
     }
 }
 
+impl<R: Read> ChompInterleaved<R> for RawContent {
+    fn chomp_interleaved(reader: &mut R, count: usize) -> Result<Vec<Self>> {
+        // The kind of each value comes first, followed by separate lists of the URIs and objects
+        let source_types = i32::chomp_interleaved_transformed(reader, count)?;
+
+        let num_uris = chomp_len(reader)?;
+        let mut uris = Vec::with_capacity(num_uris.min(MAX_PREALLOC));
+        for _ in 0..num_uris {
+            uris.push(String::chomp(reader)?);
+        }
+
+        let num_objects = chomp_len(reader)?;
+        let mut objects = i32::chomp_interleaved_transformed(reader, num_objects)?;
+        decode_cumulative(&mut objects);
+
+        if chomp_len(reader)? != 0 {
+            return Err(Error::invalid_data(
+                "content referring to external objects isn't supported",
+            ));
+        }
+
+        let mut uris = uris.into_iter();
+        let mut objects = objects.into_iter();
+        source_types
+            .into_iter()
+            .map(|source_type| match source_type {
+                0 => Ok(RawContent::None),
+                1 => uris
+                    .next()
+                    .map(RawContent::Uri)
+                    .ok_or_else(|| Error::invalid_data("content is missing its URI")),
+                2 => objects
+                    .next()
+                    .map(RawContent::Object)
+                    .ok_or_else(|| Error::invalid_data("content is missing its object")),
+                _ => Err(Error::unknown_variant(source_type)),
+            })
+            .collect()
+    }
+}
+
 impl<R: Read> Chomp<R> for NumberSequence {
     fn chomp(reader: &mut R) -> Result<Self> {
-        let num_keypoints = i32::chomp(reader)?;
-        let mut keypoints = Vec::with_capacity(num_keypoints as usize);
+        let num_keypoints = chomp_len(reader)?;
+        let mut keypoints = Vec::with_capacity(num_keypoints.min(MAX_PREALLOC));
         for _ in 0..num_keypoints {
             let time = f32::chomp(reader)?;
             let value = f32::chomp(reader)?;
@@ -502,8 +631,8 @@ impl<R: Read> Chomp<R> for NumberSequence {
 
 impl<R: Read> Chomp<R> for ColorSequence {
     fn chomp(reader: &mut R) -> Result<Self> {
-        let num_keypoints = i32::chomp(reader)?;
-        let mut keypoints = Vec::with_capacity(num_keypoints as usize);
+        let num_keypoints = chomp_len(reader)?;
+        let mut keypoints = Vec::with_capacity(num_keypoints.min(MAX_PREALLOC));
         for _ in 0..num_keypoints {
             let time = f32::chomp(reader)?;
             let color = Color3::chomp(reader)?;
@@ -561,32 +690,45 @@ impl<R: Read> ChompInterleaved<R> for Rect {
     }
 }
 
-impl<R: Read> ChompInterleaved<R> for Pivot {
+impl<R: Read> ChompInterleaved<R> for Option<CFrame> {
     fn chomp_interleaved(reader: &mut R, count: usize) -> Result<Vec<Self>> {
-        let _first = u8::chomp(reader)?;
-        debug_assert_eq!(_first, 16, "Unexpected pivot first unknown");
+        // Stored as a full CFrame array, followed by a bool array of which values are present.
+        // Each array is prefixed by its type ID.
+        if u8::chomp(reader)? != 16 {
+            return Err(Error::invalid_data(
+                "optional CFrame didn't contain CFrame values",
+            ));
+        }
 
         let cframes = CFrame::chomp_interleaved(reader, count)?;
 
-        let _second = u8::chomp(reader)?;
-        debug_assert_eq!(_second, 2, "Unexpected pivot second unknown");
-
-        let mut unknown = Vec::new();
-
-        for _ in 0..count {
-            unknown.push(u8::chomp(reader)?);
+        if u8::chomp(reader)? != 2 {
+            return Err(Error::invalid_data(
+                "optional CFrame didn't contain presence flags",
+            ));
         }
 
-        let mut out = Vec::new();
-
-        for (cframe, unknown) in cframes.into_iter().zip(unknown.into_iter()) {
-            out.push(Pivot { cframe, unknown });
+        let mut out = Vec::with_capacity(count.min(MAX_PREALLOC));
+        for cframe in cframes {
+            out.push(bool::chomp(reader)?.then_some(cframe));
         }
 
         Ok(out)
     }
 }
 
+fn chomp_attribute_vector2<R: Read>(reader: &mut R) -> Result<Vector2> {
+    Ok(Vector2::new(f32::chomp(reader)?, f32::chomp(reader)?))
+}
+
+fn chomp_attribute_vector3<R: Read>(reader: &mut R) -> Result<Vector3> {
+    Ok(Vector3::new(
+        f32::chomp(reader)?,
+        f32::chomp(reader)?,
+        f32::chomp(reader)?,
+    ))
+}
+
 impl<R: Read> Chomp<R> for Attributes {
     fn chomp(reader: &mut R) -> Result<Self> {
         let num_props = i32::chomp(reader)?;
@@ -594,20 +736,63 @@ impl<R: Read> Chomp<R> for Attributes {
         for _ in 0..num_props {
             let name = String::chomp(reader)?;
             let prop_ty = u8::chomp(reader)?;
+            // Unlike properties, attribute floats are never transformed
             let prop = match prop_ty {
+                1 => Property::Nil,
                 2 => Property::TextString(String::chomp(reader)?),
                 3 => Property::Bool(bool::chomp(reader)?),
+                4 => Property::Int32(i32::chomp(reader)?),
+                5 => Property::Float(f32::chomp(reader)?),
                 6 => Property::Double(f64::chomp(reader)?),
                 9 => Property::UDim(UDim::chomp(reader)?),
                 10 => Property::UDim2(UDim2::chomp(reader)?),
                 14 => Property::BrickColor(BrickColor::chomp(reader)?),
                 15 => Property::Color3(Color3::chomp(reader)?),
-                16 => Property::Vector2(Vector2::chomp(reader)?),
-                17 => Property::Vector3(Vector3::chomp(reader)?),
+                16 => Property::Vector2(chomp_attribute_vector2(reader)?),
+                17 => Property::Vector3(chomp_attribute_vector3(reader)?),
+                20 => {
+                    let position = chomp_attribute_vector3(reader)?;
+                    let angle = match u8::chomp(reader)? {
+                        0 => {
+                            let mut data = [[0f32; 3]; 3];
+                            for row in &mut data {
+                                for val in row {
+                                    *val = f32::chomp(reader)?;
+                                }
+                            }
+                            data
+                        }
+                        angle_type => special_angle(angle_type)?,
+                    };
+                    Property::CFrame(CFrame { position, angle })
+                }
+                21 => Property::EnumItem(EnumItem {
+                    enum_type: String::chomp(reader)?,
+                    value: u32::chomp(reader)?,
+                }),
                 23 => Property::NumberSequence(NumberSequence::chomp(reader)?),
                 25 => Property::ColorSequence(ColorSequence::chomp(reader)?),
                 27 => Property::NumberRange(NumberRange::chomp(reader)?),
-                28 => Property::Rect(Rect::chomp(reader)?),
+                28 => Property::Rect(Rect {
+                    top_left: chomp_attribute_vector2(reader)?,
+                    bottom_right: chomp_attribute_vector2(reader)?,
+                }),
+                33 => {
+                    // Fonts are stored in a different order than as a property
+                    let weight = u16::chomp(reader)? as i32;
+                    let style = u8::chomp(reader)? as i32;
+                    let family = String::chomp(reader)?;
+                    let cached_face_id = String::chomp(reader)?;
+
+                    Property::Font(FontFace {
+                        family,
+                        weight: FontWeight::try_from(weight)
+                            .map_err(|_| Error::unknown_variant(weight))?,
+                        style: FontStyle::try_from(style)
+                            .map_err(|_| Error::unknown_variant(style))?,
+                        cached_face_id,
+                    })
+                }
                 _ => return Err(Error::unknown_property(prop_ty)),
             };
 
@@ -620,18 +805,18 @@ impl<R: Read> Chomp<R> for Attributes {
 #[cfg(feature = "mesh-format")]
 impl<R: Read> Chomp<R> for ConvexHull {
     fn chomp(reader: &mut R) -> Result<Self> {
-        let unknown_1_len = i32::chomp(reader)?;
-        let mut unknown_1 = vec![0; unknown_1_len as usize];
-        reader.read_exact(&mut unknown_1)?;
+        let unknown_1_len = chomp_len(reader)?;
+        let unknown_1 = chomp_bytes(reader, unknown_1_len)?;
 
-        let unknown_2_len = i32::chomp(reader)?;
-        let mut unknown_2 = vec![0; unknown_2_len as usize];
-        reader.read_exact(&mut unknown_2)?;
+        let unknown_2_len = chomp_len(reader)?;
+        let unknown_2 = chomp_bytes(reader, unknown_2_len)?;
 
-        let vert_len = i32::chomp(reader)? / 3;
+        let vert_len = chomp_len(reader)? / 3;
         let vert_width = i32::chomp(reader)?;
-        assert_eq!(vert_width, 4, "Unexpected vertice width");
-        let mut vertices = Vec::with_capacity(vert_len as usize);
+        if vert_width != 4 {
+            return Err(Error::invalid_data("unexpected vertex width"));
+        }
+        let mut vertices = Vec::with_capacity(vert_len.min(MAX_PREALLOC));
         for _ in 0..vert_len {
             vertices.push(Vector3 {
                 x: f32::chomp(reader)?,
@@ -640,14 +825,10 @@ impl<R: Read> Chomp<R> for ConvexHull {
             });
         }
 
-        let faces_len = i32::chomp(reader)? / 3;
-        let mut faces = Vec::with_capacity(faces_len as usize);
+        let faces_len = chomp_len(reader)? / 3;
+        let mut faces = Vec::with_capacity(faces_len.min(MAX_PREALLOC));
         for _ in 0..faces_len {
-            faces.push((
-                i32::chomp(reader)? as usize,
-                i32::chomp(reader)? as usize,
-                i32::chomp(reader)? as usize,
-            ));
+            faces.push((chomp_len(reader)?, chomp_len(reader)?, chomp_len(reader)?));
         }
 
         Ok(ConvexHull {
@@ -834,4 +1015,202 @@ mod tests {
             [[0.0, 0.0, -1.0], [0.0, -1.0, 0.0], [-1.0, 0.0, 0.0],]
         );
     }
+
+    #[test]
+    fn test_cframe_rotation_ids() {
+        use crate::serde::encoding::PrintInterleaved;
+
+        // Every axis-aligned rotation is written as the ID it's read back from
+        let ids = [
+            2, 3, 5, 6, 7, 9, 10, 12, 13, 14, 16, 17, 20, 21, 23, 24, 25, 27, 28, 30, 31, 32, 34,
+            35,
+        ];
+        for id in ids {
+            let angle = special_angle(id).unwrap();
+            let mut bytes = Vec::new();
+            CFrame::print_interleaved(&mut bytes, &[CFrame::new(Vector3::ZERO, angle)]).unwrap();
+            assert_eq!(bytes[0], id);
+
+            let cframe = CFrame::chomp_interleaved(&mut &*bytes, 1)
+                .unwrap()
+                .remove(0);
+            assert_eq!(cframe.angle, angle);
+        }
+
+        // IDs 9 and 13 are the two rotations cycling the axes, and easy to mix up
+        assert_eq!(
+            special_angle(9).unwrap(),
+            [[0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0]]
+        );
+        assert_eq!(
+            special_angle(13).unwrap(),
+            [[0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+        );
+    }
+
+    #[test]
+    fn test_cframe_matrix_print() {
+        use crate::serde::encoding::PrintInterleaved;
+
+        // The same bytes `test_cframe_matrix` reads
+        let cframe = CFrame::new(
+            Vector3::ZERO,
+            [[0.5, 1.0, 1.5], [-0.5, -1.0, -1.5], [0.0, 0.0, 0.0]],
+        );
+        let mut bytes = Vec::new();
+        CFrame::print_interleaved(&mut bytes, &[cframe]).unwrap();
+        assert_eq!(
+            bytes,
+            [
+                0, 0, 0, 0, 63, 0, 0, 0, 191, 0, 0, 0, 0, 0, 0, 128, 63, 0, 0, 128, 191, 0, 0, 0,
+                0, 0, 0, 192, 63, 0, 0, 192, 191, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cframe_quat() {
+        use crate::serde::encoding::PrintInterleaved;
+
+        let half = core::f32::consts::FRAC_1_SQRT_2;
+        let cframes = [
+            CFrameQuat::new(Vector3::new(1.0, 2.0, 3.0), Quaternion::IDENTITY),
+            CFrameQuat::new(Vector3::ZERO, Quaternion::new(0.0, half, 0.0, half)),
+        ];
+        let mut bytes = Vec::new();
+        CFrameQuat::print_interleaved(&mut bytes, &cframes).unwrap();
+        assert_eq!(
+            CFrameQuat::chomp_interleaved(&mut &*bytes, 2).unwrap(),
+            cframes
+        );
+
+        // Axis-aligned rotations are stored by ID, the same as for regular CFrames
+        let mut bytes = vec![13];
+        bytes.extend_from_slice(&[0; 12]);
+        let cframe = CFrameQuat::chomp_interleaved(&mut &*bytes, 1)
+            .unwrap()
+            .remove(0);
+        let angle = CFrame::from(cframe).angle;
+        let expected = special_angle(13).unwrap();
+        for (row, expected) in angle.iter().zip(&expected) {
+            for (val, expected) in row.iter().zip(expected) {
+                assert!((val - expected).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn test_optional_cframe() {
+        use crate::serde::encoding::PrintInterleaved;
+
+        let angle = [[0.5, 1.0, 1.5], [-0.5, -1.0, -1.5], [0.0, 0.0, 1.0]];
+        let cframes = [
+            Some(CFrame::new(Vector3::new(1.0, 2.0, 3.0), angle)),
+            None,
+            Some(CFrame::new(
+                Vector3::new(-4.0, 0.0, 4.0),
+                special_angle(9).unwrap(),
+            )),
+        ];
+        let mut bytes = Vec::new();
+        <Option<CFrame>>::print_interleaved(&mut bytes, &cframes).unwrap();
+
+        let mut reader = &*bytes;
+        let new_cframes = <Option<CFrame>>::chomp_interleaved(&mut reader, 3).unwrap();
+        assert!(reader.is_empty());
+        assert_eq!(new_cframes.len(), 3);
+        for (new, old) in new_cframes.iter().zip(&cframes) {
+            match (new, old) {
+                (Some(new), Some(old)) => {
+                    assert_eq!(new.position, old.position);
+                    assert_eq!(new.angle, old.angle);
+                }
+                (None, None) => (),
+                _ => panic!("Expected presence to round-trip"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_attributes() {
+        use crate::serde::encoding::Print;
+
+        let mut attrs = Attributes::default();
+        attrs.insert(String::from("Nil"), Property::Nil);
+        attrs.insert(String::from("Int"), Property::Int32(-5));
+        attrs.insert(String::from("Float"), Property::Float(0.5));
+        attrs.insert(
+            String::from("Vector2"),
+            Property::Vector2(Vector2::new(1.0, -2.0)),
+        );
+        attrs.insert(
+            String::from("CFrame"),
+            Property::CFrame(CFrame::new(
+                Vector3::new(1.0, 2.0, 3.0),
+                [[0.0, 1.0, 0.0], [-1.0, 0.0, 0.0], [0.0, 0.0, 1.0]],
+            )),
+        );
+        attrs.insert(
+            String::from("Enum"),
+            Property::EnumItem(EnumItem::new(String::from("Material"), 256)),
+        );
+        attrs.insert(
+            String::from("Font"),
+            Property::Font(FontFace {
+                family: String::from("rbxasset://fonts/families/Arial.json"),
+                weight: FontWeight::Bold,
+                style: FontStyle::Italic,
+                cached_face_id: String::new(),
+            }),
+        );
+
+        let mut bytes = Vec::new();
+        Attributes::print(&mut bytes, attrs).unwrap();
+        let mut reader = &*bytes;
+        let attrs = Attributes::chomp(&mut reader).unwrap();
+        assert!(reader.is_empty());
+
+        assert!(matches!(attrs["Nil"], Property::Nil));
+        assert!(matches!(attrs["Int"], Property::Int32(-5)));
+        assert!(matches!(attrs["Float"], Property::Float(val) if val == 0.5));
+        assert!(
+            matches!(&attrs["Vector2"], Property::Vector2(val) if *val == Vector2::new(1.0, -2.0))
+        );
+        match &attrs["CFrame"] {
+            Property::CFrame(cframe) => {
+                assert_eq!(cframe.position, Vector3::new(1.0, 2.0, 3.0));
+                assert_eq!(
+                    cframe.angle,
+                    [[0.0, 1.0, 0.0], [-1.0, 0.0, 0.0], [0.0, 0.0, 1.0]]
+                );
+            }
+            _ => panic!("Expected a CFrame"),
+        }
+        assert!(matches!(
+            &attrs["Enum"],
+            Property::EnumItem(item) if item.enum_type == "Material" && item.value == 256
+        ));
+        assert!(matches!(
+            &attrs["Font"],
+            Property::Font(FontFace {
+                weight: FontWeight::Bold,
+                style: FontStyle::Italic,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_attribute_vector3() {
+        // Attribute floats are plain little-endian, not transformed like property floats
+        let mut bytes = vec![1, 0, 0, 0, 3, 0, 0, 0, b'P', b'o', b's', 17];
+        for val in [1f32, -2.0, 0.5] {
+            bytes.extend_from_slice(&val.to_le_bytes());
+        }
+        let attrs = Attributes::chomp(&mut &*bytes).unwrap();
+
+        assert!(
+            matches!(&attrs["Pos"], Property::Vector3(val) if *val == Vector3::new(1.0, -2.0, 0.5))
+        );
+    }
 }
@@ -1,5 +1,6 @@
 use crate::model::*;
-use crate::serde::encoding::{encode_f32, encode_i32};
+use crate::serde::encoding::{encode_cumulative, encode_f32, encode_i32};
+use crate::serde::internal::RawContent;
 use crate::serde::io::Write;
 use crate::serde::{Error, Result};
 
@@ -65,6 +66,13 @@ impl<W: Write> Print<W> for u16 {
     }
 }
 
+impl<W: Write> Print<W> for u32 {
+    fn print(writer: &mut W, val: Self) -> Result<()> {
+        writer.write_all(&val.to_le_bytes())?;
+        Ok(())
+    }
+}
+
 impl<W: Write> Print<W> for i16 {
     fn print(writer: &mut W, val: Self) -> Result<()> {
         writer.write_all(&val.to_le_bytes())?;
@@ -403,10 +411,23 @@ impl<W: Write> Print<W> for Vector3Int16 {
     }
 }
 
+/// Write the positions of a set of `CFrame`s, as separate interleaved arrays of each axis
+fn print_positions<'a, W: Write>(
+    writer: &mut W,
+    positions: impl Iterator<Item = &'a Vector3> + Clone,
+) -> Result<()> {
+    let xs = positions.clone().map(|pos| pos.x).collect::<Vec<_>>();
+    let ys = positions.clone().map(|pos| pos.y).collect::<Vec<_>>();
+    let zs = positions.map(|pos| pos.z).collect::<Vec<_>>();
+    f32::print_interleaved(writer, &xs)?;
+    f32::print_interleaved(writer, &ys)?;
+    f32::print_interleaved(writer, &zs)?;
+    Ok(())
+}
+
 impl<W: Write> PrintInterleaved<W> for CFrame {
     fn print_interleaved(writer: &mut W, vals: &[Self]) -> Result<()> {
         let mut angles = Vec::new();
-        let mut positions = Vec::with_capacity(vals.len());
 
         for cframe in vals {
             let angle_ty = float_match! {
@@ -416,10 +437,10 @@ impl<W: Write> PrintInterleaved<W> for CFrame {
                 [[1f32, 0f32, 0f32], [0f32, -1f32, 0f32], [0f32, 0f32, -1f32]]; 5,
                 [[1f32, 0f32, 0f32], [0f32, 0f32, 1f32], [0f32, -1f32, 0f32]]; 6,
                 [[0f32, 1f32, 0f32], [1f32, 0f32, 0f32], [0f32, 0f32, -1f32]]; 7,
-                [[0f32, 0f32, 1f32], [1f32, 0f32, 0f32], [0f32, 1f32, 0f32]]; 9,
+                [[0f32, 1f32, 0f32], [0f32, 0f32, 1f32], [1f32, 0f32, 0f32]]; 9,
                 [[0f32, -1f32, 0f32], [1f32, 0f32, 0f32], [0f32, 0f32, 1f32]]; 10,
                 [[0f32, 0f32, -1f32], [1f32, 0f32, 0f32], [0f32, -1f32, 0f32]]; 12,
-                [[0f32, 1f32, 0f32], [0f32, 0f32, 1f32], [1f32, 0f32, 0f32]]; 13,
+                [[0f32, 0f32, 1f32], [1f32, 0f32, 0f32], [0f32, 1f32, 0f32]]; 13,
                 [[0f32, 0f32, -1f32], [0f32, 1f32, 0f32], [1f32, 0f32, 0f32]]; 14,
                 [[0f32, -1f32, 0f32], [0f32, 0f32, -1f32], [1f32, 0f32, 0f32]]; 16,
                 [[0f32, 0f32, 1f32], [0f32, -1f32, 0f32], [1f32, 0f32, 0f32]]; 17,
@@ -439,25 +460,107 @@ impl<W: Write> PrintInterleaved<W> for CFrame {
 
             angles.push(angle_ty);
 
+            // Written in the same order `chomp_interleaved` reads them
             if angle_ty == 0 {
-                for i in &cframe.angle {
-                    for j in i {
-                        f32::print(&mut angles, *j)?;
+                for i in 0..3 {
+                    for row in &cframe.angle {
+                        f32::print(&mut angles, row[i])?;
                     }
                 }
             }
-
-            positions.push(&cframe.position);
         }
 
         writer.write_all(&angles)?;
-        for pos in positions {
-            Vector3::print(writer, pos.clone())?;
+        print_positions(writer, vals.iter().map(|val| &val.position))
+    }
+}
+
+impl<W: Write> PrintInterleaved<W> for CFrameQuat {
+    fn print_interleaved(writer: &mut W, vals: &[Self]) -> Result<()> {
+        // Rotations are always written in full, as converting them to one of the axis-aligned
+        // IDs isn't exact
+        for cframe in vals {
+            u8::print(writer, 0)?;
+            let Quaternion { x, y, z, w } = cframe.rotation;
+            for component in [x, y, z, w] {
+                f32::print(writer, component)?;
+            }
+        }
+
+        print_positions(writer, vals.iter().map(|val| &val.position))
+    }
+}
+
+impl<W: Write> PrintInterleaved<W> for Option<CFrame> {
+    fn print_interleaved(writer: &mut W, vals: &[Self]) -> Result<()> {
+        // Missing values still take up a slot in the CFrame array, so are written as the default
+        u8::print(writer, 16)?;
+        let cframes = vals
+            .iter()
+            .map(|val| val.clone().unwrap_or_default())
+            .collect::<Vec<_>>();
+        CFrame::print_interleaved(writer, &cframes)?;
+
+        u8::print(writer, 2)?;
+        for val in vals {
+            bool::print(writer, val.is_some())?;
         }
         Ok(())
     }
 }
 
+impl<W: Write> PrintInterleaved<W> for SecurityCapabilities {
+    fn print_interleaved(writer: &mut W, vals: &[Self]) -> Result<()> {
+        let mut data = vec![0; vals.len() * 8];
+
+        for i in 0..vals.len() {
+            let bytes = vals[i].0.to_be_bytes();
+            for j in 0..8 {
+                data[i + j * vals.len()] = bytes[j];
+            }
+        }
+
+        writer.write_all(&data)?;
+        Ok(())
+    }
+}
+
+impl<W: Write> PrintInterleaved<W> for RawContent {
+    fn print_interleaved(writer: &mut W, vals: &[Self]) -> Result<()> {
+        let mut source_types = Vec::with_capacity(vals.len());
+        let mut uris = Vec::new();
+        let mut objects = Vec::new();
+        for val in vals {
+            match val {
+                RawContent::None => source_types.push(0),
+                RawContent::Uri(uri) => {
+                    source_types.push(1);
+                    uris.push(uri);
+                }
+                RawContent::Object(id) => {
+                    source_types.push(2);
+                    objects.push(*id);
+                }
+            }
+        }
+
+        i32::print_interleaved_transformed(writer, &source_types)?;
+
+        i32::print(writer, uris.len() as i32)?;
+        for uri in uris {
+            String::print(writer, uri.clone())?;
+        }
+
+        encode_cumulative(&mut objects);
+        i32::print(writer, objects.len() as i32)?;
+        i32::print_interleaved_transformed(writer, &objects)?;
+
+        // No external objects
+        i32::print(writer, 0)?;
+        Ok(())
+    }
+}
+
 impl<W: Write> Print<W> for NumberSequence {
     fn print(writer: &mut W, val: Self) -> Result<()> {
         i32::print(writer, val.keypoints.len() as i32)?;
@@ -570,12 +673,40 @@ impl<W: Write> Print<W> for ConvexHull {
     }
 }
 
+fn print_attribute_vector2<W: Write>(writer: &mut W, val: &Vector2) -> Result<()> {
+    f32::print(writer, val.x)?;
+    f32::print(writer, val.y)?;
+    Ok(())
+}
+
+fn print_attribute_vector3<W: Write>(writer: &mut W, val: &Vector3) -> Result<()> {
+    f32::print(writer, val.x)?;
+    f32::print(writer, val.y)?;
+    f32::print(writer, val.z)?;
+    Ok(())
+}
+
+fn print_attribute_cframe<W: Write>(writer: &mut W, val: &CFrame) -> Result<()> {
+    u8::print(writer, 20)?;
+    print_attribute_vector3(writer, &val.position)?;
+    // Rotations are always written in full, rather than picking out axis-aligned IDs
+    u8::print(writer, 0)?;
+    for row in &val.angle {
+        for val in row {
+            f32::print(writer, *val)?;
+        }
+    }
+    Ok(())
+}
+
 impl<W: Write> Print<W> for Attributes {
     fn print(writer: &mut W, val: Self) -> Result<()> {
         i32::print(writer, val.backing.len() as i32)?;
         for (name, prop) in val.backing {
             String::print(writer, name.clone())?;
+            // Unlike properties, attribute floats are never transformed
             match prop {
+                Property::Nil => u8::print(writer, 1)?,
                 Property::TextString(val) => {
                     u8::print(writer, 2)?;
                     String::print(writer, val)?;
@@ -584,6 +715,14 @@ impl<W: Write> Print<W> for Attributes {
                     u8::print(writer, 3)?;
                     bool::print(writer, val)?;
                 }
+                Property::Int32(val) => {
+                    u8::print(writer, 4)?;
+                    i32::print(writer, val)?;
+                }
+                Property::Float(val) => {
+                    u8::print(writer, 5)?;
+                    f32::print(writer, val)?;
+                }
                 Property::Double(val) => {
                     u8::print(writer, 6)?;
                     f64::print(writer, val)?;
@@ -606,11 +745,18 @@ impl<W: Write> Print<W> for Attributes {
                 }
                 Property::Vector2(val) => {
                     u8::print(writer, 16)?;
-                    Vector2::print(writer, val)?;
+                    print_attribute_vector2(writer, &val)?;
                 }
                 Property::Vector3(val) => {
                     u8::print(writer, 17)?;
-                    Vector3::print(writer, val)?;
+                    print_attribute_vector3(writer, &val)?;
+                }
+                Property::CFrame(val) => print_attribute_cframe(writer, &val)?,
+                Property::CFrameQuat(val) => print_attribute_cframe(writer, &val.into())?,
+                Property::EnumItem(val) => {
+                    u8::print(writer, 21)?;
+                    String::print(writer, val.enum_type)?;
+                    u32::print(writer, val.value)?;
                 }
                 Property::NumberSequence(val) => {
                     u8::print(writer, 23)?;
@@ -626,7 +772,16 @@ impl<W: Write> Print<W> for Attributes {
                 }
                 Property::Rect(val) => {
                     u8::print(writer, 28)?;
-                    Rect::print(writer, val)?;
+                    print_attribute_vector2(writer, &val.top_left)?;
+                    print_attribute_vector2(writer, &val.bottom_right)?;
+                }
+                Property::Font(val) => {
+                    // Fonts are stored in a different order than as a property
+                    u8::print(writer, 33)?;
+                    u16::print(writer, val.weight as u16)?;
+                    u8::print(writer, val.style as u8)?;
+                    String::print(writer, val.family)?;
+                    String::print(writer, val.cached_face_id)?;
                 }
                 _ => {
                     return Err(Error::wrong_property_type(name, None));
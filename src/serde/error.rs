@@ -1,6 +1,7 @@
 //! Common error handling machinery for serialization/deserialization
 
 use crate::model::property::PropertyType;
+use alloc::boxed::Box;
 use alloc::format;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
@@ -9,6 +10,64 @@ use core::fmt;
 /// A result used for all ser/de methods that return a result
 pub type Result<T> = core::result::Result<T, Error>;
 
+/// Information about where in the input an error occurred. Each field is only present if it was
+/// known at the point of failure.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ErrorContext {
+    /// Byte offset into the file. For binary files this is the start of the chunk being read,
+    /// for XML files the start of the element being read.
+    pub offset: Option<u64>,
+    /// Index of the chunk being read, counting from the first chunk in the file
+    pub chunk_index: Option<usize>,
+    /// Name of the chunk being read, such as `INST` or `PROP`
+    pub chunk_name: Option<String>,
+    /// Index of the class being read, as referenced by `INST` and `PROP` chunks
+    pub class_index: Option<i32>,
+    /// Name of the class being read
+    pub class_name: Option<String>,
+    /// Name of the property being read
+    pub property_name: Option<String>,
+    /// ID of the instance being read
+    pub instance_id: Option<i32>,
+}
+
+impl ErrorContext {
+    /// Whether no context information is present
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == ErrorContext::default()
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(index) = self.chunk_index {
+            match &self.chunk_name {
+                Some(name) => parts.push(format!("chunk {index} (`{name}`)")),
+                None => parts.push(format!("chunk {index}")),
+            }
+        }
+        if let Some(offset) = self.offset {
+            parts.push(format!("offset {offset}"));
+        }
+        match (self.class_index, &self.class_name) {
+            (Some(index), Some(name)) => parts.push(format!("class {index} (`{name}`)")),
+            (Some(index), None) => parts.push(format!("class {index}")),
+            (None, Some(name)) => parts.push(format!("class `{name}`")),
+            (None, None) => (),
+        }
+        if let Some(name) = &self.property_name {
+            parts.push(format!("property `{name}`"));
+        }
+        if let Some(id) = self.instance_id {
+            parts.push(format!("instance {id}"));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
 /// A common error handling type for ser/de.
 #[derive(Debug)]
 #[non_exhaustive]
@@ -17,6 +76,8 @@ pub struct Error {
     backtrace: std::backtrace::Backtrace,
     /// The kind of this error, exposed for ease of matching by users
     pub kind: ErrorKind,
+    /// Where in the input this error occurred
+    pub context: Box<ErrorContext>,
 }
 
 impl Error {
@@ -25,15 +86,52 @@ impl Error {
             #[cfg(all(feature = "std", feature = "unstable"))]
             backtrace: std::backtrace::Backtrace::capture(),
             kind,
+            context: Box::default(),
         }
     }
 
-    pub(crate) fn bad_magic() -> Error {
-        Error::from_kind(ErrorKind::BadMagic)
+    // Context is added as an error propagates outwards, so the innermost (most specific)
+    // information is kept if it was already set
+
+    pub(crate) fn with_offset(mut self, offset: u64) -> Error {
+        self.context.offset.get_or_insert(offset);
+        self
     }
 
-    pub(crate) fn unknown_block(name: String) -> Error {
-        Error::from_kind(ErrorKind::UnknownBlock(name))
+    pub(crate) fn with_chunk(mut self, index: usize, name: &str) -> Error {
+        self.context.chunk_index.get_or_insert(index);
+        self.context
+            .chunk_name
+            .get_or_insert_with(|| name.to_string());
+        self
+    }
+
+    pub(crate) fn with_class_index(mut self, index: i32) -> Error {
+        self.context.class_index.get_or_insert(index);
+        self
+    }
+
+    pub(crate) fn with_class_name(mut self, name: &str) -> Error {
+        self.context
+            .class_name
+            .get_or_insert_with(|| name.to_string());
+        self
+    }
+
+    pub(crate) fn with_property(mut self, name: &str) -> Error {
+        self.context
+            .property_name
+            .get_or_insert_with(|| name.to_string());
+        self
+    }
+
+    pub(crate) fn with_instance(mut self, id: i32) -> Error {
+        self.context.instance_id.get_or_insert(id);
+        self
+    }
+
+    pub(crate) fn bad_magic() -> Error {
+        Error::from_kind(ErrorKind::BadMagic)
     }
 
     pub(crate) fn unknown_class(id: i32) -> Error {
@@ -44,6 +142,14 @@ impl Error {
         Error::from_kind(ErrorKind::UnknownInstance(inst))
     }
 
+    pub(crate) fn unknown_shared_string(id: i32) -> Error {
+        Error::from_kind(ErrorKind::UnknownSharedString(id))
+    }
+
+    pub(crate) fn shared_string_hash_mismatch(index: usize) -> Error {
+        Error::from_kind(ErrorKind::SharedStringHashMismatch(index))
+    }
+
     pub(crate) fn unknown_cframe(id: u8) -> Error {
         Error::from_kind(ErrorKind::UnknownCFrame(id))
     }
@@ -72,10 +178,6 @@ impl Error {
         Error::from_kind(ErrorKind::MissingProperty(name))
     }
 
-    pub(crate) fn unconsumed_properties(ty: String, remaining: Vec<String>) -> Error {
-        Error::from_kind(ErrorKind::UnconsumedProperties(ty, remaining))
-    }
-
     pub(crate) fn inconsistent_tree() -> Error {
         Error::from_kind(ErrorKind::InconsistentTree)
     }
@@ -84,14 +186,72 @@ impl Error {
         Error::from_kind(ErrorKind::InvalidString)
     }
 
+    pub(crate) fn invalid_length(len: i64) -> Error {
+        Error::from_kind(ErrorKind::InvalidLength(len))
+    }
+
+    pub(crate) fn invalid_data(msg: &'static str) -> Error {
+        Error::from_kind(ErrorKind::InvalidData(msg))
+    }
+
+    pub(crate) fn decompressed_size_exceeded(limit: usize) -> Error {
+        Error::from_kind(ErrorKind::DecompressedSizeExceeded(limit))
+    }
+
+    pub(crate) fn too_many_instances(limit: usize) -> Error {
+        Error::from_kind(ErrorKind::TooManyInstances(limit))
+    }
+
+    pub(crate) fn too_many_classes(limit: usize) -> Error {
+        Error::from_kind(ErrorKind::TooManyClasses(limit))
+    }
+
+    pub(crate) fn string_too_long(limit: usize) -> Error {
+        Error::from_kind(ErrorKind::StringTooLong(limit))
+    }
+
+    pub(crate) fn tree_too_deep(limit: usize) -> Error {
+        Error::from_kind(ErrorKind::TreeTooDeep(limit))
+    }
+
     pub(crate) fn invalid_lz4() -> Error {
         Error::from_kind(ErrorKind::InvalidLz4)
     }
+
+    #[cfg(feature = "gzip")]
+    pub(crate) fn invalid_gzip() -> Error {
+        Error::from_kind(ErrorKind::InvalidGzip)
+    }
+
+    #[cfg(feature = "zstd")]
+    pub(crate) fn invalid_zstd() -> Error {
+        Error::from_kind(ErrorKind::InvalidZstd)
+    }
+
+    #[cfg_attr(all(feature = "gzip", feature = "zstd"), allow(dead_code))]
+    pub(crate) fn missing_feature(feature: &'static str) -> Error {
+        Error::from_kind(ErrorKind::MissingFeature(feature))
+    }
+
+    pub(crate) fn invalid_xml(msg: String) -> Error {
+        Error::from_kind(ErrorKind::InvalidXml(msg))
+    }
+
+    pub(crate) fn unknown_xml_type(name: String) -> Error {
+        Error::from_kind(ErrorKind::UnknownXmlType(name))
+    }
+
+    pub(crate) fn unknown_referent(referent: String) -> Error {
+        Error::from_kind(ErrorKind::UnknownReferent(referent))
+    }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "RBXM Ser/De Error: {}", self.kind)?;
+        if !self.context.is_empty() {
+            writeln!(f, "    at {}", self.context)?;
+        }
         #[cfg(all(feature = "std", feature = "unstable"))]
         {
             writeln!(f, "{}", self.backtrace)?;
@@ -128,13 +288,16 @@ pub enum ErrorKind {
     /// RBXM file had invalid magic bytes at the start/end
     BadMagic,
 
-    /// An RBXM block name wasn't recognized (A block name is always four or
-    /// fewer uppercase characters)
-    UnknownBlock(String),
     /// A class index wasn't recognized, generally a PROP block references an invalid INST block
     UnknownClass(i32),
     /// An instance ID wasn't recognized, generally a PRNT block reference an invalid instance ID
     UnknownInstance(i32),
+    /// A shared string ID wasn't recognized, generally a PROP block references a string
+    /// past the end of the SSTR block
+    UnknownSharedString(i32),
+    /// A shared string's stored hash didn't match its contents. Only checked if enabled in the
+    /// deserializer options.
+    SharedStringHashMismatch(usize),
     /// A CFrame ID (indicating certain special values) was unrecognized
     UnknownCFrame(u8),
     /// A property type ID wasn't recognized
@@ -149,8 +312,6 @@ pub enum ErrorKind {
     WrongPropertyType(String, Option<(PropertyType, PropertyType)>),
     /// An instance successfully parsed, but was missing an expected property
     MissingProperty(String),
-    /// An instance successfully parsed, but contained more properties than expected
-    UnconsumedProperties(String, Vec<String>),
     /// A parent->child relationship was encoded in the RBXM incorrectly
     InconsistentTree,
 
@@ -161,19 +322,50 @@ pub enum ErrorKind {
     ),
     /// A string value contained invalid bytes
     InvalidString,
+    /// A length or count was negative, or larger than the data it describes
+    InvalidLength(i64),
+    /// A value with a fixed expected form, such as a reserved field or marker, didn't match it
+    InvalidData(&'static str),
+    /// The input decompressed to more bytes than the configured limit
+    DecompressedSizeExceeded(usize),
+    /// The input contained more instances than the configured limit
+    TooManyInstances(usize),
+    /// The input contained more classes than the configured limit
+    TooManyClasses(usize),
+    /// The input contained a string longer than the configured limit
+    StringTooLong(usize),
+    /// The input's instance tree was nested deeper than the configured limit
+    TreeTooDeep(usize),
     /// An LZ4 block contained invalid bytes
     InvalidLz4,
+    /// A zstd block contained invalid bytes
+    InvalidZstd,
+    /// A gzip-wrapped file couldn't be decompressed, or contained more gzip data
+    InvalidGzip,
+    /// The input requires support from a cargo feature that isn't enabled
+    MissingFeature(&'static str),
+    /// An XML document was malformed, or didn't match the expected model structure
+    InvalidXml(String),
+    /// An XML property element had a type name that wasn't recognized
+    UnknownXmlType(String),
+    /// An XML reference pointed at a referent that no item in the document has
+    UnknownReferent(String),
 }
 
 impl fmt::Display for ErrorKind {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         let msg = match self {
             ErrorKind::BadMagic => "Invalid File Magic".to_string(),
-            ErrorKind::UnknownBlock(block) => format!("Unrecognized data-block type `{block}`"),
             ErrorKind::UnknownClass(id) => format!("Reference to unknown class with ID `{id}`"),
             ErrorKind::UnknownInstance(id) => {
                 format!("Reference to unknown instance with ID `{id}`")
             }
+            ErrorKind::UnknownSharedString(id) => {
+                format!("Reference to unknown shared string with ID `{id}`")
+            }
+            ErrorKind::SharedStringHashMismatch(index) => {
+                format!("Shared string with ID `{index}` didn't match its hash")
+            }
             ErrorKind::UnknownCFrame(id) => format!("Unknown CFrame type `{id}`"),
             ErrorKind::UnknownProperty(id) => format!("Unknown property type `{id}`"),
             ErrorKind::UnknownVariant(id) => format!("Unknown enum variant with ID `{id}`"),
@@ -191,16 +383,40 @@ impl fmt::Display for ErrorKind {
                 None => format!("Property {prop_name} was of a wrong type"),
             },
             ErrorKind::MissingProperty(prop_name) => format!("Property {prop_name} was missing"),
-            ErrorKind::UnconsumedProperties(class_name, prop_names) => format!(
-                "Instance type {class_name} had unexpected properties with names {prop_names:?}"
-            ),
             ErrorKind::InconsistentTree => {
                 String::from("RBXM parent->child relationships were inconsistent")
             }
 
             ErrorKind::IoError(err) => format!("Error in IO: {err}"),
             ErrorKind::InvalidString => "String contained invalid UTF data".to_string(),
+            ErrorKind::InvalidLength(len) => format!("Invalid length or count `{len}`"),
+            ErrorKind::InvalidData(msg) => format!("Invalid data: {msg}"),
+            ErrorKind::DecompressedSizeExceeded(limit) => {
+                format!("Input decompressed to more than the limit of {limit} bytes")
+            }
+            ErrorKind::TooManyInstances(limit) => {
+                format!("Input contained more than the limit of {limit} instances")
+            }
+            ErrorKind::TooManyClasses(limit) => {
+                format!("Input contained more than the limit of {limit} classes")
+            }
+            ErrorKind::StringTooLong(limit) => {
+                format!("Input contained a string longer than the limit of {limit} bytes")
+            }
+            ErrorKind::TreeTooDeep(limit) => {
+                format!("Instance tree was nested deeper than the limit of {limit}")
+            }
             ErrorKind::InvalidLz4 => "LZ4 block couldn't be deserialized".to_string(),
+            ErrorKind::InvalidZstd => "Zstd block couldn't be deserialized".to_string(),
+            ErrorKind::InvalidGzip => "Gzip data couldn't be decompressed".to_string(),
+            ErrorKind::MissingFeature(feature) => {
+                format!("Input requires the `{feature}` feature to be enabled")
+            }
+            ErrorKind::InvalidXml(msg) => format!("Invalid XML model: {msg}"),
+            ErrorKind::UnknownXmlType(name) => format!("Unknown XML property type `{name}`"),
+            ErrorKind::UnknownReferent(referent) => {
+                format!("Reference to unknown referent `{referent}`")
+            }
         };
         write!(fmt, "{msg}")
     }
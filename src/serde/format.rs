@@ -0,0 +1,340 @@
+//! Detection of the different file formats a model may be stored in, and entry points which
+//! dispatch to the correct implementation for each
+
+use crate::model::RbxModel;
+use crate::serde::de::DeserializerOptions;
+use crate::serde::io::{Read, Write};
+use crate::serde::{de, ser, xml, Error, Result};
+
+use alloc::vec::Vec;
+
+const BINARY_MAGIC: &[u8] = b"<roblox!";
+const GZIP_MAGIC: &[u8] = &[0x1F, 0x8B];
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+/// The underlying encoding used by a model file
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum FormatKind {
+    /// The binary format, as used by `.rbxm` and `.rbxl` files
+    #[default]
+    Binary,
+    /// The XML format, as used by `.rbxmx` and `.rbxlx` files
+    Xml,
+}
+
+/// The format of a model file, as detected when reading or requested when writing
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Format {
+    /// The encoding of the model itself
+    pub kind: FormatKind,
+    /// Whether the file is additionally wrapped in gzip compression. Requires the `gzip`
+    /// feature to read or write.
+    pub gzip: bool,
+}
+
+impl Format {
+    /// An uncompressed binary file
+    pub const BINARY: Format = Format {
+        kind: FormatKind::Binary,
+        gzip: false,
+    };
+    /// An uncompressed XML file
+    pub const XML: Format = Format {
+        kind: FormatKind::Xml,
+        gzip: false,
+    };
+
+    fn detect_kind(data: &[u8]) -> Option<FormatKind> {
+        if data.starts_with(BINARY_MAGIC) {
+            return Some(FormatKind::Binary);
+        }
+
+        let data = data.strip_prefix(UTF8_BOM).unwrap_or(data);
+        let start = data.iter().position(|c| !c.is_ascii_whitespace())?;
+        let data = &data[start..];
+
+        let is_root = data.strip_prefix(b"<roblox").is_some_and(|rest| {
+            rest.first()
+                .is_some_and(|&c| c == b'>' || c.is_ascii_whitespace())
+        });
+
+        if is_root || data.starts_with(b"<?xml") {
+            Some(FormatKind::Xml)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(feature = "gzip")]
+fn gzip_decompress(data: &[u8], options: &DeserializerOptions) -> Result<Vec<u8>> {
+    // Read at most one byte past the limit, enough to tell that it was exceeded
+    let limit = options
+        .max_decompressed_bytes
+        .map_or(u64::MAX, |limit| (limit as u64).saturating_add(1));
+    let mut out = Vec::new();
+    let mut decoder = std::io::Read::take(flate2::read::GzDecoder::new(data), limit);
+    std::io::Read::read_to_end(&mut decoder, &mut out).map_err(|_| Error::invalid_gzip())?;
+    options.check_decompressed(out.len())?;
+    Ok(out)
+}
+
+#[cfg(feature = "gzip")]
+fn gzip_compress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    std::io::Write::write_all(&mut encoder, data)?;
+    Ok(encoder.finish()?)
+}
+
+/// Read a model from a raw byte slice, detecting which format it is stored in. The detected
+/// format is recorded on the model, and can be retrieved with [`RbxModel::format`].
+pub fn from_bytes(bytes: &[u8]) -> Result<RbxModel> {
+    from_bytes_with_options(bytes, &DeserializerOptions::default()).map(|(model, _)| model)
+}
+
+/// Read a model from a raw byte slice with a set of deserialization options, detecting which
+/// format it is stored in. Returns the model along with any warnings produced. A single layer of
+/// gzip compression is unwrapped, gzip data nested inside it is an error.
+pub fn from_bytes_with_options(
+    bytes: &[u8],
+    options: &DeserializerOptions,
+) -> Result<(RbxModel, Vec<Error>)> {
+    if bytes.starts_with(GZIP_MAGIC) {
+        #[cfg(feature = "gzip")]
+        {
+            let data = gzip_decompress(bytes, options)?;
+            if data.starts_with(GZIP_MAGIC) {
+                return Err(Error::invalid_gzip());
+            }
+            let (mut model, warnings) = from_uncompressed(&data, options)?;
+            model.format.gzip = true;
+            return Ok((model, warnings));
+        }
+        #[cfg(not(feature = "gzip"))]
+        return Err(Error::missing_feature("gzip"));
+    }
+
+    from_uncompressed(bytes, options)
+}
+
+fn from_uncompressed(
+    bytes: &[u8],
+    options: &DeserializerOptions,
+) -> Result<(RbxModel, Vec<Error>)> {
+    match Format::detect_kind(bytes) {
+        Some(FormatKind::Binary) => {
+            de::Deserializer::with_options(bytes, options.clone()).deserialize_with_warnings()
+        }
+        Some(FormatKind::Xml) => {
+            xml::de::Deserializer::with_options(bytes, options.clone()).deserialize_with_warnings()
+        }
+        None => Err(Error::bad_magic()),
+    }
+}
+
+/// Read a model from the provided IO reader, detecting which format it is stored in
+pub fn from_reader<R: Read>(reader: R) -> Result<RbxModel> {
+    from_reader_with_options(reader, &DeserializerOptions::default()).map(|(model, _)| model)
+}
+
+/// Read a model from the provided IO reader with a set of deserialization options, detecting
+/// which format it is stored in. Returns the model along with any warnings produced.
+pub fn from_reader_with_options<R: Read>(
+    mut reader: R,
+    options: &DeserializerOptions,
+) -> Result<(RbxModel, Vec<Error>)> {
+    let data = options.read_limited(&mut reader)?;
+    from_bytes_with_options(&data, options)
+}
+
+/// Read a model from an existing file, detecting which format it is stored in
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[cfg(feature = "std")]
+pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> Result<RbxModel> {
+    from_reader(std::fs::File::open(path)?)
+}
+
+/// Write a model to a vector as raw bytes in the provided format, and return it
+pub fn to_bytes_format(model: &RbxModel, format: Format) -> Result<Vec<u8>> {
+    let out = match format.kind {
+        FormatKind::Binary => ser::to_bytes(model)?,
+        FormatKind::Xml => xml::to_string(model)?.into_bytes(),
+    };
+
+    if format.gzip {
+        #[cfg(feature = "gzip")]
+        return gzip_compress(&out);
+        #[cfg(not(feature = "gzip"))]
+        return Err(Error::missing_feature("gzip"));
+    }
+
+    Ok(out)
+}
+
+/// Write a model to a vector as raw bytes in the format recorded on it, and return it
+pub fn to_bytes(model: &RbxModel) -> Result<Vec<u8>> {
+    to_bytes_format(model, model.format())
+}
+
+/// Write a model out to a provided IO writer in the provided format
+pub fn to_writer_format<W: Write>(mut writer: W, model: &RbxModel, format: Format) -> Result<()> {
+    writer.write_all(&to_bytes_format(model, format)?)
+}
+
+/// Write a model out to a provided IO writer, in the format recorded on it. This will be the
+/// format it was read from, or binary for a newly created model.
+pub fn to_writer<W: Write>(writer: W, model: &RbxModel) -> Result<()> {
+    to_writer_format(writer, model, model.format())
+}
+
+/// Write a model out to a file in the format recorded on it, creating it if necessary
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[cfg(feature = "std")]
+pub fn to_file<P: AsRef<std::path::Path>>(path: P, model: &RbxModel) -> Result<()> {
+    to_writer(std::fs::File::create(path)?, model)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Instance, Property};
+    use alloc::collections::BTreeMap;
+    use alloc::string::{String, ToString};
+
+    fn make_model() -> RbxModel {
+        let model = RbxModel::new();
+        let mut props = BTreeMap::new();
+        props.insert(
+            String::from("Name"),
+            Property::TextString(String::from("Root")),
+        );
+        model
+            .tree()
+            .add_root(Instance::Other(String::from("Custom"), props));
+        model
+    }
+
+    #[test]
+    fn test_detect_kind() {
+        assert_eq!(
+            Format::detect_kind(b"<roblox!\x89\xff\r\n\x1a\n\0\0"),
+            Some(FormatKind::Binary)
+        );
+        assert_eq!(
+            Format::detect_kind(b"<roblox version=\"4\">"),
+            Some(FormatKind::Xml)
+        );
+        assert_eq!(
+            Format::detect_kind(b"\xEF\xBB\xBF\r\n<?xml version=\"1.0\"?>"),
+            Some(FormatKind::Xml)
+        );
+        assert_eq!(Format::detect_kind(b"<robloxian>"), None);
+        assert_eq!(Format::detect_kind(b""), None);
+    }
+
+    #[test]
+    fn test_round_trip_formats() {
+        let mut model = make_model();
+
+        let binary = to_bytes(&model).unwrap();
+        let new_model = from_bytes(&binary).unwrap();
+        assert_eq!(new_model.format(), Format::BINARY);
+
+        model.set_format(Format::XML);
+        let xml = to_bytes(&model).unwrap();
+        let new_model = from_bytes(&xml).unwrap();
+        assert_eq!(new_model.format(), Format::XML);
+        assert_eq!(new_model.get_path("Root").unwrap().name(), "Root");
+    }
+
+    #[test]
+    fn test_sibling_order() {
+        let mut model = make_model();
+        let root = model.tree().root_keys().next().unwrap();
+        for name in ["C", "A", "B"] {
+            let mut props = BTreeMap::new();
+            props.insert(
+                String::from("Name"),
+                Property::TextString(String::from(name)),
+            );
+            model
+                .tree()
+                .new_child(Instance::Other(String::from("Custom"), props), root);
+        }
+        // Move `C` to the end, so sibling order differs from creation order
+        let first = model.tree().child_keys_of(root).next().unwrap();
+        model.tree().set_child(root, first);
+
+        for format in [Format::BINARY, Format::XML] {
+            model.set_format(format);
+            let new_model = from_bytes(&to_bytes(&model).unwrap()).unwrap();
+            let root = new_model.tree().roots().next().unwrap().unwrap();
+            let names = root
+                .children()
+                .map(|child| child.unwrap().name().to_string())
+                .collect::<Vec<_>>();
+            assert_eq!(names, ["A", "B", "C"]);
+        }
+    }
+
+    #[test]
+    fn test_reader_limit() {
+        let mut model = make_model();
+        model.set_format(Format::XML);
+        let xml = to_bytes(&model).unwrap();
+
+        let limited = |limit| DeserializerOptions {
+            max_decompressed_bytes: Some(limit),
+            ..DeserializerOptions::default()
+        };
+
+        assert!(from_reader_with_options(&*xml, &limited(xml.len())).is_ok());
+        let err = from_reader_with_options(&*xml, &limited(xml.len() - 1)).unwrap_err();
+        assert!(matches!(
+            err.kind,
+            crate::serde::ErrorKind::DecompressedSizeExceeded(_)
+        ));
+
+        let err = xml::de::Deserializer::with_options(&*xml, limited(16))
+            .deserialize()
+            .unwrap_err();
+        assert!(matches!(
+            err.kind,
+            crate::serde::ErrorKind::DecompressedSizeExceeded(16)
+        ));
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_round_trip_gzip() {
+        let format = Format {
+            kind: FormatKind::Xml,
+            gzip: true,
+        };
+        let data = to_bytes_format(&make_model(), format).unwrap();
+        assert!(data.starts_with(GZIP_MAGIC));
+
+        let new_model = from_bytes(&data).unwrap();
+        assert_eq!(new_model.format(), format);
+
+        let nested = gzip_compress(&data).unwrap();
+        let err = from_bytes(&nested).unwrap_err();
+        assert!(matches!(err.kind, crate::serde::ErrorKind::InvalidGzip));
+
+        let options = DeserializerOptions {
+            max_decompressed_bytes: Some(usize::MAX),
+            ..DeserializerOptions::default()
+        };
+        assert!(from_bytes_with_options(&data, &options).is_ok());
+    }
+
+    #[cfg(not(feature = "gzip"))]
+    #[test]
+    fn test_gzip_disabled() {
+        let err = from_bytes(&[0x1F, 0x8B, 0x08, 0x00]).unwrap_err();
+        assert!(matches!(
+            err.kind,
+            crate::serde::ErrorKind::MissingFeature("gzip")
+        ));
+    }
+}
@@ -2,14 +2,22 @@ use crate::model::property::PropertyType;
 use crate::model::*;
 use crate::serde::encoding::{Chomp, Print};
 use crate::serde::{Error, ErrorKind, Result};
+use crate::tree::TreeKey;
 
 use alloc::collections::BTreeMap;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 
+use md5::{Digest, Md5};
 use uuid::Uuid;
 
-macro_rules! prop_ty_impl {
+/// The MD5 hash identifying a shared string by its contents, stored alongside it in both the
+/// binary and XML formats
+pub(crate) fn shared_string_hash(data: &[u8]) -> [u8; 16] {
+    Md5::digest(data).into()
+}
+
+macro_rules! prop_ty_from_impl {
     ($($ty:ty : $variant:ident),+ $(,)?) => {
         $(
         impl FieldFromProperties for $ty {
@@ -23,8 +31,23 @@ macro_rules! prop_ty_impl {
                     None => Err($crate::SerdeError::missing_property(attrs.prop_name.to_string())),
                 }
             }
+
+            fn check_properties(attrs: FieldAttrs, properties: &BTreeMap<String, Property>) -> Result<()> {
+                check_property(&attrs, properties, PropertyType::$variant, |prop| {
+                    matches!(prop, Property::$variant(_))
+                })
+                .map(|_| ())
+            }
         }
+        )*
+    }
+}
+
+macro_rules! prop_ty_impl {
+    ($($ty:ty : $variant:ident),+ $(,)?) => {
+        prop_ty_from_impl! { $($ty : $variant),+ }
 
+        $(
         impl FieldToProperties for $ty {
             fn to_properties(self, attrs: FieldAttrs, properties: &mut BTreeMap<String, Property>) {
                 properties.insert(attrs.prop_name.to_string(), Property::$variant(self));
@@ -34,38 +57,85 @@ macro_rules! prop_ty_impl {
     }
 }
 
+/// A property value as stored in the binary format, before shared strings and instance
+/// references are resolved. Each variant corresponds to one binary type ID.
 #[derive(Debug, Clone)]
 pub enum RawProperty {
-    RawString(Vec<u8>), // This may or may not be a 'real' string, it can also be just a data blob
+    /// A string, which may or may not be valid UTF-8. It can also be just a data blob
+    RawString(Vec<u8>),
+    /// A boolean value
     Bool(bool),
+    /// A 32-bit integer
     Int32(i32),
+    /// A 64-bit integer
     Int64(i64),
+    /// A single-precision float
     Float(f32),
+    /// A double-precision float
     Double(f64),
+    /// A [`UDim`]
     UDim(UDim),
+    /// A [`UDim2`]
     UDim2(UDim2),
+    /// A [`Ray`]
     Ray(Ray),
+    /// A set of [`Faces`]
     Face(Faces),
+    /// A set of [`Axes`]
     Axis(Axes),
-    BrickColor(BrickColor), // Deprecated
+    /// A [`BrickColor`]. Deprecated in favor of [`Color3`]
+    BrickColor(BrickColor),
+    /// A [`Color3`]
     Color3(Color3),
+    /// A [`Vector2`]
     Vector2(Vector2),
+    /// A [`Vector3`]
     Vector3(Vector3),
+    /// A [`CFrame`]
     CFrame(CFrame),
+    /// A [`CFrameQuat`]
+    CFrameQuat(CFrameQuat),
+    /// The value of an enum
     Enum(i32),
+    /// The referent of another instance, or `-1` for none
     InstanceRef(i32),
+    /// A [`Vector3Int16`]
     Vector3Int16(Vector3Int16),
+    /// A [`NumberSequence`]
     NumberSequence(NumberSequence),
+    /// A [`ColorSequence`]
     ColorSequence(ColorSequence),
+    /// A [`NumberRange`]
     NumberRange(NumberRange),
+    /// A [`Rect`]
     Rect(Rect),
+    /// A set of [`PhysicalProperties`]
     PhysicalProperties(PhysicalProperties),
+    /// A [`Color3Uint8`]
     Color3Uint8(Color3Uint8),
+    /// The index of a string in the `SSTR` chunk
     RawSharedString(i32),
-    // TODO: This is called 'OptionalCoordinateFrame' in XML
-    Pivot(Pivot),
+    /// A [`CFrame`] which may be unset
+    OptionalCFrame(Option<CFrame>),
+    /// A [`Uuid`]
     Uuid(Uuid),
+    /// A [`FontFace`]
     Font(FontFace),
+    /// A set of [`SecurityCapabilities`]
+    SecurityCapabilities(SecurityCapabilities),
+    /// A [`Content`] value, with any object stored as its referent
+    Content(RawContent),
+}
+
+/// A [`Content`] value as stored in the binary format, before object references are resolved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RawContent {
+    /// No content
+    None,
+    /// A URI for an asset
+    Uri(String),
+    /// The referent of an object in the same model
+    Object(i32),
 }
 
 impl RawProperty {
@@ -86,7 +156,7 @@ impl RawProperty {
             RawProperty::Vector2(..) => 13,
             RawProperty::Vector3(..) => 14,
             RawProperty::CFrame(..) => 16,
-            // RawProperty::Quaternion => 17,
+            RawProperty::CFrameQuat(..) => 17,
             RawProperty::Enum(..) => 18,
             RawProperty::InstanceRef(..) => 19,
             RawProperty::Vector3Int16(..) => 20,
@@ -98,9 +168,11 @@ impl RawProperty {
             RawProperty::Color3Uint8(..) => 26,
             RawProperty::Int64(..) => 27,
             RawProperty::RawSharedString(..) => 28,
-            RawProperty::Pivot(..) => 30,
+            RawProperty::OptionalCFrame(..) => 30,
             RawProperty::Uuid(..) => 31,
             RawProperty::Font(..) => 32,
+            RawProperty::SecurityCapabilities(..) => 33,
+            RawProperty::Content(..) => 34,
         }
     }
 
@@ -121,7 +193,7 @@ impl RawProperty {
             RawProperty::Vector2(val) => Property::Vector2(val),
             RawProperty::Vector3(val) => Property::Vector3(val),
             RawProperty::CFrame(val) => Property::CFrame(val),
-            // RawProperty::Quaternion => unimplemented!("Quaternions not supported"),
+            RawProperty::CFrameQuat(val) => Property::CFrameQuat(val),
             RawProperty::Enum(val) => Property::Enum(val),
             RawProperty::InstanceRef(..) => unreachable!(),
             RawProperty::Vector3Int16(val) => Property::Vector3Int16(val),
@@ -133,14 +205,21 @@ impl RawProperty {
             RawProperty::Color3Uint8(val) => Property::Color3Uint8(val),
             RawProperty::Int64(val) => Property::Int64(val),
             RawProperty::RawSharedString(..) => unreachable!(),
-            RawProperty::Pivot(val) => Property::Pivot(val),
+            RawProperty::OptionalCFrame(val) => Property::OptionalCFrame(val),
             RawProperty::Uuid(val) => Property::Uuid(val),
             RawProperty::Font(val) => Property::Font(val),
+            RawProperty::SecurityCapabilities(val) => Property::SecurityCapabilities(val),
+            RawProperty::Content(RawContent::None) => Property::Content(Content::None),
+            RawProperty::Content(RawContent::Uri(uri)) => Property::Content(Content::Uri(uri)),
+            RawProperty::Content(RawContent::Object(..)) => unreachable!(),
         }
     }
 
-    pub(crate) fn from_real(prop: Property) -> RawProperty {
-        match prop {
+    /// Convert a property to how it's stored, other than strings and references which need the
+    /// rest of the model. An [`EnumItem`] is stored as its value alone, the same as
+    /// [`Property::Enum`]. A [`Property::Nil`] can't be stored outside of attributes at all.
+    pub(crate) fn from_real(prop: Property) -> Result<RawProperty> {
+        Ok(match prop {
             Property::BinaryString(..) => unreachable!(),
             Property::TextString(..) => unreachable!(),
             Property::SharedBinaryString(..) => unreachable!(),
@@ -159,7 +238,7 @@ impl RawProperty {
             Property::Vector2(val) => RawProperty::Vector2(val),
             Property::Vector3(val) => RawProperty::Vector3(val),
             Property::CFrame(val) => RawProperty::CFrame(val),
-            // Property::Quaternion => unimplemented!("Quaternions not supported"),
+            Property::CFrameQuat(val) => RawProperty::CFrameQuat(val),
             Property::Enum(val) => RawProperty::Enum(val),
             Property::InstanceRef(..) => unreachable!(),
             Property::Vector3Int16(val) => RawProperty::Vector3Int16(val),
@@ -170,25 +249,92 @@ impl RawProperty {
             Property::PhysicalProperties(val) => RawProperty::PhysicalProperties(val),
             Property::Color3Uint8(val) => RawProperty::Color3Uint8(val),
             Property::Int64(val) => RawProperty::Int64(val),
-            Property::Pivot(val) => RawProperty::Pivot(val),
+            Property::OptionalCFrame(val) => RawProperty::OptionalCFrame(val),
             Property::Uuid(val) => RawProperty::Uuid(val),
             Property::Font(val) => RawProperty::Font(val),
-        }
+            Property::SecurityCapabilities(val) => RawProperty::SecurityCapabilities(val),
+            Property::EnumItem(val) => RawProperty::Enum(val.value as i32),
+            Property::Nil => {
+                return Err(Error::invalid_data(
+                    "unset values can only be stored in attributes",
+                ))
+            }
+            Property::Content(Content::None) => RawProperty::Content(RawContent::None),
+            Property::Content(Content::Uri(uri)) => RawProperty::Content(RawContent::Uri(uri)),
+            Property::Content(Content::Object(..)) => unreachable!(),
+        })
     }
 }
 
 pub trait FromProperties: Sized {
     fn from_properties(properties: &mut BTreeMap<String, Property>) -> Result<Self>;
+
+    /// Check, without consuming anything, that `from_properties` wouldn't fail with a missing
+    /// property, a property of the wrong type, an unknown enum variant or an invalid string
+    fn check_properties(properties: &BTreeMap<String, Property>) -> Result<()>;
 }
 
 pub trait ToProperties: Sized {
     fn to_properties(&self, properties: &mut BTreeMap<String, Property>);
+
+    /// Whether the named property is marked `#[protected]`, on this type or one it inherits from
+    fn is_protected(&self, name: &str) -> bool;
+
+    /// Change the target of every reference held by this type or one it inherits from, in place.
+    /// Returns whether any reference was changed.
+    fn remap_refs(&mut self, remap: &mut dyn FnMut(TreeKey) -> Option<TreeKey>) -> bool;
+}
+
+/// Change the target of a reference held by a loosely typed property, in place. Returns whether
+/// the reference was changed.
+pub(crate) fn remap_property_refs(
+    prop: &mut Property,
+    remap: &mut dyn FnMut(TreeKey) -> Option<TreeKey>,
+) -> bool {
+    match prop {
+        Property::InstanceRef(val) => val.remap_refs(remap),
+        Property::Content(val) => val.remap_refs(remap),
+        _ => false,
+    }
 }
 
 pub struct FieldAttrs {
-    pub field_name: &'static str,
     pub prop_name: &'static str,
     pub shared: bool,
+    /// Whether an `Option` field is stored as a property which may be unset, rather than one
+    /// which may be missing. Only `Option<CFrame>` has such a property type, and the derive
+    /// rejects the attribute on any other field.
+    pub optional: bool,
+}
+
+/// Turn a missing property into `None`, passing through any other result
+fn missing_as_none<T>(result: Result<T>) -> Result<Option<T>> {
+    match result {
+        Ok(val) => Ok(Some(val)),
+        Err(Error {
+            kind: ErrorKind::MissingProperty(_),
+            ..
+        }) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Look up a property without removing it, failing like `from_properties` would if it's missing
+/// or isn't a type the field accepts
+pub(crate) fn check_property<'a>(
+    attrs: &FieldAttrs,
+    properties: &'a BTreeMap<String, Property>,
+    expected: PropertyType,
+    accepts: impl FnOnce(&Property) -> bool,
+) -> Result<&'a Property> {
+    match properties.get(attrs.prop_name) {
+        Some(prop) if accepts(prop) => Ok(prop),
+        Some(prop) => Err(Error::wrong_property_type(
+            attrs.prop_name.to_string(),
+            Some((expected, prop.kind())),
+        )),
+        None => Err(Error::missing_property(attrs.prop_name.to_string())),
+    }
 }
 
 pub trait FieldFromProperties: Sized {
@@ -196,12 +342,33 @@ pub trait FieldFromProperties: Sized {
         attrs: FieldAttrs,
         properties: &mut BTreeMap<String, Property>,
     ) -> Result<Self>;
+
+    fn from_optional_properties(
+        attrs: FieldAttrs,
+        properties: &mut BTreeMap<String, Property>,
+    ) -> Result<Option<Self>> {
+        missing_as_none(Self::from_properties(attrs, properties))
+    }
+
+    /// The non-consuming counterpart of `from_properties`, see [`FromProperties::check_properties`]
+    fn check_properties(attrs: FieldAttrs, properties: &BTreeMap<String, Property>) -> Result<()>;
+
+    fn check_optional_properties(
+        attrs: FieldAttrs,
+        properties: &BTreeMap<String, Property>,
+    ) -> Result<()> {
+        missing_as_none(Self::check_properties(attrs, properties)).map(|_| ())
+    }
 }
 
 impl<T: FromProperties> FieldFromProperties for T {
     fn from_properties(_: FieldAttrs, properties: &mut BTreeMap<String, Property>) -> Result<Self> {
         T::from_properties(properties)
     }
+
+    fn check_properties(_: FieldAttrs, properties: &BTreeMap<String, Property>) -> Result<()> {
+        T::check_properties(properties)
+    }
 }
 
 impl FieldFromProperties for String {
@@ -229,6 +396,19 @@ impl FieldFromProperties for String {
             }
         }
     }
+
+    fn check_properties(attrs: FieldAttrs, properties: &BTreeMap<String, Property>) -> Result<()> {
+        let result = if attrs.shared {
+            check_property(&attrs, properties, PropertyType::SharedTextString, |prop| {
+                matches!(prop, Property::SharedTextString(_))
+            })
+        } else {
+            check_property(&attrs, properties, PropertyType::TextString, |prop| {
+                matches!(prop, Property::TextString(_))
+            })
+        };
+        result.map(|_| ())
+    }
 }
 
 impl FieldFromProperties for Vec<u8> {
@@ -258,6 +438,116 @@ impl FieldFromProperties for Vec<u8> {
             }
         }
     }
+
+    fn check_properties(attrs: FieldAttrs, properties: &BTreeMap<String, Property>) -> Result<()> {
+        check_bytes(&attrs, properties).map(|_| ())
+    }
+}
+
+/// Borrow the bytes `<Vec<u8>>::from_properties` would take
+fn check_bytes<'a>(
+    attrs: &FieldAttrs,
+    properties: &'a BTreeMap<String, Property>,
+) -> Result<&'a [u8]> {
+    let prop = if attrs.shared {
+        check_property(
+            attrs,
+            properties,
+            PropertyType::SharedBinaryString,
+            |prop| {
+                matches!(
+                    prop,
+                    Property::SharedBinaryString(_) | Property::SharedTextString(_)
+                )
+            },
+        )?
+    } else {
+        check_property(attrs, properties, PropertyType::BinaryString, |prop| {
+            matches!(prop, Property::BinaryString(_) | Property::TextString(_))
+        })?
+    };
+    Ok(match prop {
+        Property::BinaryString(val) | Property::SharedBinaryString(val) => val,
+        Property::TextString(str) | Property::SharedTextString(str) => str.as_bytes(),
+        _ => unreachable!(),
+    })
+}
+
+impl FieldFromProperties for CFrame {
+    fn from_properties(
+        attrs: FieldAttrs,
+        properties: &mut BTreeMap<String, Property>,
+    ) -> Result<Self> {
+        match properties.remove(attrs.prop_name) {
+            Some(Property::CFrame(val)) => Ok(val),
+            Some(Property::CFrameQuat(val)) => Ok(val.into()),
+            Some(prop) => Err(Error::wrong_property_type(
+                attrs.prop_name.to_string(),
+                Some((PropertyType::CFrame, prop.kind())),
+            )),
+            None => Err(Error::missing_property(attrs.prop_name.to_string())),
+        }
+    }
+
+    fn from_optional_properties(
+        attrs: FieldAttrs,
+        properties: &mut BTreeMap<String, Property>,
+    ) -> Result<Option<Self>> {
+        if !attrs.optional {
+            return missing_as_none(Self::from_properties(attrs, properties));
+        }
+
+        match properties.remove(attrs.prop_name) {
+            Some(Property::OptionalCFrame(val)) => Ok(val),
+            Some(prop) => Err(Error::wrong_property_type(
+                attrs.prop_name.to_string(),
+                Some((PropertyType::OptionalCFrame, prop.kind())),
+            )),
+            None => Ok(None),
+        }
+    }
+
+    fn check_properties(attrs: FieldAttrs, properties: &BTreeMap<String, Property>) -> Result<()> {
+        check_property(&attrs, properties, PropertyType::CFrame, |prop| {
+            matches!(prop, Property::CFrame(_) | Property::CFrameQuat(_))
+        })
+        .map(|_| ())
+    }
+
+    fn check_optional_properties(
+        attrs: FieldAttrs,
+        properties: &BTreeMap<String, Property>,
+    ) -> Result<()> {
+        if !attrs.optional {
+            return missing_as_none(Self::check_properties(attrs, properties)).map(|_| ());
+        }
+
+        match properties.get(attrs.prop_name) {
+            Some(Property::OptionalCFrame(_)) | None => Ok(()),
+            Some(prop) => Err(Error::wrong_property_type(
+                attrs.prop_name.to_string(),
+                Some((PropertyType::OptionalCFrame, prop.kind())),
+            )),
+        }
+    }
+}
+
+impl FieldToProperties for CFrame {
+    fn to_properties(self, attrs: FieldAttrs, properties: &mut BTreeMap<String, Property>) {
+        properties.insert(attrs.prop_name.to_string(), Property::CFrame(self));
+    }
+
+    fn optional_to_properties(
+        val: Option<Self>,
+        attrs: FieldAttrs,
+        properties: &mut BTreeMap<String, Property>,
+    ) {
+        if attrs.optional {
+            properties.insert(attrs.prop_name.to_string(), Property::OptionalCFrame(val));
+        } else if let Some(val) = val {
+            val.to_properties(attrs, properties);
+        }
+    }
 }
 
 impl FieldFromProperties for Attributes {
@@ -271,17 +561,46 @@ impl FieldFromProperties for Attributes {
                     return Ok(Attributes::default());
                 }
                 let mut reader = &*bytes;
-                let out = Attributes::chomp(&mut reader);
-                debug_assert_eq!(
-                    *reader,
-                    [],
-                    "Attributes didn't consume whole serialized buffer"
-                );
-                out
+                let out = Attributes::chomp(&mut reader)?;
+                if !reader.is_empty() {
+                    return Err(Error::invalid_data(
+                        "attributes didn't consume whole serialized buffer",
+                    ));
+                }
+                Ok(out)
             }
             Err(e) => Err(e),
         }
     }
+
+    fn check_properties(attrs: FieldAttrs, properties: &BTreeMap<String, Property>) -> Result<()> {
+        // Attribute names and string values are UTF-8 checked while parsing, so parse them here
+        // too rather than miss an invalid string
+        let mut reader = check_bytes(&attrs, properties)?;
+        if !reader.is_empty() {
+            Attributes::chomp(&mut reader)?;
+        }
+        Ok(())
+    }
+}
+
+impl FieldFromProperties for Tags {
+    fn from_properties(
+        attrs: FieldAttrs,
+        properties: &mut BTreeMap<String, Property>,
+    ) -> Result<Self> {
+        let prop_name = attrs.prop_name;
+        let bytes = <Vec<u8>>::from_properties(attrs, properties)?;
+        Tags::from_bytes(&bytes).ok_or_else(|| Error::invalid_string().with_property(prop_name))
+    }
+
+    fn check_properties(attrs: FieldAttrs, properties: &BTreeMap<String, Property>) -> Result<()> {
+        // The separators are ASCII, so the tags are all valid exactly when the whole buffer is
+        match core::str::from_utf8(check_bytes(&attrs, properties)?) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(Error::invalid_string().with_property(attrs.prop_name)),
+        }
+    }
 }
 
 #[cfg(feature = "mesh-format")]
@@ -297,13 +616,22 @@ impl FieldFromProperties for TriMesh {
                     return Ok(TriMesh::Box);
                 }
                 let mut reader = &*bytes;
-                let out = TriMesh::chomp(&mut reader);
-                debug_assert_eq!(*reader, [], "TriMesh didn't consume whole physics buffer");
-                out
+                let out = TriMesh::chomp(&mut reader)?;
+                if !reader.is_empty() {
+                    return Err(Error::invalid_data(
+                        "TriMesh didn't consume whole physics buffer",
+                    ));
+                }
+                Ok(out)
             }
             Err(e) => Err(e),
         }
     }
+
+    fn check_properties(attrs: FieldAttrs, properties: &BTreeMap<String, Property>) -> Result<()> {
+        // Physics data holds no strings, so a malformed buffer is never a structure error
+        check_bytes(&attrs, properties).map(|_| ())
+    }
 }
 
 impl<T: FieldFromProperties> FieldFromProperties for Option<T> {
@@ -311,25 +639,98 @@ impl<T: FieldFromProperties> FieldFromProperties for Option<T> {
         attrs: FieldAttrs,
         properties: &mut BTreeMap<String, Property>,
     ) -> Result<Self> {
-        match T::from_properties(attrs, properties) {
-            Ok(val) => Ok(Some(val)),
-            Err(Error {
-                kind: ErrorKind::MissingProperty(_),
-                ..
-            }) => Ok(None),
-            Err(e) => Err(e),
-        }
+        T::from_optional_properties(attrs, properties)
+    }
+
+    fn check_properties(attrs: FieldAttrs, properties: &BTreeMap<String, Property>) -> Result<()> {
+        T::check_optional_properties(attrs, properties)
     }
 }
 
 pub trait FieldToProperties: Sized {
     fn to_properties(self, attrs: FieldAttrs, properties: &mut BTreeMap<String, Property>);
+
+    fn optional_to_properties(
+        val: Option<Self>,
+        attrs: FieldAttrs,
+        properties: &mut BTreeMap<String, Property>,
+    ) {
+        if let Some(val) = val {
+            val.to_properties(attrs, properties);
+        }
+    }
+
+    /// Whether the named property is marked `#[protected]` within this field, for fields which
+    /// hold a whole inherited type
+    fn is_protected(&self, _name: &str) -> bool {
+        false
+    }
+
+    /// Change the target of any reference held by this field, in place. Returns whether any
+    /// reference was changed.
+    fn remap_refs(&mut self, _remap: &mut dyn FnMut(TreeKey) -> Option<TreeKey>) -> bool {
+        false
+    }
 }
 
 impl<T: ToProperties> FieldToProperties for T {
     fn to_properties(self, _: FieldAttrs, properties: &mut BTreeMap<String, Property>) {
         T::to_properties(&self, properties)
     }
+
+    fn is_protected(&self, name: &str) -> bool {
+        ToProperties::is_protected(self, name)
+    }
+
+    fn remap_refs(&mut self, remap: &mut dyn FnMut(TreeKey) -> Option<TreeKey>) -> bool {
+        ToProperties::remap_refs(self, remap)
+    }
+}
+
+impl FieldToProperties for InstanceRef {
+    fn to_properties(self, attrs: FieldAttrs, properties: &mut BTreeMap<String, Property>) {
+        properties.insert(attrs.prop_name.to_string(), Property::InstanceRef(self));
+    }
+
+    fn remap_refs(&mut self, remap: &mut dyn FnMut(TreeKey) -> Option<TreeKey>) -> bool {
+        let InstanceRef::Item(key) = self else {
+            return false;
+        };
+        match remap(*key) {
+            Some(new_key) if new_key == *key => false,
+            Some(new_key) => {
+                *key = new_key;
+                true
+            }
+            None => {
+                *self = InstanceRef::Null;
+                true
+            }
+        }
+    }
+}
+
+impl FieldToProperties for Content {
+    fn to_properties(self, attrs: FieldAttrs, properties: &mut BTreeMap<String, Property>) {
+        properties.insert(attrs.prop_name.to_string(), Property::Content(self));
+    }
+
+    fn remap_refs(&mut self, remap: &mut dyn FnMut(TreeKey) -> Option<TreeKey>) -> bool {
+        let Content::Object(key) = self else {
+            return false;
+        };
+        match remap(*key) {
+            Some(new_key) if new_key == *key => false,
+            Some(new_key) => {
+                *key = new_key;
+                true
+            }
+            None => {
+                *self = Content::None;
+                true
+            }
+        }
+    }
 }
 
 impl FieldToProperties for String {
@@ -340,7 +741,7 @@ impl FieldToProperties for String {
             Property::TextString(self)
         };
 
-        properties.insert(attrs.field_name.to_string(), prop);
+        properties.insert(attrs.prop_name.to_string(), prop);
     }
 }
 
@@ -352,7 +753,7 @@ impl FieldToProperties for Vec<u8> {
             Property::BinaryString(self)
         };
 
-        properties.insert(attrs.field_name.to_string(), prop);
+        properties.insert(attrs.prop_name.to_string(), prop);
     }
 }
 
@@ -364,6 +765,12 @@ impl FieldToProperties for Attributes {
     }
 }
 
+impl FieldToProperties for Tags {
+    fn to_properties(self, attrs: FieldAttrs, properties: &mut BTreeMap<String, Property>) {
+        self.to_bytes().to_properties(attrs, properties);
+    }
+}
+
 #[cfg(feature = "mesh-format")]
 impl FieldToProperties for TriMesh {
     fn to_properties(self, attrs: FieldAttrs, properties: &mut BTreeMap<String, Property>) {
@@ -375,9 +782,11 @@ impl FieldToProperties for TriMesh {
 
 impl<T: FieldToProperties> FieldToProperties for Option<T> {
     fn to_properties(self, attrs: FieldAttrs, properties: &mut BTreeMap<String, Property>) {
-        if let Some(val) = self {
-            T::to_properties(val, attrs, properties);
-        }
+        T::optional_to_properties(self, attrs, properties);
+    }
+
+    fn remap_refs(&mut self, remap: &mut dyn FnMut(TreeKey) -> Option<TreeKey>) -> bool {
+        self.as_mut().is_some_and(|val| val.remap_refs(remap))
     }
 }
 
@@ -396,8 +805,7 @@ prop_ty_impl! {
     Color3 : Color3,
     Vector2 : Vector2,
     Vector3 : Vector3,
-    CFrame : CFrame,
-    InstanceRef : InstanceRef,
+    CFrameQuat : CFrameQuat,
     Vector3Int16 : Vector3Int16,
     NumberSequence : NumberSequence,
     ColorSequence : ColorSequence,
@@ -405,7 +813,13 @@ prop_ty_impl! {
     Rect : Rect,
     PhysicalProperties : PhysicalProperties,
     Color3Uint8 : Color3Uint8,
-    Pivot : Pivot,
     Uuid : Uuid,
     FontFace : Font,
+    SecurityCapabilities : SecurityCapabilities,
+}
+
+// These also hold references, so their `FieldToProperties` impls are written out above
+prop_ty_from_impl! {
+    InstanceRef : InstanceRef,
+    Content : Content,
 }
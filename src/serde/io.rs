@@ -6,6 +6,32 @@ use crate::serde::Result;
 pub trait Read {
     /// Read an exact buffer size
     fn read_exact(&mut self, buf: &mut [u8]) -> Result<()>;
+
+    /// Read all remaining bytes into a buffer, returning how many were read.
+    ///
+    /// The default implementation reads a byte at a time with [`read_exact`](Read::read_exact),
+    /// and treats the first failed read as the end of the input.
+    fn read_to_end(&mut self, buf: &mut alloc::vec::Vec<u8>) -> Result<usize> {
+        let start = buf.len();
+        let mut byte = [0];
+        while self.read_exact(&mut byte).is_ok() {
+            buf.push(byte[0]);
+        }
+        Ok(buf.len() - start)
+    }
+
+    /// Read remaining bytes into a buffer until the end of the input, or until `limit` bytes
+    /// have been read, returning how many were read.
+    ///
+    /// The default implementation reads a byte at a time like [`read_to_end`](Read::read_to_end).
+    fn read_to_limit(&mut self, buf: &mut alloc::vec::Vec<u8>, limit: usize) -> Result<usize> {
+        let start = buf.len();
+        let mut byte = [0];
+        while buf.len() - start < limit && self.read_exact(&mut byte).is_ok() {
+            buf.push(byte[0]);
+        }
+        Ok(buf.len() - start)
+    }
 }
 
 #[cfg(feature = "std")]
@@ -13,6 +39,15 @@ impl<T: std::io::Read> Read for T {
     fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
         <Self as std::io::Read>::read_exact(self, buf).map_err(Into::into)
     }
+
+    fn read_to_end(&mut self, buf: &mut alloc::vec::Vec<u8>) -> Result<usize> {
+        <Self as std::io::Read>::read_to_end(self, buf).map_err(Into::into)
+    }
+
+    fn read_to_limit(&mut self, buf: &mut alloc::vec::Vec<u8>, limit: usize) -> Result<usize> {
+        let limit = u64::try_from(limit).unwrap_or(u64::MAX);
+        std::io::Read::read_to_end(&mut std::io::Read::take(self, limit), buf).map_err(Into::into)
+    }
 }
 
 #[cfg(not(feature = "std"))]
@@ -31,6 +66,20 @@ impl Read for &[u8] {
             )))
         }
     }
+
+    fn read_to_end(&mut self, buf: &mut alloc::vec::Vec<u8>) -> Result<usize> {
+        let len = self.len();
+        buf.extend_from_slice(self);
+        *self = &self[len..];
+        Ok(len)
+    }
+
+    fn read_to_limit(&mut self, buf: &mut alloc::vec::Vec<u8>, limit: usize) -> Result<usize> {
+        let len = self.len().min(limit);
+        buf.extend_from_slice(&self[..len]);
+        *self = &self[len..];
+        Ok(len)
+    }
 }
 
 /// A `no_std` minimal implementation of [`std::io::Write`]
@@ -1,135 +1,298 @@
 //! The serialization implementation for an RBXM
 
 use crate::model::*;
-use crate::serde::encoding::{
-    encode_cumulative, Print, PrintInterleaved, PrintInterleavedTransform, PrintTransform,
+use crate::serde::chunk::{
+    ChunkWriter, FileHeader, InstChunk, MetaChunk, PrntChunk, PropChunk, SharedStringEntry,
+    SstrChunk,
 };
-use crate::serde::internal::RawProperty;
+use crate::serde::internal::{RawContent, RawProperty};
 use crate::serde::io::Write;
-use crate::serde::Result;
+use crate::serde::{Error, Result};
 
-use alloc::collections::BTreeMap;
+use alloc::collections::{BTreeMap, BTreeSet};
 use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
-use uuid::Uuid;
-
-#[derive(Debug)]
-pub(crate) enum Block {
-    Meta(BTreeMap<String, String>),
-    SharedStr(Vec<Vec<u8>>),
-    Instance {
-        index: i32,
-        class_name: String,
-        is_service: bool,
-        instance_ids: Vec<i32>,
-    },
-    Property {
-        class_index: i32,
-        property_name: String,
-        properties: Vec<RawProperty>,
-    },
-    Parent {
-        instance_referents: Vec<i32>,
-        parent_referents: Vec<i32>,
-    },
-    End,
+
+/// The compression applied to the data of each block in a binary model
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Compression {
+    /// Store blocks uncompressed, useful for inspecting the output
+    None,
+    /// Compress blocks with LZ4, which all versions of Roblox can read
+    #[default]
+    Lz4,
+    /// Compress blocks with zstd, as newer versions of Roblox do
+    #[cfg_attr(docsrs, doc(cfg(feature = "zstd")))]
+    #[cfg(feature = "zstd")]
+    Zstd,
+    /// Pick whichever of the other options produces the smallest output, separately for each
+    /// block
+    Smallest,
+}
+
+impl Compression {
+    /// Compress a block's data, returning `None` if it should be stored uncompressed
+    pub(crate) fn compress(self, data: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            Compression::None => None,
+            Compression::Lz4 => Some(lz4_flex::block::compress(data)),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Some(ruzstd::encoding::compress_to_vec(
+                data,
+                ruzstd::encoding::CompressionLevel::Fastest,
+            )),
+            Compression::Smallest => [
+                Compression::Lz4,
+                #[cfg(feature = "zstd")]
+                Compression::Zstd,
+            ]
+            .into_iter()
+            .filter_map(|compression| compression.compress(data))
+            .filter(|compressed| compressed.len() < data.len())
+            .min_by_key(Vec::len),
+        }
+    }
+}
+
+/// Options controlling how a model is serialized
+#[derive(Debug, Clone, Default)]
+pub struct SerializerOptions {
+    /// The compression to use for block data
+    pub compression: Compression,
+    /// The compression to use for particular kinds of block instead of `compression`, keyed by
+    /// block name such as `"PROP"` or `"SSTR"`
+    pub chunk_compression: BTreeMap<String, Compression>,
+    /// Number classes in order of their names, and shared strings in order of first use by the
+    /// property blocks, rather than in the order a walk of the instance tree first reaches them.
+    ///
+    /// Output is always the same for the same logical model: referents are assigned in tree
+    /// order, and properties are written sorted by name, whether or not this is set. This only
+    /// additionally makes the class and shared string tables independent of where in the tree
+    /// each class or string first appears.
+    pub deterministic: bool,
+}
+
+impl SerializerOptions {
+    /// The compression to use for a block with the given name
+    pub(crate) fn compression_for(&self, name: &str) -> Compression {
+        self.chunk_compression
+            .get(name)
+            .copied()
+            .unwrap_or(self.compression)
+    }
 }
 
 /// Necessary state for serializing a value
 pub struct Serializer<W> {
     writer: W,
+    options: SerializerOptions,
+}
+
+/// Renumber shared strings in order of their first use by the property blocks, so their order
+/// doesn't depend on the order instances were visited in
+fn renumber_shared_strs<'a>(
+    shared_strs: Vec<Vec<u8>>,
+    prop_chunks: impl Iterator<Item = &'a mut PropChunk>,
+) -> Vec<Vec<u8>> {
+    let mut old_to_new = BTreeMap::new();
+    let mut out = Vec::with_capacity(shared_strs.len());
+    for prop in prop_chunks.flat_map(|chunk| &mut chunk.values) {
+        if let RawProperty::RawSharedString(id) = prop {
+            *id = *old_to_new.entry(*id).or_insert_with(|| {
+                out.push(shared_strs[*id as usize].clone());
+                out.len() as i32 - 1
+            });
+        }
+    }
+    out
+}
+
+/// Find a string in the shared strings, adding it if it isn't there yet
+fn intern_shared_str(shared_strs: &mut Vec<Vec<u8>>, blob: Vec<u8>) -> i32 {
+    let pos = shared_strs
+        .iter()
+        .position(|data| data == &blob)
+        .unwrap_or_else(|| {
+            shared_strs.push(blob);
+            shared_strs.len() - 1
+        });
+    pos as i32
+}
+
+/// The value written for an instance that has no value for a property other instances of its
+/// class do. Every instance needs a value in each `PROP` chunk of its class, so this is the
+/// default of the chunk's type, or the absent encoding where the type has one.
+fn padding_for(template: &RawProperty, shared_strs: &mut Vec<Vec<u8>>) -> RawProperty {
+    match template {
+        RawProperty::RawString(..) => RawProperty::RawString(Vec::new()),
+        RawProperty::Bool(..) => RawProperty::Bool(Default::default()),
+        RawProperty::Int32(..) => RawProperty::Int32(Default::default()),
+        RawProperty::Int64(..) => RawProperty::Int64(Default::default()),
+        RawProperty::Float(..) => RawProperty::Float(Default::default()),
+        RawProperty::Double(..) => RawProperty::Double(Default::default()),
+        RawProperty::UDim(..) => RawProperty::UDim(Default::default()),
+        RawProperty::UDim2(..) => RawProperty::UDim2(Default::default()),
+        RawProperty::Ray(..) => RawProperty::Ray(Default::default()),
+        RawProperty::Face(..) => RawProperty::Face(Default::default()),
+        RawProperty::Axis(..) => RawProperty::Axis(Default::default()),
+        RawProperty::BrickColor(..) => RawProperty::BrickColor(Default::default()),
+        RawProperty::Color3(..) => RawProperty::Color3(Default::default()),
+        RawProperty::Vector2(..) => RawProperty::Vector2(Default::default()),
+        RawProperty::Vector3(..) => RawProperty::Vector3(Default::default()),
+        RawProperty::CFrame(..) => RawProperty::CFrame(Default::default()),
+        RawProperty::CFrameQuat(..) => RawProperty::CFrameQuat(Default::default()),
+        RawProperty::Enum(..) => RawProperty::Enum(Default::default()),
+        RawProperty::Vector3Int16(..) => RawProperty::Vector3Int16(Default::default()),
+        RawProperty::NumberSequence(..) => RawProperty::NumberSequence(Default::default()),
+        RawProperty::ColorSequence(..) => RawProperty::ColorSequence(Default::default()),
+        RawProperty::NumberRange(..) => RawProperty::NumberRange(Default::default()),
+        RawProperty::Rect(..) => RawProperty::Rect(Default::default()),
+        RawProperty::PhysicalProperties(..) => RawProperty::PhysicalProperties(Default::default()),
+        RawProperty::Color3Uint8(..) => RawProperty::Color3Uint8(Default::default()),
+        RawProperty::Uuid(..) => RawProperty::Uuid(Default::default()),
+        RawProperty::SecurityCapabilities(..) => {
+            RawProperty::SecurityCapabilities(Default::default())
+        }
+        RawProperty::InstanceRef(..) => RawProperty::InstanceRef(-1),
+        RawProperty::RawSharedString(..) => {
+            RawProperty::RawSharedString(intern_shared_str(shared_strs, Vec::new()))
+        }
+        RawProperty::OptionalCFrame(..) => RawProperty::OptionalCFrame(None),
+        RawProperty::Font(..) => RawProperty::Font(FontFace {
+            family: String::new(),
+            weight: FontWeight::Regular,
+            style: FontStyle::Normal,
+            cached_face_id: String::new(),
+        }),
+        RawProperty::Content(..) => RawProperty::Content(RawContent::None),
+    }
+}
+
+/// A model broken down into the chunks it's written as
+struct ModelChunks {
+    header: FileHeader,
+    meta: MetaChunk,
+    sstr: Option<SstrChunk>,
+    insts: Vec<InstChunk>,
+    props: Vec<PropChunk>,
+    prnt: PrntChunk,
 }
 
-fn break_model(model: &RbxModel) -> (i32, i32, Vec<Block>) {
+fn break_model(model: &RbxModel, options: &SerializerOptions) -> Result<ModelChunks> {
+    // Referents are assigned in tree order, so that reading the model back keeps sibling order
     let key_to_id: BTreeMap<_, _> = model
         .nodes
-        .unordered_keys()
+        .depth_first_keys()
         .enumerate()
         .map(|(idx, key)| (key, idx))
         .collect();
 
-    let mut inst_blocks = BTreeMap::new();
-    let mut prop_blocks = BTreeMap::new();
+    // Classes are otherwise numbered in the order they're first seen
+    let class_indices: BTreeMap<_, _> = if options.deterministic {
+        model
+            .nodes
+            .unordered_iter()
+            .map(|node| node.expect("Couldn't borrow node").class_name())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .enumerate()
+            .map(|(idx, class_name)| (class_name, idx))
+            .collect()
+    } else {
+        BTreeMap::new()
+    };
+
+    let mut inst_chunks = BTreeMap::new();
+    let mut prop_chunks = BTreeMap::new();
+    let mut prop_kinds = BTreeMap::new();
     let mut parents = BTreeMap::new();
     let mut shared_strs = Vec::new();
 
-    for node in model.nodes.unordered_iter() {
-        let node = node.expect("Couldn't borrow node");
+    for key in model.nodes.depth_first_keys() {
+        let node = model.nodes.try_get(key).expect("Couldn't borrow node");
         let index = key_to_id[&node.key()];
         let inst = &*node;
-        let next_index = inst_blocks.len();
         let class_name = inst.class_name();
-
-        let inst_block = inst_blocks
-            .entry(class_name.clone())
-            .or_insert(Block::Instance {
-                index: next_index as i32,
-                class_name,
-                is_service: false,
-                instance_ids: vec![],
-            });
-
-        let class_index = if let Block::Instance {
-            index: class_index,
-            instance_ids,
-            ..
-        } = inst_block
-        {
-            instance_ids.push(index as i32);
-            *class_index
-        } else {
-            unreachable!()
-        };
+        let next_index = class_indices
+            .get(&class_name)
+            .copied()
+            .unwrap_or(inst_chunks.len());
+
+        let inst_chunk = inst_chunks.entry(class_name.clone()).or_insert(InstChunk {
+            class_index: next_index as i32,
+            class_name,
+            referents: vec![],
+            service_markers: Some(vec![]),
+        });
+
+        inst_chunk.referents.push(index as i32);
+        inst_chunk
+            .service_markers
+            .get_or_insert_with(Vec::new)
+            .push(model.is_service(key));
+        let class_index = inst_chunk.class_index;
+        let class_position = inst_chunk.referents.len() - 1;
 
         for (prop_name, prop_value) in Instance::break_instance(inst) {
-            let prop_block = prop_blocks
+            // Nothing is stored for an unset value
+            if let Property::Nil = prop_value {
+                continue;
+            }
+
+            let prop_chunk = prop_chunks
                 .entry((class_index, prop_name.clone()))
-                .or_insert(Block::Property {
+                .or_insert(PropChunk {
                     class_index,
-                    property_name: prop_name.clone(),
-                    properties: vec![],
+                    name: prop_name.clone(),
+                    type_id: 0,
+                    values: vec![],
                 });
-            if let Block::Property { properties, .. } = prop_block {
-                let raw = match prop_value {
-                    Property::BinaryString(blob) => RawProperty::RawString(blob.clone()),
-                    Property::TextString(str) => RawProperty::RawString(str.clone().into_bytes()),
-                    Property::SharedBinaryString(blob) => {
-                        let pos = shared_strs
-                            .iter()
-                            .position(|data| data == &blob)
-                            .unwrap_or_else(|| {
-                                shared_strs.push(blob);
-                                shared_strs.len() - 1
-                            });
-
-                        RawProperty::RawSharedString(pos as i32)
-                    }
-                    Property::SharedTextString(str) => {
-                        let blob = str.into_bytes();
-                        let pos = shared_strs
-                            .iter()
-                            .position(|data| data == &blob)
-                            .unwrap_or_else(|| {
-                                shared_strs.push(blob);
-                                shared_strs.len() - 1
-                            });
-
-                        RawProperty::RawSharedString(pos as i32)
-                    }
-                    Property::InstanceRef(val) => {
-                        let id = match val {
-                            InstanceRef::Null => -1,
-                            InstanceRef::Item(key) => key_to_id[&key] as i32,
-                        };
-                        RawProperty::InstanceRef(id)
-                    }
-                    prop => RawProperty::from_real(prop.clone()),
-                };
-                properties.push(raw);
-            } else {
-                unreachable!()
+            let kind = prop_value.kind();
+            let raw = match prop_value {
+                Property::BinaryString(blob) => RawProperty::RawString(blob.clone()),
+                Property::TextString(str) => RawProperty::RawString(str.clone().into_bytes()),
+                Property::SharedBinaryString(blob) => {
+                    RawProperty::RawSharedString(intern_shared_str(&mut shared_strs, blob))
+                }
+                Property::SharedTextString(str) => RawProperty::RawSharedString(intern_shared_str(
+                    &mut shared_strs,
+                    str.into_bytes(),
+                )),
+                Property::InstanceRef(val) => {
+                    let id = match val {
+                        InstanceRef::Null => -1,
+                        InstanceRef::Item(key) => key_to_id[&key] as i32,
+                    };
+                    RawProperty::InstanceRef(id)
+                }
+                Property::Content(Content::Object(key)) => {
+                    RawProperty::Content(RawContent::Object(key_to_id[&key] as i32))
+                }
+                prop => RawProperty::from_real(prop).map_err(|err| {
+                    err.with_class_name(&inst.class_name())
+                        .with_property(&prop_name)
+                })?,
+            };
+            // A property block has a single type, so every instance of the class has to agree
+            let first_kind = *prop_kinds
+                .entry((class_index, prop_name.clone()))
+                .or_insert(kind);
+            if !prop_chunk.values.is_empty() && raw.encode_ty() != prop_chunk.type_id {
+                return Err(Error::wrong_property_type(
+                    prop_name.clone(),
+                    Some((first_kind, kind)),
+                )
+                .with_class_name(&inst.class_name())
+                .with_property(&prop_name));
             }
+            // Earlier instances of the class that didn't have this property still need a value
+            while prop_chunk.values.len() < class_position {
+                let padding = padding_for(&raw, &mut shared_strs);
+                prop_chunk.values.push(padding);
+            }
+            prop_chunk.type_id = raw.encode_ty();
+            prop_chunk.values.push(raw);
         }
 
         let parent_index = match node.parent() {
@@ -141,348 +304,112 @@ fn break_model(model: &RbxModel) -> (i32, i32, Vec<Block>) {
         parents.insert(index as i32, parent_index);
     }
 
-    let num_classes = inst_blocks.len();
-    let num_insts = model.nodes.len();
-
-    let child_ref = parents.keys().copied().collect::<Vec<_>>();
-    let parent_ref = parents.values().copied().collect::<Vec<_>>();
+    // As do any instances after the last one that had it
+    let class_sizes: BTreeMap<_, _> = inst_chunks
+        .values()
+        .map(|inst| (inst.class_index, inst.referents.len()))
+        .collect();
+    for prop_chunk in prop_chunks.values_mut() {
+        while prop_chunk.values.len() < class_sizes[&prop_chunk.class_index] {
+            let padding = padding_for(&prop_chunk.values[0], &mut shared_strs);
+            prop_chunk.values.push(padding);
+        }
+    }
 
-    let mut out = vec![Block::Meta(model.meta.clone())];
-    if !shared_strs.is_empty() {
-        out.push(Block::SharedStr(shared_strs));
+    if options.deterministic {
+        shared_strs = renumber_shared_strs(shared_strs, prop_chunks.values_mut());
     }
-    out.extend(inst_blocks.into_values());
-    out.extend(prop_blocks.into_values());
-    out.push(Block::Parent {
-        instance_referents: child_ref,
-        parent_referents: parent_ref,
+
+    // Classes without any service instances aren't marked as services at all
+    let insts = inst_chunks
+        .into_values()
+        .map(|mut inst| {
+            if !inst.service_markers.iter().flatten().any(|&marker| marker) {
+                inst.service_markers = None;
+            }
+            inst
+        })
+        .collect::<Vec<_>>();
+
+    let sstr = (!shared_strs.is_empty()).then(|| SstrChunk {
+        strings: shared_strs
+            .into_iter()
+            .map(SharedStringEntry::new)
+            .collect(),
     });
-    out.push(Block::End);
 
-    (num_classes as i32, num_insts as i32, out)
+    Ok(ModelChunks {
+        header: FileHeader {
+            num_classes: insts.len() as i32,
+            num_instances: model.nodes.len() as i32,
+        },
+        meta: MetaChunk {
+            entries: model.meta.clone().into_iter().collect(),
+        },
+        sstr,
+        insts,
+        props: prop_chunks.into_values().collect(),
+        prnt: PrntChunk {
+            links: parents.into_iter().collect(),
+        },
+    })
 }
 
 impl<W: Write> Serializer<W> {
     /// Create a new serializer from a writer and if necessary any other state
     pub fn new(writer: W) -> Serializer<W> {
-        Serializer { writer }
+        Serializer::with_options(writer, SerializerOptions::default())
     }
 
-    /// Serialize a model to the output stream
-    pub fn serialize(mut self, model: &RbxModel) -> Result<()> {
-        let (num_classes, num_insts, blocks) = break_model(model);
-
-        // Magic start value
-        self.writer.write_all(&[
-            0x3C, 0x72, 0x6F, 0x62, 0x6C, 0x6F, 0x78, 0x21, 0x89, 0xFF, 0x0D, 0x0A, 0x1A, 0x0A,
-            0x00, 0x00,
-        ])?;
-
-        i32::print(&mut self.writer, num_classes)?;
-        i32::print(&mut self.writer, num_insts)?;
-        i32::print(&mut self.writer, 0)?;
-        i32::print(&mut self.writer, 0)?;
-
-        for b in blocks {
-            self.write_block(b)?;
-        }
-
-        // Magic end value, maybe part of END?
-        self.writer
-            .write_all(&[0x3C, 0x2F, 0x72, 0x6F, 0x62, 0x6C, 0x6F, 0x78, 0x3E])?;
-
-        Ok(())
+    /// Create a new serializer from a writer, with a set of options controlling the output
+    pub fn with_options(writer: W, options: SerializerOptions) -> Serializer<W> {
+        Serializer { writer, options }
     }
 
-    fn write_block(&mut self, block: Block) -> Result<()> {
-        let mut out_buffer = Vec::new();
-        let writer = &mut out_buffer;
-
-        let block_name = match block {
-            Block::Meta(attrs) => {
-                i32::print(writer, attrs.len() as i32)?;
-                for (key, value) in attrs {
-                    String::print(writer, key)?;
-                    String::print(writer, value)?;
-                }
-                b"META"
-            }
-            Block::SharedStr(strs) => {
-                i32::print(writer, 0)?;
-                i32::print(writer, strs.len() as i32)?;
-
-                for blob in strs {
-                    i32::print(writer, 0)?;
-                    i32::print(writer, 0)?;
-                    i32::print(writer, 0)?;
-                    i32::print(writer, 0)?;
-
-                    <&[u8]>::print(writer, &blob)?;
-                }
-
-                b"SSTR"
-            }
-            Block::Instance {
-                index,
-                is_service,
-                class_name,
-                mut instance_ids,
-            } => {
-                i32::print(writer, index)?;
-                String::print(writer, class_name)?;
-                bool::print(writer, is_service)?;
-                i32::print(writer, instance_ids.len() as i32)?;
-
-                encode_cumulative(&mut instance_ids);
-                i32::print_interleaved_transformed(writer, &instance_ids)?;
-
-                b"INST"
-            }
-            Block::Property {
-                class_index,
-                property_name,
-                properties,
-            } => {
-                i32::print(writer, class_index)?;
-                String::print(writer, property_name)?;
-
-                let prop_ty = properties[0].encode_ty();
-
-                u8::print(writer, prop_ty)?;
-
-                for prop in &properties {
-                    match prop {
-                        RawProperty::RawString(blob) => <&[u8]>::print(writer, blob)?,
-                        RawProperty::Bool(val) => bool::print(writer, *val)?,
-                        RawProperty::Int32(val) => i32::print_transformed(writer, *val)?,
-                        RawProperty::Float(val) => f32::print_transformed(writer, *val)?,
-                        RawProperty::Double(val) => f64::print(writer, *val)?,
-                        RawProperty::UDim(..) => {
-                            UDim::print_interleaved(
-                                writer,
-                                &properties
-                                    .iter()
-                                    .cloned()
-                                    .map(|i| {
-                                        if let RawProperty::UDim(u) = i {
-                                            u
-                                        } else {
-                                            unreachable!()
-                                        }
-                                    })
-                                    .collect::<Vec<_>>(),
-                            )?;
-                            break;
-                        }
-                        RawProperty::UDim2(..) => {
-                            UDim2::print_interleaved(
-                                writer,
-                                &properties
-                                    .iter()
-                                    .cloned()
-                                    .map(|i| {
-                                        if let RawProperty::UDim2(u) = i {
-                                            u
-                                        } else {
-                                            unreachable!()
-                                        }
-                                    })
-                                    .collect::<Vec<_>>(),
-                            )?;
-                            break;
-                        }
-                        RawProperty::Ray(..) => {
-                            Ray::print_interleaved(
-                                writer,
-                                &properties
-                                    .iter()
-                                    .cloned()
-                                    .map(|i| {
-                                        if let RawProperty::Ray(r) = i {
-                                            r
-                                        } else {
-                                            unreachable!()
-                                        }
-                                    })
-                                    .collect::<Vec<_>>(),
-                            )?;
-                            break;
-                        }
-                        RawProperty::Face(val) => Faces::print(writer, val.clone())?,
-                        RawProperty::Axis(val) => Axes::print(writer, val.clone())?,
-                        RawProperty::BrickColor(..) => {
-                            BrickColor::print_interleaved(
-                                writer,
-                                &properties
-                                    .iter()
-                                    .cloned()
-                                    .map(|i| {
-                                        if let RawProperty::BrickColor(bc) = i {
-                                            bc
-                                        } else {
-                                            unreachable!()
-                                        }
-                                    })
-                                    .collect::<Vec<_>>(),
-                            )?;
-                            break;
-                        }
-                        RawProperty::Color3(val) => Color3::print_transformed(writer, val.clone())?,
-                        RawProperty::Vector2(val) => Vector2::print(writer, val.clone())?,
-                        RawProperty::Vector3(val) => Vector3::print(writer, val.clone())?,
-                        RawProperty::CFrame(..) => {
-                            CFrame::print_interleaved(
-                                writer,
-                                &properties
-                                    .iter()
-                                    .cloned()
-                                    .map(|i| {
-                                        if let RawProperty::CFrame(c) = i {
-                                            c
-                                        } else {
-                                            unreachable!()
-                                        }
-                                    })
-                                    .collect::<Vec<_>>(),
-                            )?;
-                            break;
-                        }
-                        // RawProperty::Quaternion => unimplemented!("Quaternions not supported"),
-                        RawProperty::Enum(..) => {
-                            i32::print_interleaved(
-                                writer,
-                                &properties
-                                    .iter()
-                                    .cloned()
-                                    .map(|i| {
-                                        if let RawProperty::Enum(e) = i {
-                                            e
-                                        } else {
-                                            unreachable!()
-                                        }
-                                    })
-                                    .collect::<Vec<_>>(),
-                            )?;
-                            break;
-                        }
-                        RawProperty::InstanceRef(..) => {
-                            i32::print_interleaved(
-                                writer,
-                                &properties
-                                    .iter()
-                                    .cloned()
-                                    .map(|i| {
-                                        if let RawProperty::InstanceRef(e) = i {
-                                            e
-                                        } else {
-                                            unreachable!()
-                                        }
-                                    })
-                                    .collect::<Vec<_>>(),
-                            )?;
-                            break;
-                        }
-                        RawProperty::Vector3Int16(val) => Vector3Int16::print(writer, val.clone())?,
-                        RawProperty::NumberSequence(val) => {
-                            NumberSequence::print(writer, val.clone())?;
-                        }
-                        RawProperty::ColorSequence(val) => {
-                            ColorSequence::print(writer, val.clone())?;
-                        }
-                        RawProperty::NumberRange(val) => NumberRange::print(writer, val.clone())?,
-                        RawProperty::Rect(..) => {
-                            Rect::print_interleaved(
-                                writer,
-                                &properties
-                                    .iter()
-                                    .cloned()
-                                    .map(|i| {
-                                        if let RawProperty::Rect(c) = i {
-                                            c
-                                        } else {
-                                            unreachable!()
-                                        }
-                                    })
-                                    .collect::<Vec<_>>(),
-                            )?;
-                            break;
-                        }
-                        RawProperty::PhysicalProperties(val) => {
-                            PhysicalProperties::print(writer, val.clone())?;
-                        }
-                        RawProperty::Color3Uint8(val) => Color3Uint8::print(writer, val.clone())?,
-                        RawProperty::Int64(val) => i64::print(writer, *val)?,
-                        RawProperty::RawSharedString(..) => {
-                            i32::print_interleaved(
-                                writer,
-                                &properties
-                                    .iter()
-                                    .cloned()
-                                    .map(|i| {
-                                        if let RawProperty::InstanceRef(e) = i {
-                                            e
-                                        } else {
-                                            unreachable!()
-                                        }
-                                    })
-                                    .collect::<Vec<_>>(),
-                            )?;
-                            break;
-                        }
-                        RawProperty::Pivot(..) => {
-                            CFrame::print_interleaved(
-                                writer,
-                                &properties
-                                    .iter()
-                                    .cloned()
-                                    .map(|i| {
-                                        if let RawProperty::CFrame(c) = i {
-                                            c
-                                        } else {
-                                            unreachable!()
-                                        }
-                                    })
-                                    .collect::<Vec<_>>(),
-                            )?;
-                            break;
-                        }
-                        RawProperty::Uuid(uuid) => Uuid::print(writer, *uuid)?,
-                        RawProperty::Font(font) => FontFace::print(writer, font.clone())?,
-                    }
-                }
-
-                b"PROP"
-            }
-            Block::Parent {
-                mut instance_referents,
-                mut parent_referents,
-            } => {
-                encode_cumulative(&mut instance_referents);
-                encode_cumulative(&mut parent_referents);
-
-                u8::print(writer, 0)?;
-                i32::print(writer, instance_referents.len() as i32)?;
-                i32::print_interleaved_transformed(writer, &instance_referents)?;
-                i32::print_interleaved_transformed(writer, &parent_referents)?;
-                b"PRNT"
-            }
-            Block::End => {
-                // END is special
-                self.writer.write_all(b"END\0")?;
-                i32::print(&mut self.writer, 0)?;
-                i32::print(&mut self.writer, 9)?;
-                i32::print(&mut self.writer, 0)?;
-                return Ok(());
-            }
+    /// Serialize a model to the output stream. Fails if instances of one class have a property
+    /// of the same name with different types, as each property is stored once per class.
+    pub fn serialize(self, model: &RbxModel) -> Result<()> {
+        let chunks = break_model(model, &self.options)?;
+        let mut writer = ChunkWriter::with_options(self.writer, chunks.header, self.options)?;
+
+        // Unknown chunks go back after the last chunk of the kind they originally followed
+        let write_unknown = |writer: &mut ChunkWriter<W>, after: Option<&str>| {
+            model
+                .unknown_chunks
+                .iter()
+                .filter(|chunk| chunk.after.as_deref() == after)
+                .try_for_each(|chunk| writer.write_raw(&chunk.name, &chunk.data).map(drop))
         };
 
-        let compressed_data = lz4_flex::block::compress(&out_buffer);
-
-        self.writer.write_all(block_name)?;
-        i32::print(&mut self.writer, compressed_data.len() as i32)?;
-        i32::print(&mut self.writer, out_buffer.len() as i32)?;
-        i32::print(&mut self.writer, 0)?;
-        self.writer.write_all(&compressed_data)?;
+        write_unknown(&mut writer, None)?;
+        writer.write_meta(chunks.meta)?;
+        write_unknown(&mut writer, Some("META"))?;
+        if let Some(sstr) = chunks.sstr {
+            writer.write_sstr(sstr)?;
+        }
+        write_unknown(&mut writer, Some("SSTR"))?;
+        for inst in chunks.insts {
+            writer.write_inst(inst)?;
+        }
+        write_unknown(&mut writer, Some("INST"))?;
+        for prop in chunks.props {
+            writer.write_prop(prop)?;
+        }
+        write_unknown(&mut writer, Some("PROP"))?;
+        writer.write_prnt(chunks.prnt)?;
+
+        // Anything else, including chunks following the last known one, goes right before END
+        model
+            .unknown_chunks
+            .iter()
+            .filter(|chunk| {
+                !matches!(
+                    chunk.after.as_deref(),
+                    None | Some("META" | "SSTR" | "INST" | "PROP")
+                )
+            })
+            .try_for_each(|chunk| writer.write_raw(&chunk.name, &chunk.data).map(drop))?;
+        writer.finish()?;
 
         Ok(())
     }
@@ -506,3 +433,475 @@ pub fn to_bytes(model: &RbxModel) -> Result<Vec<u8>> {
     to_writer(&mut out, model)?;
     Ok(out)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serde::de::from_bytes;
+
+    fn make_model() -> RbxModel {
+        let model = RbxModel::default();
+        let mut props = BTreeMap::new();
+        props.insert(
+            String::from("Name"),
+            Property::TextString(String::from("Root")),
+        );
+        props.insert(String::from("Value"), Property::Int32(10));
+        model
+            .tree()
+            .add_root(Instance::Other(String::from("Custom"), props));
+        model
+    }
+
+    fn round_trip(options: SerializerOptions) -> Vec<u8> {
+        let mut out = Vec::new();
+        Serializer::with_options(&mut out, options)
+            .serialize(&make_model())
+            .unwrap();
+
+        let model = from_bytes(&out).unwrap();
+        let root = model.get_path("Root").unwrap();
+        match &*root {
+            Instance::Other(_, props) => assert!(matches!(props["Value"], Property::Int32(10))),
+            _ => panic!("Expected an unrecognized instance"),
+        }
+
+        out
+    }
+
+    #[test]
+    fn test_lz4_round_trip() {
+        round_trip(SerializerOptions {
+            compression: Compression::Lz4,
+            ..SerializerOptions::default()
+        });
+    }
+
+    #[test]
+    fn test_uncompressed_round_trip() {
+        let out = round_trip(SerializerOptions {
+            compression: Compression::None,
+            ..SerializerOptions::default()
+        });
+
+        // The first block is META, whose header follows the 32 byte file header
+        assert_eq!(out[32..36], *b"META");
+        assert_eq!(out[36..40], [0, 0, 0, 0]);
+        assert_eq!(out[48..52], [1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_smallest_round_trip() {
+        let out = round_trip(SerializerOptions {
+            compression: Compression::Smallest,
+            ..SerializerOptions::default()
+        });
+
+        // A META block with a single short entry doesn't benefit from compression
+        assert_eq!(out[36..40], [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_chunk_compression() {
+        let mut chunk_compression = BTreeMap::new();
+        chunk_compression.insert(String::from("META"), Compression::None);
+        let out = round_trip(SerializerOptions {
+            compression: Compression::Lz4,
+            chunk_compression,
+            ..SerializerOptions::default()
+        });
+
+        // META is stored raw, while the INST block after it is still compressed
+        assert_eq!(out[32..36], *b"META");
+        assert_eq!(out[36..40], [0, 0, 0, 0]);
+        let inst = out.windows(4).position(|name| name == b"INST").unwrap();
+        assert_ne!(out[inst + 4..inst + 8], [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_deterministic() {
+        fn other(class_name: &str, value: &str) -> Instance {
+            let mut props = BTreeMap::new();
+            props.insert(
+                String::from("Name"),
+                Property::TextString(String::from(class_name)),
+            );
+            props.insert(
+                String::from("Value"),
+                Property::SharedTextString(String::from(value)),
+            );
+            Instance::Other(String::from(class_name), props)
+        }
+
+        let first = RbxModel::default();
+        let root = first.tree().add_root(other("Zed", "a"));
+        first.tree().new_child(other("Beta", "b"), root);
+        first.tree().new_child(other("Alpha", "c"), root);
+
+        // The same logical model, with its nodes created in a different order
+        let second = RbxModel::default();
+        let alpha = second.tree().add_root(other("Alpha", "c"));
+        let beta = second.tree().add_root(other("Beta", "b"));
+        let root = second.tree().add_root(other("Zed", "a"));
+        second.tree().set_child(root, beta);
+        second.tree().set_child(root, alpha);
+
+        let serialize = |model: &RbxModel| {
+            let mut out = Vec::new();
+            let options = SerializerOptions {
+                compression: Compression::None,
+                deterministic: true,
+                ..SerializerOptions::default()
+            };
+            Serializer::with_options(&mut out, options)
+                .serialize(model)
+                .unwrap();
+            out
+        };
+
+        let out = serialize(&first);
+        assert_eq!(out, serialize(&second));
+
+        // Classes are numbered by name, so the first INST block is `Alpha` with index 0
+        let inst = out.windows(4).position(|w| w == b"INST").unwrap();
+        assert_eq!(out[inst + 16..inst + 20], [0, 0, 0, 0]);
+        assert_eq!(out[inst + 24..inst + 29], *b"Alpha");
+    }
+
+    #[test]
+    fn test_shared_string_round_trip() {
+        let model = RbxModel::default();
+        let mut props = BTreeMap::new();
+        props.insert(
+            String::from("Name"),
+            Property::TextString(String::from("Root")),
+        );
+        props.insert(
+            String::from("Blob"),
+            Property::SharedBinaryString(vec![1, 2, 255]),
+        );
+        model
+            .tree()
+            .add_root(Instance::Other(String::from("Custom"), props));
+
+        let new_model = from_bytes(&to_bytes(&model).unwrap()).unwrap();
+        let root = new_model.get_path("Root").unwrap();
+        match &*root {
+            Instance::Other(_, props) => assert!(
+                matches!(&props["Blob"], Property::SharedBinaryString(blob) if blob == &[1, 2, 255])
+            ),
+            _ => panic!("Expected an unrecognized instance"),
+        }
+    }
+
+    #[test]
+    fn test_service_round_trip() {
+        let mut model = make_model();
+        let root = model.tree().root_keys().next().unwrap();
+        let mut props = BTreeMap::new();
+        props.insert(
+            String::from("Name"),
+            Property::TextString(String::from("Child")),
+        );
+        props.insert(String::from("Value"), Property::Int32(20));
+        model
+            .tree()
+            .new_child(Instance::Other(String::from("Custom"), props), root);
+        model.set_service(root, true);
+
+        let new_model = from_bytes(&to_bytes(&model).unwrap()).unwrap();
+        let root = new_model.get_path("Root").unwrap().key();
+        let child = new_model.get_path("Root/Child").unwrap().key();
+        assert!(new_model.is_service(root));
+        assert!(!new_model.is_service(child));
+    }
+
+    #[test]
+    fn test_unknown_chunks() {
+        use crate::serde::chunk::{ChunkReader, UnknownChunk};
+
+        let mut model = make_model();
+        model.unknown_chunks_mut().extend([
+            UnknownChunk {
+                name: String::from("SIGN"),
+                data: vec![1, 2, 3],
+                after: Some(String::from("META")),
+            },
+            UnknownChunk {
+                name: String::from("NEW"),
+                data: vec![],
+                after: Some(String::from("PRNT")),
+            },
+        ]);
+
+        let data = to_bytes(&model).unwrap();
+        let names = ChunkReader::new(&*data)
+            .unwrap()
+            .map(|chunk| chunk.unwrap().header.name)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["META", "SIGN", "INST", "PROP", "PROP", "PRNT", "NEW", "END"]
+        );
+
+        let new_model = from_bytes(&data).unwrap();
+        assert_eq!(new_model.unknown_chunks(), model.unknown_chunks());
+    }
+
+    #[test]
+    fn test_capabilities_and_content() {
+        fn other(name: &str, caps: u64, content: Content) -> Instance {
+            let mut props = BTreeMap::new();
+            props.insert(
+                String::from("Name"),
+                Property::TextString(String::from(name)),
+            );
+            props.insert(
+                String::from("Capabilities"),
+                Property::SecurityCapabilities(SecurityCapabilities(caps)),
+            );
+            props.insert(String::from("Content"), Property::Content(content));
+            Instance::Other(String::from("Custom"), props)
+        }
+
+        let model = RbxModel::default();
+        let uri = Content::Uri(String::from("rbxassetid://1234"));
+        let root = model.tree().add_root(other("Root", 5, uri));
+        model
+            .tree()
+            .new_child(other("Child", u64::MAX, Content::Object(root)), root);
+        model
+            .tree()
+            .new_child(other("Empty", 0, Content::None), root);
+
+        let new_model = from_bytes(&to_bytes(&model).unwrap()).unwrap();
+        let props = |path| match &*new_model.get_path(path).unwrap() {
+            Instance::Other(_, props) => props.clone(),
+            _ => panic!("Expected an unrecognized instance"),
+        };
+        let root = new_model.get_path("Root").unwrap().key();
+
+        let root_props = props("Root");
+        assert!(matches!(
+            root_props["Capabilities"],
+            Property::SecurityCapabilities(SecurityCapabilities(5))
+        ));
+        assert!(matches!(
+            &root_props["Content"],
+            Property::Content(Content::Uri(uri)) if uri == "rbxassetid://1234"
+        ));
+
+        let child_props = props("Root/Child");
+        assert!(matches!(
+            child_props["Capabilities"],
+            Property::SecurityCapabilities(SecurityCapabilities(u64::MAX))
+        ));
+        assert!(matches!(
+            child_props["Content"],
+            Property::Content(Content::Object(key)) if key == root
+        ));
+
+        assert!(matches!(
+            props("Root/Empty")["Content"],
+            Property::Content(Content::None)
+        ));
+    }
+
+    #[test]
+    fn test_world_pivot() {
+        use crate::model::instance::Model;
+
+        let model = RbxModel::default();
+        let mut pivoted = Model::new_named(String::from("Pivoted"));
+        pivoted.world_pivot_data = Some(CFrame::new(
+            Vector3::new(1.0, 2.0, 3.0),
+            [[0.0, 0.0, 1.0], [0.0, 1.0, 0.0], [-1.0, 0.0, 0.0]],
+        ));
+        let root = model.tree().add_root(Instance::Model(pivoted));
+        model.tree().new_child(Instance::Model(Model::new()), root);
+
+        let new_model = from_bytes(&to_bytes(&model).unwrap()).unwrap();
+        let pivoted = new_model.get_path("Pivoted").unwrap();
+        match &*pivoted {
+            Instance::Model(model) => {
+                let pivot = model.world_pivot_data.as_ref().unwrap();
+                assert_eq!(pivot.position, Vector3::new(1.0, 2.0, 3.0));
+                assert_eq!(
+                    pivot.angle,
+                    [[0.0, 0.0, 1.0], [0.0, 1.0, 0.0], [-1.0, 0.0, 0.0]]
+                );
+            }
+            _ => panic!("Expected a Model"),
+        }
+        let child = new_model.get_path("Pivoted/Model").unwrap();
+        match &*child {
+            Instance::Model(model) => assert!(model.world_pivot_data.is_none()),
+            _ => panic!("Expected a Model"),
+        }
+    }
+
+    #[test]
+    fn test_mixed_instances() {
+        use crate::model::instance::Model;
+
+        // Instances of one class that don't all have the same properties still get a value in
+        // every one of the class's property blocks
+        let model = RbxModel::default();
+        let root = model
+            .tree()
+            .add_root(Instance::Model(Model::new_named(String::from("First"))));
+        let mut second = Model::new_named(String::from("Second"));
+        second.model_in_primary = Some(CFrame::new(
+            Vector3::new(1.0, 2.0, 3.0),
+            [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+        ));
+        model.tree().new_child(Instance::Model(second), root);
+        let mut third = Model::new_named(String::from("Third"));
+        third
+            .extra_properties
+            .insert(String::from("Extra"), Property::Int32(5));
+        third
+            .extra_properties
+            .insert(String::from("Unset"), Property::Nil);
+        model.tree().new_child(Instance::Model(third), root);
+
+        let mut props = BTreeMap::new();
+        props.insert(
+            String::from("Name"),
+            Property::TextString(String::from("Blob")),
+        );
+        props.insert(
+            String::from("Blob"),
+            Property::SharedBinaryString(vec![1, 2, 255]),
+        );
+        model
+            .tree()
+            .new_child(Instance::Other(String::from("Custom"), props), root);
+        let mut props = BTreeMap::new();
+        props.insert(
+            String::from("Name"),
+            Property::TextString(String::from("Empty")),
+        );
+        model
+            .tree()
+            .new_child(Instance::Other(String::from("Custom"), props), root);
+
+        let new_model = from_bytes(&to_bytes(&model).unwrap()).unwrap();
+        let second = new_model.get_path("First/Second").unwrap();
+        match &*second {
+            Instance::Model(model) => assert_eq!(
+                model.model_in_primary.as_ref().unwrap().position,
+                Vector3::new(1.0, 2.0, 3.0)
+            ),
+            _ => panic!("Expected a Model"),
+        }
+        let third = new_model.get_path("First/Third").unwrap();
+        match &*third {
+            Instance::Model(model) => {
+                assert!(matches!(
+                    model.extra_properties["Extra"],
+                    Property::Int32(5)
+                ));
+            }
+            _ => panic!("Expected a Model"),
+        }
+        let blob = new_model.get_path("First/Blob").unwrap();
+        match &*blob {
+            Instance::Other(_, props) => assert!(
+                matches!(&props["Blob"], Property::SharedBinaryString(blob) if blob == &[1, 2, 255])
+            ),
+            _ => panic!("Expected an unrecognized instance"),
+        }
+    }
+
+    #[test]
+    fn test_conflicting_property_types() {
+        use crate::model::property::PropertyType;
+        use crate::serde::ErrorKind;
+
+        let model = RbxModel::default();
+        for value in [
+            Property::Int32(5),
+            Property::TextString(String::from("Five")),
+        ] {
+            let mut props = BTreeMap::new();
+            props.insert(String::from("Value"), value);
+            model
+                .tree()
+                .add_root(Instance::Other(String::from("Custom"), props));
+        }
+
+        let err = to_bytes(&model).unwrap_err();
+        assert!(matches!(
+            &err.kind,
+            ErrorKind::WrongPropertyType(name, Some((PropertyType::Int32, PropertyType::TextString)))
+                if name == "Value"
+        ));
+        assert_eq!(err.context.class_name.as_deref(), Some("Custom"));
+    }
+
+    #[test]
+    fn test_attribute_only_properties() {
+        let model = RbxModel::default();
+        let mut props = BTreeMap::new();
+        props.insert(
+            String::from("Name"),
+            Property::TextString(String::from("Root")),
+        );
+        props.insert(
+            String::from("Material"),
+            Property::EnumItem(EnumItem::new(String::from("Material"), 256)),
+        );
+        props.insert(String::from("Unset"), Property::Nil);
+        model
+            .tree()
+            .add_root(Instance::Other(String::from("Custom"), props));
+
+        // Outside of attributes, an enum item is only its value, and an unset value isn't written
+        let new_model = from_bytes(&to_bytes(&model).unwrap()).unwrap();
+        let root = new_model.get_path("Root").unwrap();
+        match &*root {
+            Instance::Other(_, props) => {
+                assert!(matches!(props["Material"], Property::Enum(256)));
+                assert!(!props.contains_key("Unset"));
+            }
+            _ => panic!("Expected an unrecognized instance"),
+        }
+
+        assert!(RawProperty::from_real(Property::Nil).is_err());
+    }
+
+    #[test]
+    fn test_renamed_string_property() {
+        use crate::model::instance::Part;
+
+        // String fields with a `#[propname]` are written under that name, not the field's own
+        let mut part = Part::new();
+        part.material_variant = String::from("Mossy");
+        assert!(Instance::Part(part.clone())
+            .break_instance()
+            .contains_key("MaterialVariantSerialized"));
+
+        let model = RbxModel::default();
+        model.tree().add_root(Instance::Part(part));
+
+        let new_model = from_bytes(&to_bytes(&model).unwrap()).unwrap();
+        let part = new_model.get_path("Part").unwrap();
+        match &*part {
+            Instance::Part(part) => assert_eq!(part.material_variant, "Mossy"),
+            _ => panic!("Expected a Part"),
+        }
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_round_trip() {
+        let out = round_trip(SerializerOptions {
+            compression: Compression::Zstd,
+            ..SerializerOptions::default()
+        });
+
+        // The first block is META, its data follows the 32 byte file header and 16 byte block header
+        assert_eq!(out[48..52], [0x28, 0xB5, 0x2F, 0xFD]);
+    }
+}
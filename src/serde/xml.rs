@@ -0,0 +1,12 @@
+//! The ability to serialize/deserialize the XML variant of the model format, as used by `.rbxmx`
+//! and `.rbxlx` files
+
+pub mod de;
+pub mod ser;
+
+#[cfg(feature = "std")]
+pub use de::from_file;
+pub use de::{from_reader, from_str};
+#[cfg(feature = "std")]
+pub use ser::to_file;
+pub use ser::{to_string, to_writer};
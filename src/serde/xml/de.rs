@@ -0,0 +1,865 @@
+//! The deserialization implementation for an XML model
+
+use crate::model::*;
+use crate::serde::de::{make_model, DeserializerOptions, RawInfo, Strictness};
+use crate::serde::format::Format;
+use crate::serde::internal::{shared_string_hash, RawContent, RawProperty};
+use crate::serde::io::Read;
+use crate::serde::{Error, ErrorKind, Result};
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use base64::Engine;
+use core::str::FromStr;
+use roxmltree::{Document, Node};
+use uuid::Uuid;
+
+/// The deepest element nesting accepted in a document. The XML parser recurses once per level,
+/// so deeper documents are rejected up front rather than risk overflowing the stack.
+const MAX_ELEMENT_DEPTH: usize = 256;
+
+fn elements<'a, 'input>(node: Node<'a, 'input>) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children().filter(Node::is_element)
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Result<Node<'a, 'input>> {
+    elements(node)
+        .find(|n| n.tag_name().name() == name)
+        .ok_or_else(|| {
+            Error::invalid_xml(format!(
+                "Element `{}` is missing child `{name}`",
+                node.tag_name().name()
+            ))
+        })
+}
+
+fn text(node: Node<'_, '_>) -> String {
+    node.children()
+        .filter(Node::is_text)
+        .filter_map(|n| n.text())
+        .collect()
+}
+
+fn parse<T: FromStr>(str: &str) -> Result<T> {
+    str.trim()
+        .parse()
+        .map_err(|_| Error::invalid_xml(format!("Couldn't parse value `{str}`")))
+}
+
+fn parse_float<T: FromStr + num::Float>(str: &str) -> Result<T> {
+    match str.trim() {
+        "INF" => Ok(T::infinity()),
+        "-INF" => Ok(T::neg_infinity()),
+        "NAN" => Ok(T::nan()),
+        str => parse(str),
+    }
+}
+
+fn child_float(node: Node<'_, '_>, name: &str) -> Result<f32> {
+    parse_float(&text(child(node, name)?))
+}
+
+fn child_int<T: FromStr>(node: Node<'_, '_>, name: &str) -> Result<T> {
+    parse(&text(child(node, name)?))
+}
+
+fn read_vector2(node: Node<'_, '_>) -> Result<Vector2> {
+    Ok(Vector2::new(
+        child_float(node, "X")?,
+        child_float(node, "Y")?,
+    ))
+}
+
+fn read_vector3(node: Node<'_, '_>) -> Result<Vector3> {
+    Ok(Vector3::new(
+        child_float(node, "X")?,
+        child_float(node, "Y")?,
+        child_float(node, "Z")?,
+    ))
+}
+
+fn read_cframe(node: Node<'_, '_>) -> Result<CFrame> {
+    let position = read_vector3(node)?;
+    let mut angle = [[0.0; 3]; 3];
+    for (r, row) in angle.iter_mut().enumerate() {
+        for (c, val) in row.iter_mut().enumerate() {
+            *val = child_float(node, &format!("R{r}{c}"))?;
+        }
+    }
+    Ok(CFrame::new(position, angle))
+}
+
+fn read_color3(node: Node<'_, '_>) -> Result<Color3> {
+    if elements(node).next().is_some() {
+        Ok(Color3 {
+            r: child_float(node, "R")?,
+            g: child_float(node, "G")?,
+            b: child_float(node, "B")?,
+        })
+    } else {
+        Ok(read_color3uint8(node)?.into())
+    }
+}
+
+fn read_color3uint8(node: Node<'_, '_>) -> Result<Color3Uint8> {
+    let [_, r, g, b] = parse::<u32>(&text(node))?.to_be_bytes();
+    Ok(Color3Uint8::new(r, g, b))
+}
+
+fn read_url(node: Node<'_, '_>) -> String {
+    match elements(node).next() {
+        Some(inner) if inner.tag_name().name() == "null" => String::new(),
+        Some(inner) => text(inner),
+        None => text(node),
+    }
+}
+
+fn read_floats(node: Node<'_, '_>) -> Result<Vec<f32>> {
+    text(node).split_whitespace().map(parse_float).collect()
+}
+
+fn read_font(node: Node<'_, '_>) -> Result<FontFace> {
+    let weight = child_int::<i32>(node, "Weight")?;
+    let weight = FontWeight::try_from(weight).map_err(|_| Error::unknown_variant(weight))?;
+    let style = match text(child(node, "Style")?).trim() {
+        "Normal" => FontStyle::Normal,
+        "Italic" => FontStyle::Italic,
+        style => return Err(Error::invalid_xml(format!("Unknown font style `{style}`"))),
+    };
+
+    Ok(FontFace {
+        family: read_url(child(node, "Family")?),
+        weight,
+        style,
+        cached_face_id: child(node, "CachedFaceId")
+            .map(read_url)
+            .unwrap_or_default(),
+    })
+}
+
+fn decode_base64(str: &str) -> Result<Vec<u8>> {
+    let str = str
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>();
+    base64::engine::general_purpose::STANDARD
+        .decode(str)
+        .map_err(|_| Error::invalid_xml(String::from("Invalid base64 data")))
+}
+
+/// State needed to resolve referents and shared strings while reading properties
+struct Lookup<'a> {
+    referents: BTreeMap<&'a str, i32>,
+    shared_strs: BTreeMap<&'a str, i32>,
+}
+
+impl Lookup<'_> {
+    fn referent(&self, node: Node<'_, '_>) -> Result<i32> {
+        let referent = text(node);
+        let referent = referent.trim();
+        match self.referents.get(referent) {
+            Some(&id) => Ok(id),
+            // Unset references are written as `null`
+            None if referent.is_empty() || referent == "null" => Ok(-1),
+            None => Err(Error::unknown_referent(referent.to_string())),
+        }
+    }
+}
+
+/// Read a `Content` element. Older files use the same name for asset URLs stored as plain
+/// strings, so anything not in the newer encoding is read as a string.
+fn read_content(node: Node<'_, '_>, lookup: &Lookup<'_>) -> Result<RawProperty> {
+    let content = match elements(node).next() {
+        Some(inner) if inner.tag_name().name() == "None" => RawContent::None,
+        Some(inner) if inner.tag_name().name() == "Uri" => RawContent::Uri(text(inner)),
+        Some(inner) if inner.tag_name().name() == "Object" => match lookup.referent(inner)? {
+            -1 => RawContent::None,
+            id => RawContent::Object(id),
+        },
+        _ => return Ok(RawProperty::RawString(read_url(node).into_bytes())),
+    };
+    Ok(RawProperty::Content(content))
+}
+
+fn read_property(node: Node<'_, '_>, lookup: &Lookup<'_>) -> Result<RawProperty> {
+    let prop = match node.tag_name().name() {
+        "string" | "ProtectedString" => RawProperty::RawString(text(node).into_bytes()),
+        "Content" => read_content(node, lookup)?,
+        "BinaryString" => RawProperty::RawString(decode_base64(&text(node))?),
+        "bool" => RawProperty::Bool(match text(node).trim() {
+            "true" => true,
+            "false" => false,
+            val => return Err(Error::invalid_xml(format!("Invalid bool `{val}`"))),
+        }),
+        "int" => RawProperty::Int32(parse(&text(node))?),
+        "int64" => RawProperty::Int64(parse(&text(node))?),
+        "float" => RawProperty::Float(parse_float(&text(node))?),
+        "double" => RawProperty::Double(parse_float(&text(node))?),
+        "token" => RawProperty::Enum(parse::<u32>(&text(node))? as i32),
+        "UDim" => RawProperty::UDim(UDim::new(child_float(node, "S")?, child_int(node, "O")?)),
+        "UDim2" => RawProperty::UDim2(UDim2::new_components(
+            child_float(node, "XS")?,
+            child_int(node, "XO")?,
+            child_float(node, "YS")?,
+            child_int(node, "YO")?,
+        )),
+        "Ray" => RawProperty::Ray(Ray {
+            origin: read_vector3(child(node, "origin")?)?,
+            direction: read_vector3(child(node, "direction")?)?,
+        }),
+        "Faces" => {
+            let bits = child_int::<u8>(node, "faces")?;
+            RawProperty::Face(Faces {
+                right: bits & 0b0000_0001 != 0,
+                top: bits & 0b0000_0010 != 0,
+                back: bits & 0b0000_0100 != 0,
+                left: bits & 0b0000_1000 != 0,
+                bottom: bits & 0b0001_0000 != 0,
+                front: bits & 0b0010_0000 != 0,
+            })
+        }
+        "Axes" => {
+            let bits = child_int::<u8>(node, "axes")?;
+            RawProperty::Axis(Axes {
+                x: bits & 0b001 != 0,
+                y: bits & 0b010 != 0,
+                z: bits & 0b100 != 0,
+            })
+        }
+        "BrickColor" => RawProperty::BrickColor(BrickColor {
+            index: parse(&text(node))?,
+        }),
+        "Color3" => RawProperty::Color3(read_color3(node)?),
+        "Color3uint8" => RawProperty::Color3Uint8(read_color3uint8(node)?),
+        "Vector2" => RawProperty::Vector2(read_vector2(node)?),
+        "Vector3" => RawProperty::Vector3(read_vector3(node)?),
+        "Vector3int16" => RawProperty::Vector3Int16(Vector3Int16 {
+            x: child_int(node, "X")?,
+            y: child_int(node, "Y")?,
+            z: child_int(node, "Z")?,
+        }),
+        "CoordinateFrame" | "CFrame" => RawProperty::CFrame(read_cframe(node)?),
+        "OptionalCoordinateFrame" => {
+            // An empty element means the value is unset
+            let cframe = match child(node, "CFrame") {
+                Ok(cframe) => Some(read_cframe(cframe)?),
+                Err(_) => None,
+            };
+            RawProperty::OptionalCFrame(cframe)
+        }
+        "Ref" => RawProperty::InstanceRef(lookup.referent(node)?),
+        "SharedString" => {
+            let key = text(node);
+            let id = lookup
+                .shared_strs
+                .get(key.trim())
+                .copied()
+                .ok_or_else(|| Error::invalid_xml(format!("Unknown shared string `{key}`")))?;
+            RawProperty::RawSharedString(id)
+        }
+        "NumberSequence" => {
+            let keypoints = read_floats(node)?
+                .chunks_exact(3)
+                .map(|vals| NumberKeypoint {
+                    time: vals[0],
+                    value: vals[1],
+                    envelope: vals[2],
+                })
+                .collect();
+            RawProperty::NumberSequence(NumberSequence { keypoints })
+        }
+        "ColorSequence" => {
+            let keypoints = read_floats(node)?
+                .chunks_exact(5)
+                .map(|vals| ColorKeypoint {
+                    time: vals[0],
+                    color: Color3 {
+                        r: vals[1],
+                        g: vals[2],
+                        b: vals[3],
+                    },
+                    envelope: vals[4],
+                })
+                .collect();
+            RawProperty::ColorSequence(ColorSequence { keypoints })
+        }
+        "NumberRange" => {
+            let vals = read_floats(node)?;
+            if vals.len() < 2 {
+                return Err(Error::invalid_xml(String::from(
+                    "NumberRange needs two values",
+                )));
+            }
+            RawProperty::NumberRange(NumberRange {
+                low: vals[0],
+                high: vals[1],
+            })
+        }
+        "Rect2D" => RawProperty::Rect(Rect {
+            top_left: read_vector2(child(node, "min")?)?,
+            bottom_right: read_vector2(child(node, "max")?)?,
+        }),
+        "PhysicalProperties" => {
+            let custom = text(child(node, "CustomPhysics")?).trim() == "true";
+            let props = if custom {
+                PhysicalProperties::Custom {
+                    density: child_float(node, "Density")?,
+                    friction: child_float(node, "Friction")?,
+                    elasticity: child_float(node, "Elasticity")?,
+                    friction_weight: child_float(node, "FrictionWeight")?,
+                    elasticity_weight: child_float(node, "ElasticityWeight")?,
+                }
+            } else {
+                PhysicalProperties::Default
+            };
+            RawProperty::PhysicalProperties(props)
+        }
+        "SecurityCapabilities" => {
+            RawProperty::SecurityCapabilities(SecurityCapabilities(parse(&text(node))?))
+        }
+        "UniqueId" => RawProperty::Uuid(
+            Uuid::parse_str(text(node).trim())
+                .map_err(|_| Error::invalid_xml(String::from("Invalid UniqueId")))?,
+        ),
+        "Font" => RawProperty::Font(read_font(node)?),
+        name => return Err(Error::unknown_xml_type(name.to_string())),
+    };
+
+    Ok(prop)
+}
+
+/// Collect every `Item` below a node in document order, along with its ID and its parent's ID.
+/// Items are walked with an explicit stack, so deeply nested documents can't overflow the call
+/// stack.
+fn collect_items<'a, 'input>(
+    node: Node<'a, 'input>,
+    options: &DeserializerOptions,
+) -> Result<Vec<(Node<'a, 'input>, i32, i32)>> {
+    let mut items = Vec::new();
+    let mut stack = vec![(elements(node), -1, 1)];
+    while let Some((children, parent, depth)) = stack.last_mut() {
+        let item = match children.find(|n| n.tag_name().name() == "Item") {
+            Some(item) => item,
+            None => {
+                stack.pop();
+                continue;
+            }
+        };
+        let (parent, depth) = (*parent, *depth);
+        let id = items.len() as i32;
+        options.check_instances(items.len() + 1)?;
+        options
+            .check_depth(depth)
+            .map_err(|err| err.with_instance(id))?;
+        items.push((item, id, parent));
+        stack.push((elements(item), id, depth + 1));
+    }
+    Ok(items)
+}
+
+fn deserialize_document(
+    doc: &Document<'_>,
+    options: &DeserializerOptions,
+) -> Result<(RbxModel, Vec<Error>)> {
+    let root = doc.root_element();
+    if root.tag_name().name() != "roblox" {
+        return Err(Error::bad_magic());
+    }
+
+    let mut raw_info = RawInfo::default();
+    let mut lookup = Lookup {
+        referents: BTreeMap::new(),
+        shared_strs: BTreeMap::new(),
+    };
+
+    for node in elements(root) {
+        match node.tag_name().name() {
+            "Meta" => {
+                let name = node
+                    .attribute("name")
+                    .ok_or_else(|| Error::invalid_xml(String::from("Meta is missing a name")))?;
+                let value = text(node);
+                options.check_string(value.len())?;
+                raw_info.meta.insert(name.to_string(), value);
+            }
+            "SharedStrings" => {
+                for shared in elements(node) {
+                    let key = shared.attribute("md5").ok_or_else(|| {
+                        Error::invalid_xml(String::from("SharedString is missing a key"))
+                    })?;
+                    lookup
+                        .shared_strs
+                        .insert(key, raw_info.shared_strs.len() as i32);
+                    let value = decode_base64(&text(shared))?;
+                    options.check_string(value.len())?;
+
+                    if options.verify_shared_string_hashes
+                        && decode_base64(key).ok().as_deref()
+                            != Some(&shared_string_hash(&value)[..])
+                    {
+                        let index = raw_info.shared_strs.len();
+                        raw_info.hash_mismatch(index, options)?;
+                    }
+
+                    raw_info.shared_strs.push(value);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    let items = collect_items(root, options)?;
+
+    for &(item, id, _) in &items {
+        if let Some(referent) = item.attribute("referent") {
+            lookup.referents.insert(referent, id);
+        }
+    }
+
+    let mut classes = BTreeSet::new();
+    for (item, id, parent) in items {
+        let offset = item.range().start as u64;
+        let class_name = item.attribute("class").ok_or_else(|| {
+            Error::invalid_xml(String::from("Item is missing a class"))
+                .with_instance(id)
+                .with_offset(offset)
+        })?;
+        let context = |err: Error| {
+            err.with_instance(id)
+                .with_class_name(class_name)
+                .with_offset(offset)
+        };
+
+        classes.insert(class_name);
+        options.check_classes(classes.len()).map_err(context)?;
+
+        let mut props = BTreeMap::new();
+        if let Some(properties) = elements(item).find(|n| n.tag_name().name() == "Properties") {
+            for prop in elements(properties) {
+                let offset = prop.range().start as u64;
+                let name = prop.attribute("name").ok_or_else(|| {
+                    context(
+                        Error::invalid_xml(String::from("Property is missing a name"))
+                            .with_offset(offset),
+                    )
+                })?;
+                let value = match read_property(prop, &lookup) {
+                    // Dangling references are cleared in lenient mode, like other bad data
+                    Err(err)
+                        if options.strictness == Strictness::Lenient
+                            && matches!(err.kind, ErrorKind::UnknownReferent(_)) =>
+                    {
+                        raw_info
+                            .warnings
+                            .push(context(err.with_property(name).with_offset(offset)));
+                        if prop.tag_name().name() == "Content" {
+                            Ok(RawProperty::Content(RawContent::None))
+                        } else {
+                            Ok(RawProperty::InstanceRef(-1))
+                        }
+                    }
+                    value => value,
+                }
+                .and_then(|value| match &value {
+                    RawProperty::RawString(bytes) => {
+                        options.check_string(bytes.len()).map(|_| value)
+                    }
+                    _ => Ok(value),
+                })
+                .map_err(|err| context(err.with_property(name).with_offset(offset)))?;
+                props.insert(name.to_string(), value);
+            }
+        }
+
+        raw_info.instances.insert(id, class_name.to_string());
+        raw_info.raw_props.insert(id, props);
+        raw_info.parent_info.insert(id, parent);
+        raw_info.child_info.entry(parent).or_default().push(id);
+    }
+
+    let (mut model, warnings) = make_model(raw_info, options)?;
+    model.format = Format::XML;
+    Ok((model, warnings))
+}
+
+/// Find the end of the first `pattern` at or after `from`, or the end of the text if there isn't
+/// one
+fn skip_past(text: &[u8], from: usize, pattern: &[u8]) -> usize {
+    text[from..]
+        .windows(pattern.len())
+        .position(|window| window == pattern)
+        .map_or(text.len(), |offset| from + offset + pattern.len())
+}
+
+/// Check that elements aren't nested deeper than [`MAX_ELEMENT_DEPTH`], without building a tree.
+/// Malformed markup is left for the parser to report.
+fn check_nesting(text: &[u8]) -> Result<()> {
+    let mut depth = 0usize;
+    let mut pos = 0;
+    while let Some(offset) = text[pos..].iter().position(|&b| b == b'<') {
+        pos += offset;
+        let rest = &text[pos..];
+        if rest.starts_with(b"<!--") {
+            pos = skip_past(text, pos, b"-->");
+        } else if rest.starts_with(b"<![CDATA[") {
+            pos = skip_past(text, pos, b"]]>");
+        } else if rest.starts_with(b"<?") {
+            pos = skip_past(text, pos, b"?>");
+        } else if rest.starts_with(b"</") {
+            depth = depth.saturating_sub(1);
+            pos += 2;
+        } else if rest.starts_with(b"<!") {
+            // Declarations don't open an element
+            pos += 2;
+        } else {
+            // Skip to the end of the start tag, where `>` may appear in attribute values
+            let mut quote = None;
+            let mut end = pos + 1;
+            while end < text.len() {
+                match (quote, text[end]) {
+                    (None, b'>') => break,
+                    (None, byte @ (b'"' | b'\'')) => quote = Some(byte),
+                    (Some(open), byte) if byte == open => quote = None,
+                    _ => (),
+                }
+                end += 1;
+            }
+
+            if text[end - 1] != b'/' {
+                depth += 1;
+                if depth > MAX_ELEMENT_DEPTH {
+                    return Err(Error::invalid_xml(format!(
+                        "Elements are nested more than {MAX_ELEMENT_DEPTH} deep"
+                    ))
+                    .with_offset(pos as u64));
+                }
+            }
+            pos = end;
+        }
+    }
+    Ok(())
+}
+
+fn deserialize_str(text: &str, options: &DeserializerOptions) -> Result<(RbxModel, Vec<Error>)> {
+    check_nesting(text.as_bytes())?;
+    let doc = Document::parse(text).map_err(|err| Error::invalid_xml(err.to_string()))?;
+    deserialize_document(&doc, options)
+}
+
+/// Necessary state for deserializing a value
+pub struct Deserializer<R> {
+    reader: R,
+    options: DeserializerOptions,
+}
+
+impl<R: Read> Deserializer<R> {
+    /// Create a new deserializer from a reader and if necessary any other state
+    pub fn new(reader: R) -> Deserializer<R> {
+        Deserializer::with_options(reader, DeserializerOptions::default())
+    }
+
+    /// Create a new deserializer from a reader, with a set of options controlling how the model
+    /// is read
+    pub fn with_options(reader: R, options: DeserializerOptions) -> Deserializer<R> {
+        Deserializer { reader, options }
+    }
+
+    /// Deserialize a model from the input stream
+    pub fn deserialize(self) -> Result<RbxModel> {
+        self.deserialize_with_warnings().map(|(model, _)| model)
+    }
+
+    /// Deserialize a model from the input stream, also returning any warnings produced. Warnings
+    /// are only produced by non-strict deserialization.
+    pub fn deserialize_with_warnings(mut self) -> Result<(RbxModel, Vec<Error>)> {
+        let data = self.options.read_limited(&mut self.reader)?;
+        let text = core::str::from_utf8(&data).map_err(|_| Error::invalid_string())?;
+        deserialize_str(text, &self.options)
+    }
+}
+
+/// Read a model from the provided IO reader
+pub fn from_reader<R: Read>(reader: R) -> Result<RbxModel> {
+    Deserializer::new(reader).deserialize()
+}
+
+/// Read a model from an existing file
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[cfg(feature = "std")]
+pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> Result<RbxModel> {
+    from_reader(std::fs::File::open(path)?)
+}
+
+/// Read a model from an XML string
+pub fn from_str(text: &str) -> Result<RbxModel> {
+    deserialize_str(text, &DeserializerOptions::default()).map(|(model, _)| model)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODEL: &str = r#"<roblox xmlns:xmime="http://www.w3.org/2005/05/xmlmime" version="4">
+    <Meta name="ExplicitAutoJoints">true</Meta>
+    <External>null</External>
+    <External>nil</External>
+    <Item class="Folder" referent="RBX0">
+        <Properties>
+            <BinaryString name="AttributesSerialize"></BinaryString>
+            <string name="Name">Root</string>
+            <int64 name="SourceAssetId">-1</int64>
+            <BinaryString name="Tags"></BinaryString>
+        </Properties>
+        <Item class="ObjectValue" referent="RBX1">
+            <Properties>
+                <BinaryString name="AttributesSerialize"></BinaryString>
+                <string name="Name">Pointer</string>
+                <int64 name="SourceAssetId">-1</int64>
+                <BinaryString name="Tags"></BinaryString>
+                <Ref name="Value">RBX2</Ref>
+            </Properties>
+        </Item>
+        <Item class="Unrecognized" referent="RBX2">
+            <Properties>
+                <string name="Name">Everything</string>
+                <ProtectedString name="Source"><![CDATA[print("Hello <world>")]]></ProtectedString>
+                <Content name="Texture"><url>rbxassetid://1234</url></Content>
+                <BinaryString name="Blob">AAEC/w==</BinaryString>
+                <bool name="Bool">true</bool>
+                <int name="Int">-5</int>
+                <float name="Float">INF</float>
+                <double name="Double">0.25</double>
+                <token name="Token">3</token>
+                <UDim name="UDim"><S>0.5</S><O>10</O></UDim>
+                <UDim2 name="UDim2"><XS>1</XS><XO>2</XO><YS>3</YS><YO>4</YO></UDim2>
+                <Ray name="Ray"><origin><X>1</X><Y>2</Y><Z>3</Z></origin><direction><X>0</X><Y>0</Y><Z>1</Z></direction></Ray>
+                <Faces name="Faces"><faces>33</faces></Faces>
+                <Axes name="Axes"><axes>5</axes></Axes>
+                <BrickColor name="BrickColor">194</BrickColor>
+                <Color3 name="Color3"><R>1</R><G>0.5</G><B>0</B></Color3>
+                <Color3uint8 name="Color3uint8">4294934528</Color3uint8>
+                <Vector2 name="Vector2"><X>1</X><Y>2</Y></Vector2>
+                <Vector3 name="Vector3"><X>1</X><Y>2</Y><Z>3</Z></Vector3>
+                <Vector3int16 name="Vector3int16"><X>-1</X><Y>2</Y><Z>3</Z></Vector3int16>
+                <CoordinateFrame name="CFrame">
+                    <X>1</X><Y>2</Y><Z>3</Z>
+                    <R00>1</R00><R01>0</R01><R02>0</R02>
+                    <R10>0</R10><R11>0</R11><R12>-1</R12>
+                    <R20>0</R20><R21>1</R21><R22>0</R22>
+                </CoordinateFrame>
+                <OptionalCoordinateFrame name="Pivot"></OptionalCoordinateFrame>
+                <Ref name="Nothing">null</Ref>
+                <SharedString name="Shared">aGFzaA==</SharedString>
+                <NumberSequence name="NumberSequence">0 1 0 1 0.5 0 </NumberSequence>
+                <ColorSequence name="ColorSequence">0 1 1 1 0 1 0 0 0 0 </ColorSequence>
+                <NumberRange name="NumberRange">1 2 </NumberRange>
+                <Rect2D name="Rect"><min><X>0</X><Y>0</Y></min><max><X>5</X><Y>6</Y></max></Rect2D>
+                <PhysicalProperties name="Physics"><CustomPhysics>false</CustomPhysics></PhysicalProperties>
+                <UniqueId name="UniqueId">44b188dace632b4702e9c68d004815fc</UniqueId>
+                <Font name="Font">
+                    <Family><url>rbxasset://fonts/families/SourceSansPro.json</url></Family>
+                    <Weight>700</Weight>
+                    <Style>Italic</Style>
+                </Font>
+            </Properties>
+        </Item>
+    </Item>
+    <SharedStrings>
+        <SharedString md5="aGFzaA==">c2hhcmVk</SharedString>
+    </SharedStrings>
+</roblox>"#;
+
+    #[test]
+    fn test_read_model() {
+        let model = from_str(MODEL).unwrap();
+
+        assert_eq!(model.meta()["ExplicitAutoJoints"], "true");
+        assert_eq!(model.tree().len(), 3);
+
+        let pointer = model.get_path("Root/Pointer").unwrap();
+        let target = model.get_path("Root/Everything").unwrap();
+
+        match &*pointer {
+            Instance::ObjectValue(val) => match val.value {
+                InstanceRef::Item(key) => assert_eq!(key, target.key()),
+                InstanceRef::Null => panic!("Reference wasn't resolved"),
+            },
+            _ => panic!("Expected an ObjectValue"),
+        }
+    }
+
+    #[test]
+    fn test_read_properties() {
+        let model = from_str(MODEL).unwrap();
+        let inst = model.get_path("Root/Everything").unwrap();
+
+        let props = match &*inst {
+            Instance::Other(class, props) => {
+                assert_eq!(class, "Unrecognized");
+                props
+            }
+            _ => panic!("Expected an unrecognized instance"),
+        };
+
+        assert_eq!(props.len(), 31);
+        assert!(
+            matches!(&props["Source"], Property::TextString(src) if src == "print(\"Hello <world>\")")
+        );
+        assert!(
+            matches!(&props["Texture"], Property::TextString(url) if url == "rbxassetid://1234")
+        );
+        assert!(matches!(&props["Blob"], Property::BinaryString(blob) if blob == &[0, 1, 2, 255]));
+        assert!(matches!(props["Float"], Property::Float(val) if val == f32::INFINITY));
+        assert!(matches!(props["Token"], Property::Enum(3)));
+        assert!(
+            matches!(&props["Faces"], Property::Faces(faces) if faces.right && faces.front && !faces.top)
+        );
+        assert!(matches!(&props["Axes"], Property::Axes(axes) if axes.x && !axes.y && axes.z));
+        assert!(
+            matches!(&props["Color3uint8"], Property::Color3Uint8(col) if (col.r, col.g, col.b) == (255, 128, 0))
+        );
+        match &props["CFrame"] {
+            Property::CFrame(cframe) => {
+                assert_eq!(cframe.position, Vector3::new(1.0, 2.0, 3.0));
+                assert_eq!(
+                    cframe.angle,
+                    [[1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]]
+                );
+            }
+            _ => panic!("Expected a CFrame"),
+        }
+        assert!(matches!(&props["Pivot"], Property::OptionalCFrame(None)));
+        assert!(matches!(
+            props["Nothing"],
+            Property::InstanceRef(InstanceRef::Null)
+        ));
+        assert!(matches!(&props["Shared"], Property::SharedTextString(str) if str == "shared"));
+        assert!(
+            matches!(&props["ColorSequence"], Property::ColorSequence(seq) if seq.keypoints.len() == 2)
+        );
+        assert!(matches!(&props["Font"], Property::Font(font) if font.cached_face_id.is_empty()));
+    }
+
+    #[test]
+    fn test_error_context() {
+        let text = r#"<roblox version="4">
+            <Item class="Folder" referent="RBX0">
+                <Properties>
+                    <string name="Name">Folder</string>
+                    <int name="Value">ten</int>
+                </Properties>
+            </Item>
+        </roblox>"#;
+        let err = from_str(text).unwrap_err();
+
+        assert_eq!(err.context.class_name.as_deref(), Some("Folder"));
+        assert_eq!(err.context.property_name.as_deref(), Some("Value"));
+        assert_eq!(err.context.instance_id, Some(0));
+        assert_eq!(
+            err.context.offset,
+            text.find("<int").map(|offset| offset as u64)
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        let text = r#"<roblox version="4">
+            <Item class="Folder" referent="RBX0">
+                <Properties><string name="Name">Outer</string></Properties>
+                <Item class="Folder" referent="RBX1">
+                    <Properties><string name="Name">Inner</string></Properties>
+                </Item>
+            </Item>
+        </roblox>"#;
+
+        let options = DeserializerOptions {
+            max_tree_depth: Some(1),
+            ..DeserializerOptions::default()
+        };
+        let err = Deserializer::with_options(text.as_bytes(), options)
+            .deserialize()
+            .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::TreeTooDeep(1)));
+        assert_eq!(err.context.instance_id, Some(1));
+
+        let options = DeserializerOptions {
+            max_string_length: Some(4),
+            ..DeserializerOptions::default()
+        };
+        let err = Deserializer::with_options(text.as_bytes(), options)
+            .deserialize()
+            .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::StringTooLong(4)));
+        assert_eq!(err.context.property_name.as_deref(), Some("Name"));
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        let header = r#"<roblox version="4">"#;
+        let item = r#"<Item class="Custom">"#;
+        let nested = |depth: usize| {
+            let mut text = String::from(header);
+            for _ in 0..depth {
+                text.push_str(item);
+            }
+            for _ in 0..depth {
+                text.push_str("</Item>");
+            }
+            text.push_str("</roblox>");
+            text
+        };
+
+        let model = from_str(&nested(MAX_ELEMENT_DEPTH - 1)).unwrap();
+        assert_eq!(model.tree().len(), MAX_ELEMENT_DEPTH - 1);
+
+        let err = from_str(&nested(50_000)).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::InvalidXml(_)));
+        assert_eq!(
+            err.context.offset,
+            Some((header.len() + (MAX_ELEMENT_DEPTH - 1) * item.len()) as u64)
+        );
+    }
+
+    #[test]
+    fn test_unknown_referent() {
+        let text = r#"<roblox version="4">
+            <Item class="CustomPointer" referent="RBX0">
+                <Properties>
+                    <string name="Name">Pointer</string>
+                    <Ref name="Value">RBX9</Ref>
+                </Properties>
+            </Item>
+        </roblox>"#;
+
+        let err = from_str(text).unwrap_err();
+        assert!(matches!(&err.kind, ErrorKind::UnknownReferent(referent) if referent == "RBX9"));
+        assert_eq!(err.context.property_name.as_deref(), Some("Value"));
+
+        let options = DeserializerOptions {
+            strictness: Strictness::Lenient,
+            ..DeserializerOptions::default()
+        };
+        let (model, warnings) = Deserializer::with_options(text.as_bytes(), options)
+            .deserialize_with_warnings()
+            .unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(matches!(warnings[0].kind, ErrorKind::UnknownReferent(_)));
+
+        let pointer = model.get_path("Pointer").unwrap();
+        match &*pointer {
+            Instance::Other(_, props) => assert!(matches!(
+                props["Value"],
+                Property::InstanceRef(InstanceRef::Null)
+            )),
+            _ => panic!("Expected an unrecognized instance"),
+        }
+    }
+
+    #[test]
+    fn test_bad_root() {
+        let err = from_str("<model></model>").unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::BadMagic));
+    }
+}
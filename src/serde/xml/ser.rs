@@ -0,0 +1,704 @@
+//! The serialization implementation for an XML model
+
+use crate::model::*;
+use crate::serde::internal::shared_string_hash;
+use crate::serde::io::Write;
+use crate::serde::Result;
+use crate::tree::{NodeRef, Tree, TreeKey};
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use base64::Engine;
+use core::fmt::Display;
+use core::fmt::Write as _;
+
+/// The deepest indentation written. Lines nested further are indented no more, so the output of
+/// deep trees stays linear in their size.
+const MAX_INDENT: usize = 64;
+
+fn escape(str: &str) -> String {
+    let mut out = String::with_capacity(str.len());
+    for c in str.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\r' => out.push_str("&#13;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Whether a string can be written as XML text, XML 1.0 disallows most control characters even
+/// when escaped
+fn is_xml_safe(str: &str) -> bool {
+    str.chars()
+        .all(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r'))
+}
+
+fn encode_base64(data: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(data)
+}
+
+fn float<T: Display + num::Float>(val: T) -> String {
+    if val.is_nan() {
+        String::from("NAN")
+    } else if val.is_infinite() && val.is_sign_positive() {
+        String::from("INF")
+    } else if val.is_infinite() {
+        String::from("-INF")
+    } else {
+        format!("{val}")
+    }
+}
+
+fn vector2(val: &Vector2) -> String {
+    format!("<X>{}</X><Y>{}</Y>", float(val.x), float(val.y))
+}
+
+fn vector3(val: &Vector3) -> String {
+    format!(
+        "<X>{}</X><Y>{}</Y><Z>{}</Z>",
+        float(val.x),
+        float(val.y),
+        float(val.z)
+    )
+}
+
+fn cframe(val: &CFrame) -> String {
+    let mut out = vector3(&val.position);
+    for (r, row) in val.angle.iter().enumerate() {
+        for (c, val) in row.iter().enumerate() {
+            let _ = write!(out, "<R{r}{c}>{}</R{r}{c}>", float(*val));
+        }
+    }
+    out
+}
+
+fn url(val: &str) -> String {
+    if val.is_empty() {
+        String::from("<null></null>")
+    } else {
+        format!("<url>{}</url>", escape(val))
+    }
+}
+
+/// Necessary state for serializing a value
+pub struct Serializer<W> {
+    writer: W,
+}
+
+/// State built up while writing out the instance tree
+struct State {
+    out: String,
+    referents: BTreeMap<TreeKey, usize>,
+    shared_strs: Vec<(String, Vec<u8>)>,
+}
+
+impl State {
+    fn line(&mut self, depth: usize, text: &str) {
+        for _ in 0..depth.min(MAX_INDENT) {
+            self.out.push('\t');
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn shared_key(&mut self, data: Vec<u8>) -> String {
+        match self.shared_strs.iter().find(|(_, blob)| blob == &data) {
+            Some((key, _)) => key.clone(),
+            None => {
+                let key = encode_base64(&shared_string_hash(&data));
+                self.shared_strs.push((key.clone(), data));
+                key
+            }
+        }
+    }
+
+    fn referent(&self, inst: &InstanceRef) -> String {
+        match inst {
+            InstanceRef::Null => String::from("null"),
+            InstanceRef::Item(key) => match self.referents.get(key) {
+                Some(id) => format!("RBX{id}"),
+                None => String::from("null"),
+            },
+        }
+    }
+
+    fn write_property(&mut self, depth: usize, name: &str, prop: Property, protected: bool) {
+        let (ty, value) = match prop {
+            Property::TextString(str) if !is_xml_safe(&str) => {
+                ("BinaryString", encode_base64(str.as_bytes()))
+            }
+            Property::TextString(str) if protected => ("ProtectedString", escape(&str)),
+            Property::TextString(str) => ("string", escape(&str)),
+            Property::BinaryString(blob) => ("BinaryString", encode_base64(&blob)),
+            Property::SharedTextString(str) => ("SharedString", self.shared_key(str.into_bytes())),
+            Property::SharedBinaryString(blob) => ("SharedString", self.shared_key(blob)),
+            Property::Bool(val) => ("bool", format!("{val}")),
+            Property::Int32(val) => ("int", format!("{val}")),
+            Property::Int64(val) => ("int64", format!("{val}")),
+            Property::Float(val) => ("float", float(val)),
+            Property::Double(val) => ("double", float(val)),
+            Property::UDim(val) => (
+                "UDim",
+                format!("<S>{}</S><O>{}</O>", float(val.scale), val.offset),
+            ),
+            Property::UDim2(val) => (
+                "UDim2",
+                format!(
+                    "<XS>{}</XS><XO>{}</XO><YS>{}</YS><YO>{}</YO>",
+                    float(val.x.scale),
+                    val.x.offset,
+                    float(val.y.scale),
+                    val.y.offset,
+                ),
+            ),
+            Property::Ray(val) => (
+                "Ray",
+                format!(
+                    "<origin>{}</origin><direction>{}</direction>",
+                    vector3(&val.origin),
+                    vector3(&val.direction),
+                ),
+            ),
+            Property::Faces(val) => {
+                let bits = u8::from(val.right)
+                    | u8::from(val.top) << 1
+                    | u8::from(val.back) << 2
+                    | u8::from(val.left) << 3
+                    | u8::from(val.bottom) << 4
+                    | u8::from(val.front) << 5;
+                ("Faces", format!("<faces>{bits}</faces>"))
+            }
+            Property::Axes(val) => {
+                let bits = u8::from(val.x) | u8::from(val.y) << 1 | u8::from(val.z) << 2;
+                ("Axes", format!("<axes>{bits}</axes>"))
+            }
+            Property::BrickColor(val) => ("BrickColor", format!("{}", val.index)),
+            Property::Color3(val) => (
+                "Color3",
+                format!(
+                    "<R>{}</R><G>{}</G><B>{}</B>",
+                    float(val.r),
+                    float(val.g),
+                    float(val.b)
+                ),
+            ),
+            Property::Vector2(val) => ("Vector2", vector2(&val)),
+            Property::Vector3(val) => ("Vector3", vector3(&val)),
+            Property::CFrame(val) => ("CoordinateFrame", cframe(&val)),
+            // XML has no quaternion encoding, so these are written as regular CFrames
+            Property::CFrameQuat(val) => ("CoordinateFrame", cframe(&val.into())),
+            Property::Enum(val) => ("token", format!("{}", val as u32)),
+            Property::InstanceRef(val) => ("Ref", self.referent(&val)),
+            Property::Vector3Int16(val) => (
+                "Vector3int16",
+                format!("<X>{}</X><Y>{}</Y><Z>{}</Z>", val.x, val.y, val.z),
+            ),
+            Property::NumberSequence(val) => {
+                let mut out = String::new();
+                for key in &val.keypoints {
+                    let _ = write!(
+                        out,
+                        "{} {} {} ",
+                        float(key.time),
+                        float(key.value),
+                        float(key.envelope)
+                    );
+                }
+                ("NumberSequence", out)
+            }
+            Property::ColorSequence(val) => {
+                let mut out = String::new();
+                for key in &val.keypoints {
+                    let _ = write!(
+                        out,
+                        "{} {} {} {} {} ",
+                        float(key.time),
+                        float(key.color.r),
+                        float(key.color.g),
+                        float(key.color.b),
+                        float(key.envelope)
+                    );
+                }
+                ("ColorSequence", out)
+            }
+            Property::NumberRange(val) => (
+                "NumberRange",
+                format!("{} {} ", float(val.low), float(val.high)),
+            ),
+            Property::Rect(val) => (
+                "Rect2D",
+                format!(
+                    "<min>{}</min><max>{}</max>",
+                    vector2(&val.top_left),
+                    vector2(&val.bottom_right),
+                ),
+            ),
+            Property::PhysicalProperties(val) => {
+                let out = match val {
+                    PhysicalProperties::Custom {
+                        density,
+                        elasticity,
+                        elasticity_weight,
+                        friction,
+                        friction_weight,
+                    } => format!(
+                        "<CustomPhysics>true</CustomPhysics><Density>{}</Density>\
+                        <Friction>{}</Friction><Elasticity>{}</Elasticity>\
+                        <FrictionWeight>{}</FrictionWeight><ElasticityWeight>{}</ElasticityWeight>",
+                        float(density),
+                        float(friction),
+                        float(elasticity),
+                        float(friction_weight),
+                        float(elasticity_weight),
+                    ),
+                    _ => String::from("<CustomPhysics>false</CustomPhysics>"),
+                };
+                ("PhysicalProperties", out)
+            }
+            Property::Color3Uint8(val) => {
+                let packed = u32::from_be_bytes([0xFF, val.r, val.g, val.b]);
+                ("Color3uint8", format!("{packed}"))
+            }
+            Property::OptionalCFrame(val) => {
+                let out = match val {
+                    Some(val) => format!("<CFrame>{}</CFrame>", cframe(&val)),
+                    None => String::new(),
+                };
+                ("OptionalCoordinateFrame", out)
+            }
+            Property::Uuid(val) => ("UniqueId", format!("{}", val.simple())),
+            Property::SecurityCapabilities(val) => ("SecurityCapabilities", format!("{}", val.0)),
+            Property::EnumItem(val) => ("token", format!("{}", val.value)),
+            // Nothing is stored for an unset value
+            Property::Nil => return,
+            // Distinct from the `url` and `null` children of older string `Content` elements
+            Property::Content(Content::None) => ("Content", String::from("<None></None>")),
+            Property::Content(Content::Uri(uri)) => {
+                ("Content", format!("<Uri>{}</Uri>", escape(&uri)))
+            }
+            Property::Content(Content::Object(key)) => (
+                "Content",
+                format!(
+                    "<Object>{}</Object>",
+                    self.referent(&InstanceRef::Item(key))
+                ),
+            ),
+            Property::Font(val) => {
+                let style = match val.style {
+                    FontStyle::Normal => "Normal",
+                    FontStyle::Italic => "Italic",
+                };
+                let mut out = format!(
+                    "<Family>{}</Family><Weight>{}</Weight><Style>{style}</Style>",
+                    url(&val.family),
+                    i32::from(val.weight),
+                );
+                if !val.cached_face_id.is_empty() {
+                    let _ = write!(
+                        out,
+                        "<CachedFaceId>{}</CachedFaceId>",
+                        url(&val.cached_face_id)
+                    );
+                }
+                ("Font", out)
+            }
+        };
+
+        self.line(
+            depth,
+            &format!("<{ty} name=\"{}\">{value}</{ty}>", escape(name)),
+        );
+    }
+
+    /// Write the opening tag and properties of an item, leaving it open for its children
+    fn open_item(&mut self, depth: usize, node: &NodeRef<'_, '_, Instance>) {
+        let id = self.referents[&node.key()];
+        self.line(
+            depth,
+            &format!(
+                "<Item class=\"{}\" referent=\"RBX{id}\">",
+                escape(&node.class_name())
+            ),
+        );
+
+        let props = Instance::break_instance(node);
+        if props.is_empty() {
+            self.line(depth + 1, "<Properties />");
+        } else {
+            self.line(depth + 1, "<Properties>");
+            for (name, prop) in props {
+                let protected = node.is_protected(&name);
+                self.write_property(depth + 2, &name, prop, protected);
+            }
+            self.line(depth + 1, "</Properties>");
+        }
+    }
+
+    /// Write every item in a tree, walking it with an explicit stack so that deep trees can't
+    /// overflow the call stack
+    fn write_items(&mut self, tree: &Tree<Instance>) {
+        enum Step {
+            Open(TreeKey, usize),
+            Close(usize),
+        }
+
+        let mut stack = tree
+            .root_keys()
+            .map(|key| Step::Open(key, 1))
+            .collect::<Vec<_>>();
+        stack.reverse();
+
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Open(key, depth) => {
+                    let node = tree.try_get(key).expect("Couldn't borrow node");
+                    self.open_item(depth, &node);
+                    stack.push(Step::Close(depth));
+                    let start = stack.len();
+                    stack.extend(
+                        tree.child_keys_of(key)
+                            .map(|child| Step::Open(child, depth + 1)),
+                    );
+                    stack[start..].reverse();
+                }
+                Step::Close(depth) => self.line(depth, "</Item>"),
+            }
+        }
+    }
+}
+
+/// Write out a full model as an XML document
+fn write_model(model: &RbxModel) -> String {
+    let mut state = State {
+        out: String::new(),
+        referents: model
+            .nodes
+            .depth_first_keys()
+            .enumerate()
+            .map(|(idx, key)| (key, idx))
+            .collect(),
+        shared_strs: Vec::new(),
+    };
+
+    state.line(0, "<roblox version=\"4\">");
+    for (key, value) in &model.meta {
+        state.line(
+            1,
+            &format!("<Meta name=\"{}\">{}</Meta>", escape(key), escape(value)),
+        );
+    }
+    state.write_items(&model.nodes);
+
+    if !state.shared_strs.is_empty() {
+        let shared_strs = core::mem::take(&mut state.shared_strs);
+        state.line(1, "<SharedStrings>");
+        for (key, data) in shared_strs {
+            state.line(
+                2,
+                &format!(
+                    "<SharedString md5=\"{key}\">{}</SharedString>",
+                    encode_base64(&data)
+                ),
+            );
+        }
+        state.line(1, "</SharedStrings>");
+    }
+    state.line(0, "</roblox>");
+
+    state.out
+}
+
+impl<W: Write> Serializer<W> {
+    /// Create a new serializer from a writer and if necessary any other state
+    pub fn new(writer: W) -> Serializer<W> {
+        Serializer { writer }
+    }
+
+    /// Serialize a model to the output stream
+    ///
+    /// # Panics
+    ///
+    /// If any instance in the model is currently mutably borrowed
+    pub fn serialize(mut self, model: &RbxModel) -> Result<()> {
+        self.writer.write_all(write_model(model).as_bytes())
+    }
+}
+
+/// Write a model out to a provided IO writer
+pub fn to_writer<W: Write>(writer: W, model: &RbxModel) -> Result<()> {
+    Serializer::new(writer).serialize(model)
+}
+
+/// Write a model out to a file, creating it if necessary
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[cfg(feature = "std")]
+pub fn to_file<P: AsRef<std::path::Path>>(path: P, model: &RbxModel) -> Result<()> {
+    to_writer(std::fs::File::create(path)?, model)
+}
+
+/// Write a model to an XML string, and return it
+///
+/// # Panics
+///
+/// If any instance in the model is currently mutably borrowed
+pub fn to_string(model: &RbxModel) -> Result<String> {
+    Ok(write_model(model))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::instance::{Base, LuaSourceContainer, Model, ModuleScript};
+    use crate::serde::xml::from_str;
+
+    #[test]
+    fn test_round_trip() {
+        let model = RbxModel::new();
+        let tree = model.tree();
+
+        let root = tree.add_root(Instance::Model(Model::new_named(String::from("Root"))));
+
+        let mut props = BTreeMap::new();
+        props.insert(
+            String::from("Name"),
+            Property::TextString(String::from("Target")),
+        );
+        props.insert(
+            String::from("Value"),
+            Property::TextString(String::from("<escaped & \"quoted\">")),
+        );
+        tree.new_child(Instance::Other(String::from("Custom"), props), root);
+        let target = tree.child_keys_of(root).next().unwrap();
+
+        let mut props = BTreeMap::new();
+        props.insert(
+            String::from("Name"),
+            Property::TextString(String::from("Other")),
+        );
+        props.insert(
+            String::from("Pointer"),
+            Property::InstanceRef(InstanceRef::Item(target)),
+        );
+        props.insert(
+            String::from("Source"),
+            Property::TextString(String::from("a\r\nb")),
+        );
+        props.insert(
+            String::from("Control"),
+            Property::TextString(String::from("a\0b")),
+        );
+        props.insert(
+            String::from("Blob"),
+            Property::BinaryString(vec![0, 1, 255]),
+        );
+        props.insert(
+            String::from("Shared"),
+            Property::SharedBinaryString(vec![4, 5, 255]),
+        );
+        props.insert(String::from("Float"), Property::Float(f32::NEG_INFINITY));
+        props.insert(String::from("Double"), Property::Double(0.1));
+        props.insert(
+            String::from("Color"),
+            Property::Color3Uint8(Color3Uint8::new(1, 2, 3)),
+        );
+        props.insert(
+            String::from("Pivot"),
+            Property::OptionalCFrame(Some(CFrame::new(
+                Vector3::new(1.0, 2.0, 3.0),
+                [[0.0; 3]; 3],
+            ))),
+        );
+        tree.new_child(Instance::Other(String::from("Custom"), props), root);
+
+        let xml = to_string(&model).unwrap();
+        let new_model = from_str(&xml).unwrap();
+
+        assert_eq!(new_model.tree().len(), 3);
+
+        let target = new_model.get_path("Root/Target").unwrap();
+        match &*target {
+            Instance::Other(_, props) => assert!(
+                matches!(&props["Value"], Property::TextString(str) if str == "<escaped & \"quoted\">")
+            ),
+            _ => panic!("Expected an unrecognized instance"),
+        }
+
+        let other = new_model.get_path("Root/Other").unwrap();
+        let props = match &*other {
+            Instance::Other(class, props) => {
+                assert_eq!(class, "Custom");
+                props
+            }
+            _ => panic!("Expected an unrecognized instance"),
+        };
+
+        assert!(
+            matches!(props["Pointer"], Property::InstanceRef(InstanceRef::Item(key)) if key == target.key())
+        );
+        assert!(matches!(&props["Source"], Property::TextString(str) if str == "a\r\nb"));
+        assert!(matches!(&props["Control"], Property::TextString(str) if str == "a\0b"));
+        assert!(matches!(&props["Blob"], Property::BinaryString(blob) if blob == &[0, 1, 255]));
+        assert!(
+            matches!(&props["Shared"], Property::SharedBinaryString(blob) if blob == &[4, 5, 255])
+        );
+        assert!(matches!(props["Float"], Property::Float(val) if val == f32::NEG_INFINITY));
+        assert!(matches!(props["Double"], Property::Double(val) if val == 0.1));
+        assert!(
+            matches!(&props["Color"], Property::Color3Uint8(col) if (col.r, col.g, col.b) == (1, 2, 3))
+        );
+        assert!(
+            matches!(&props["Pivot"], Property::OptionalCFrame(Some(pivot)) if pivot.position == Vector3::new(1.0, 2.0, 3.0))
+        );
+    }
+
+    #[test]
+    fn test_content_round_trip() {
+        fn other(name: &str, content: Content) -> Instance {
+            let mut props = BTreeMap::new();
+            props.insert(
+                String::from("Name"),
+                Property::TextString(String::from(name)),
+            );
+            props.insert(String::from("Content"), Property::Content(content));
+            Instance::Other(String::from("Custom"), props)
+        }
+
+        let model = RbxModel::new();
+        let uri = Content::Uri(String::from("rbxassetid://1234"));
+        let root = model.tree().add_root(other("Root", uri));
+        model
+            .tree()
+            .new_child(other("Child", Content::Object(root)), root);
+        model.tree().new_child(other("Empty", Content::None), root);
+
+        let new_model = from_str(&to_string(&model).unwrap()).unwrap();
+        let props = |path| match &*new_model.get_path(path).unwrap() {
+            Instance::Other(_, props) => props.clone(),
+            _ => panic!("Expected an unrecognized instance"),
+        };
+        let root = new_model.get_path("Root").unwrap().key();
+
+        assert!(matches!(
+            &props("Root")["Content"],
+            Property::Content(Content::Uri(uri)) if uri == "rbxassetid://1234"
+        ));
+        assert!(matches!(
+            props("Root/Child")["Content"],
+            Property::Content(Content::Object(key)) if key == root
+        ));
+        assert!(matches!(
+            props("Root/Empty")["Content"],
+            Property::Content(Content::None)
+        ));
+    }
+
+    #[test]
+    fn test_extra_properties_round_trip() {
+        let model = from_str(
+            r#"<roblox version="4">
+                <Item class="Folder" referent="RBX0">
+                    <Properties>
+                        <BinaryString name="AttributesSerialize"></BinaryString>
+                        <string name="Name">Folder</string>
+                        <int64 name="SourceAssetId">-1</int64>
+                        <BinaryString name="Tags"></BinaryString>
+                        <int name="Unmodelled">5</int>
+                    </Properties>
+                </Item>
+            </roblox>"#,
+        )
+        .unwrap();
+
+        let new_model = from_str(&to_string(&model).unwrap()).unwrap();
+        let folder = new_model.get_path("Folder").unwrap();
+
+        match &*folder {
+            Instance::Folder(folder) => {
+                assert_eq!(folder.extra_properties.len(), 1);
+                assert!(matches!(
+                    folder.extra_properties["Unmodelled"],
+                    Property::Int32(5)
+                ));
+            }
+            _ => panic!("Expected a Folder"),
+        }
+    }
+
+    #[test]
+    fn test_protected_source() {
+        let model = RbxModel::new();
+        model.tree().add_root(Instance::ModuleScript(ModuleScript {
+            lua_source_container: LuaSourceContainer {
+                base: Base {
+                    name: String::from("Module"),
+                    tags: Tags::new(),
+                    source_asset_id: -1,
+                    attributes: Attributes::default(),
+                    unique_id: None,
+                    extra_properties: BTreeMap::new(),
+                },
+                script_guid: String::new(),
+            },
+            linked_source: String::new(),
+            source: String::from("return 1"),
+        }));
+
+        let xml = to_string(&model).unwrap();
+        assert!(xml.contains("<ProtectedString name=\"Source\">return 1</ProtectedString>"));
+        assert!(xml.contains("<string name=\"LinkedSource\">"));
+        assert!(xml.contains("<string name=\"Name\">Module</string>"));
+
+        let new_model = from_str(&xml).unwrap();
+        let script = new_model.get_path("Module").unwrap();
+        match &*script {
+            Instance::ModuleScript(script) => assert_eq!(script.source, "return 1"),
+            _ => panic!("Expected a ModuleScript"),
+        }
+    }
+
+    #[test]
+    fn test_shared_strings_deduplicated() {
+        let model = RbxModel::new();
+        let mut props = BTreeMap::new();
+        props.insert(
+            String::from("A"),
+            Property::SharedTextString(String::from("same")),
+        );
+        props.insert(
+            String::from("B"),
+            Property::SharedTextString(String::from("same")),
+        );
+        model
+            .tree()
+            .add_root(Instance::Other(String::from("Custom"), props));
+
+        let xml = to_string(&model).unwrap();
+
+        assert_eq!(xml.matches("<SharedString md5=").count(), 1);
+    }
+
+    #[test]
+    fn test_deep_tree() {
+        let model = RbxModel::new();
+        let tree = model.tree();
+        let mut parent = tree.add_root(Instance::Other(String::from("Custom"), BTreeMap::new()));
+        for _ in 1..50_000 {
+            let child = tree.add_root(Instance::Other(String::from("Custom"), BTreeMap::new()));
+            tree.set_child(parent, child);
+            parent = child;
+        }
+
+        let xml = to_string(&model).unwrap();
+
+        assert_eq!(xml.matches("<Item class=\"Custom\"").count(), 50_000);
+        assert_eq!(xml.matches("</Item>").count(), 50_000);
+        assert!(xml.lines().all(|line| !line.starts_with(&"\t".repeat(65))));
+    }
+}
@@ -2,10 +2,12 @@
 //! nodes concurrently
 
 use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec;
 use alloc::vec::Vec;
 use core::borrow::{Borrow, BorrowMut};
 use core::cell::{BorrowError, BorrowMutError};
-use core::cell::{Ref, RefCell, RefMut};
+use core::cell::{Cell, Ref, RefCell, RefMut};
 use core::fmt;
 #[cfg(feature = "unstable")]
 use core::marker::Unsize;
@@ -137,9 +139,17 @@ impl From<BorrowMutError> for Error {
 /// An implementation of a tree data structure, with the ability to get mutable references to
 /// multiple nodes at once. Supports access via slot keys, or by traversing immutable or mutable
 /// node references.
+///
+/// Cloning a tree copies the value of every node, keeping the same keys.
+///
+/// # Panics
+///
+/// Cloning panics if any node is currently mutably borrowed
 #[derive(Clone)]
 pub struct Tree<T: ?Sized> {
     inner: RefCell<InnerTree<T>>,
+    /// Counts changes to the tree, so that anything derived from it knows when to be rebuilt
+    generation: Cell<u64>,
 }
 
 impl<T: ?Sized> Tree<T> {
@@ -149,6 +159,21 @@ impl<T: ?Sized> Tree<T> {
         Tree::default()
     }
 
+    /// Get a number which changes whenever the structure of the tree changes or any node is
+    /// mutably borrowed
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation.get()
+    }
+
+    fn touch(&self) {
+        self.generation.set(self.generation.get().wrapping_add(1));
+    }
+
+    fn inner_mut(&self) -> RefMut<'_, InnerTree<T>> {
+        self.touch();
+        self.inner.borrow_mut()
+    }
+
     /// Get the length of this tree, the total number of nodes
     pub fn len(&self) -> usize {
         self.inner.borrow().nodes.len()
@@ -162,7 +187,7 @@ impl<T: ?Sized> Tree<T> {
     /// Add a new root from a type that unsizes into the type of the tree
     #[cfg(feature = "unstable")]
     pub fn add_root_from<U: Unsize<T>>(&self, item: U) -> TreeKey {
-        let mut rc = self.inner.borrow_mut();
+        let mut rc = self.inner_mut();
 
         let new_node = RefCell::new(item);
 
@@ -177,18 +202,18 @@ impl<T: ?Sized> Tree<T> {
     /// Create a new child of a node from a type that unsizes into the type of the tree
     #[cfg(feature = "unstable")]
     pub fn new_child_from<U: Unsize<T>>(&self, item: U, parent: TreeKey) {
-        self.inner.borrow_mut().new_child_from(item, parent);
+        self.inner_mut().new_child_from(item, parent);
     }
 
     /// Set the first node as the parent of the second node,
     /// unsetting the current parent if there is one
     pub fn set_child(&self, parent: TreeKey, child: TreeKey) {
-        self.inner.borrow_mut().set_child(parent, child);
+        self.inner_mut().set_child(parent, child);
     }
 
     /// Remove the second node as a child of the first node
     pub fn remove_child(&self, parent: TreeKey, child: TreeKey) {
-        self.inner.borrow_mut().remove_child(parent, child);
+        self.inner_mut().remove_child(parent, child);
     }
 
     /// Try to get an immutable reference to a node identified by the provided key
@@ -237,6 +262,23 @@ impl<T: ?Sized> Tree<T> {
             .into_iter()
     }
 
+    /// Iterator over the keys of all nodes in this tree, depth-first. Each node comes before its
+    /// children, and siblings are in the order they were added.
+    pub fn depth_first_keys(&self) -> impl Iterator<Item = TreeKey> {
+        let inner = self.inner.borrow();
+
+        let mut out = Vec::with_capacity(inner.nodes.len());
+        let mut stack = inner.roots.iter().rev().copied().collect::<Vec<_>>();
+        while let Some(key) = stack.pop() {
+            out.push(key);
+            if let Some(children) = inner.children.get(key) {
+                stack.extend(children.iter().rev());
+            }
+        }
+
+        out.into_iter()
+    }
+
     /// Iterate over the roots of this tree.
     ///
     /// A root is any node that has no parent
@@ -298,21 +340,117 @@ impl<T: ?Sized> Tree<T> {
 impl<T> Tree<T> {
     /// Create a new child of a node from the provided value
     pub fn new_child(&self, item: T, parent: TreeKey) {
-        self.inner.borrow_mut().new_child(item, parent);
+        self.inner_mut().new_child(item, parent);
     }
 
     /// Add a new root to the tree initialized with the provided value
     pub fn add_root(&self, item: T) -> TreeKey {
-        let mut rc = self.inner.borrow_mut();
+        self.inner_mut().add_root(item)
+    }
+
+    /// Remove a node and all its descendants from the tree, returning their values in depth-first
+    /// order. Fails without changing the tree if any of the removed nodes are currently borrowed.
+    pub fn destroy(&self, key: TreeKey) -> Result<Vec<T>> {
+        self.inner_mut().destroy(key)
+    }
+
+    /// Move every node of another tree into this one, without copying their values. Each root of
+    /// `other` becomes a child of `parent`, or a new root if that is `None`, keeping their order.
+    /// Returns each moved key paired with its new key, in depth-first order.
+    pub fn append(
+        &self,
+        other: Tree<T>,
+        parent: Option<TreeKey>,
+    ) -> Result<Vec<(TreeKey, TreeKey)>> {
+        let mut inner = self.inner_mut();
+        if matches!(parent, Some(parent) if !inner.nodes.contains_key(parent)) {
+            return Err(Error::Missing);
+        }
 
-        let new_node = RefCell::new(item);
+        let mut src = other.inner.into_inner();
+        let mut pairs = Vec::with_capacity(src.nodes.len());
+        let mut moved = BTreeMap::new();
+        for root in src.roots.clone() {
+            let keys = src.subtree_keys(root);
+            let old_parents = keys
+                .iter()
+                .map(|&key| src.parents.get(key).copied())
+                .collect::<Vec<_>>();
+            let items = src.destroy(root)?;
+
+            for ((old_key, old_parent), item) in keys.into_iter().zip(old_parents).zip(items) {
+                let new_parent = match old_parent {
+                    Some(old_parent) => moved.get(&old_parent).copied(),
+                    None => parent,
+                };
+
+                let new_key = match new_parent {
+                    Some(new_parent) => inner.new_child(item, new_parent),
+                    None => inner.add_root(item),
+                };
+                moved.insert(old_key, new_key);
+                pairs.push((old_key, new_key));
+            }
+        }
 
-        // SAFETY: Box::into_raw always returns a valid pointer
-        let new_node = unsafe { NonNull::new_unchecked(Box::into_raw(Box::new(new_node))) };
+        Ok(pairs)
+    }
+}
 
-        let new_key = rc.nodes.insert(new_node);
-        rc.roots.push(new_key);
-        new_key
+impl<T: Clone> Tree<T> {
+    /// Copy a node and all its descendants from another tree, or from this one, into this tree.
+    /// The copy is added as a child of `parent`, or as a new root if that is `None`. Returns each
+    /// copied key paired with the key of its copy, in depth-first order, so the first pair is
+    /// always for `key` itself.
+    pub fn copy_subtree(
+        &self,
+        src: &Tree<T>,
+        key: TreeKey,
+        parent: Option<TreeKey>,
+    ) -> Result<Vec<(TreeKey, TreeKey)>> {
+        let items = {
+            let src = src.inner.borrow();
+            if !src.nodes.contains_key(key) {
+                return Err(Error::Missing);
+            }
+
+            src.subtree_keys(key)
+                .into_iter()
+                .map(|old_key| {
+                    // SAFETY: Type guarantees inner nodes are valid
+                    let item = unsafe { src.nodes[old_key].as_ref() }.try_borrow()?.clone();
+                    let old_parent = if old_key == key {
+                        None
+                    } else {
+                        src.parents.get(old_key).copied()
+                    };
+                    Ok((old_key, old_parent, item))
+                })
+                .collect::<Result<Vec<_>>>()?
+        };
+
+        let mut inner = self.inner_mut();
+        if matches!(parent, Some(parent) if !inner.nodes.contains_key(parent)) {
+            return Err(Error::Missing);
+        }
+
+        let mut pairs = Vec::with_capacity(items.len());
+        let mut copies = BTreeMap::new();
+        for (old_key, old_parent, item) in items {
+            let new_parent = match old_parent {
+                Some(old_parent) => copies.get(&old_parent).copied(),
+                None => parent,
+            };
+
+            let new_key = match new_parent {
+                Some(new_parent) => inner.new_child(item, new_parent),
+                None => inner.add_root(item),
+            };
+            copies.insert(old_key, new_key);
+            pairs.push((old_key, new_key));
+        }
+
+        Ok(pairs)
     }
 }
 
@@ -346,11 +484,12 @@ impl<T: ?Sized> Default for Tree<T> {
     fn default() -> Self {
         Tree {
             inner: RefCell::new(InnerTree::new()),
+            generation: Cell::new(0),
         }
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 struct InnerTree<T: ?Sized> {
     nodes: SlotMap<TreeKey, NonNull<RefCell<T>>>,
     parents: SecondaryMap<TreeKey, TreeKey>,
@@ -409,16 +548,68 @@ impl<T: ?Sized> InnerTree<T> {
         self.parents.remove(child);
         self.roots.push(child);
     }
+
+    fn subtree_keys(&self, key: TreeKey) -> Vec<TreeKey> {
+        let mut keys = Vec::new();
+        let mut stack = vec![key];
+        while let Some(key) = stack.pop() {
+            keys.push(key);
+            if let Some(children) = self.children.get(key) {
+                stack.extend(children.iter().rev());
+            }
+        }
+        keys
+    }
 }
 
 impl<T> InnerTree<T> {
-    fn new_child(&mut self, item: T, parent: TreeKey) {
+    fn destroy(&mut self, key: TreeKey) -> Result<Vec<T>> {
+        if !self.nodes.contains_key(key) {
+            return Err(Error::Missing);
+        }
+
+        let keys = self.subtree_keys(key);
+
+        for &key in &keys {
+            // SAFETY: Type guarantees inner nodes are valid
+            unsafe { self.nodes[key].as_ref() }.try_borrow_mut()?;
+        }
+
+        match self.parents.remove(key) {
+            Some(parent) => self.children[parent].retain(|&k| k != key),
+            None => self.roots.retain(|&k| k != key),
+        }
+
+        Ok(keys
+            .into_iter()
+            .map(|key| {
+                self.parents.remove(key);
+                self.children.remove(key);
+                let node = self.nodes.remove(key).expect("Node was checked to exist");
+                // SAFETY: Type guarantees inner nodes are valid, and we checked above that no
+                //         references to this node are alive
+                unsafe { Box::from_raw(node.as_ptr()) }.into_inner()
+            })
+            .collect())
+    }
+
+    fn new_node(&mut self, item: T) -> TreeKey {
         let new_node = RefCell::new(item);
 
         // SAFETY: Box::into_raw is guaranteed to return non-null pointer
         let new_node = unsafe { NonNull::new_unchecked(Box::into_raw(Box::new(new_node))) };
 
-        let new_key = self.nodes.insert(new_node);
+        self.nodes.insert(new_node)
+    }
+
+    fn add_root(&mut self, item: T) -> TreeKey {
+        let new_key = self.new_node(item);
+        self.roots.push(new_key);
+        new_key
+    }
+
+    fn new_child(&mut self, item: T, parent: TreeKey) -> TreeKey {
+        let new_key = self.new_node(item);
 
         self.children
             .entry(parent)
@@ -427,6 +618,31 @@ impl<T> InnerTree<T> {
             .push(new_key);
 
         self.parents.insert(new_key, parent);
+        new_key
+    }
+}
+
+impl<T: Clone> Clone for InnerTree<T> {
+    fn clone(&self) -> Self {
+        // Keys are kept, but every node gets its own allocation so the trees don't share (and
+        // double free) them
+        let mut nodes = self.nodes.clone();
+        for node in nodes.values_mut() {
+            // SAFETY: Type guarantees inner nodes are valid
+            let item = unsafe { node.as_ref() }
+                .try_borrow()
+                .expect("Couldn't borrow node")
+                .clone();
+            // SAFETY: Box::into_raw is guaranteed to return non-null pointer
+            *node = unsafe { NonNull::new_unchecked(Box::into_raw(Box::new(RefCell::new(item)))) };
+        }
+
+        InnerTree {
+            nodes,
+            parents: self.parents.clone(),
+            children: self.children.clone(),
+            roots: self.roots.clone(),
+        }
     }
 }
 
@@ -495,6 +711,7 @@ impl<'a, 'b, T: ?Sized> NodeRefMut<'a, 'b, T> {
         key: TreeKey,
         ptr: &'_ NonNull<RefCell<T>>,
     ) -> Result<NodeRefMut<'a, 'b, T>> {
+        tree.touch();
         Ok(NodeRefMut {
             tree,
             mykey: key,
@@ -539,6 +756,14 @@ impl<T> NodeRefMut<'_, '_, T> {
     pub fn new_child(&mut self, child: T) {
         self.tree.new_child(child, self.key());
     }
+
+    /// Remove this node and all its descendants from the tree, returning their values in
+    /// depth-first order. See [`Tree::destroy`].
+    pub fn destroy(self) -> Result<Vec<T>> {
+        let NodeRefMut { tree, mykey, node } = self;
+        drop(node);
+        tree.destroy(mykey)
+    }
 }
 
 impl<T: ?Sized + fmt::Debug> fmt::Debug for NodeRefMut<'_, '_, T> {
@@ -612,6 +837,101 @@ mod tests {
         assert_eq!(children.len(), 2);
     }
 
+    #[test]
+    fn test_clone() {
+        let tree = Tree::new();
+        let root = tree.add_root(1);
+        tree.new_child(2, root);
+
+        // The copy owns its own nodes, so changing or dropping one tree doesn't affect the other
+        let copy = tree.clone();
+        *copy.try_get_mut(root).unwrap() = 3;
+        let original = tree.try_get(root).unwrap();
+        assert_eq!(*original, 1);
+        drop(original);
+        drop(tree);
+
+        let copied = copy.try_get(root).unwrap();
+        assert_eq!(*copied, 3);
+        assert_eq!(copy.len(), 2);
+        assert_eq!(copied.children().count(), 1);
+    }
+
+    #[test]
+    fn test_append() {
+        let tree = Tree::new();
+        let parent = tree.add_root(0);
+
+        let other = Tree::new();
+        let first = other.add_root(1);
+        other.new_child(2, first);
+        let second = other.add_root(3);
+
+        let pairs = tree.append(other, Some(parent)).unwrap();
+        assert_eq!(pairs.len(), 3);
+        assert_eq!(pairs[0].0, first);
+        assert_eq!(pairs[2].0, second);
+        assert_eq!(tree.len(), 4);
+
+        let values = tree
+            .depth_first_keys()
+            .map(|key| *tree.try_get(key).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(values, [0, 1, 2, 3]);
+        assert_eq!(tree.parent_key_of(pairs[1].1), Some(pairs[0].1));
+        assert_eq!(tree.parent_key_of(pairs[2].1), Some(parent));
+    }
+
+    #[test]
+    fn test_depth_first_keys() {
+        let tree = Tree::new();
+        let first = tree.add_root(0);
+        let second = tree.add_root(3);
+        tree.new_child(1, first);
+        tree.new_child(4, second);
+        tree.new_child(2, first);
+
+        let values = tree
+            .depth_first_keys()
+            .map(|key| *tree.try_get(key).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(values, [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_destroy() {
+        let tree = Tree::new();
+        let root = tree.add_root(0);
+        let other = tree.add_root(5);
+        tree.new_child(1, root);
+        let child = tree.child_keys_of(root).next().unwrap();
+        tree.new_child(2, child);
+        tree.new_child(3, child);
+        tree.new_child(4, root);
+
+        {
+            let _borrow = tree.try_get(tree.child_keys_of(child).next().unwrap());
+            assert!(matches!(tree.destroy(child), Err(Error::CantBorrow)));
+            assert_eq!(tree.len(), 6);
+        }
+
+        assert_eq!(tree.destroy(child).unwrap(), [1, 2, 3]);
+        assert_eq!(tree.len(), 3);
+        assert!(matches!(tree.try_get(child), Err(Error::Missing)));
+        assert!(matches!(tree.destroy(child), Err(Error::Missing)));
+
+        let values = tree
+            .depth_first_keys()
+            .map(|key| *tree.try_get(key).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(values, [0, 4, 5]);
+
+        let node = tree.try_get_mut(other).unwrap();
+        assert_eq!(node.destroy().unwrap(), [5]);
+        assert_eq!(tree.root_keys().collect::<Vec<_>>(), [root]);
+    }
+
     #[test]
     fn test_promote() {
         let tree = Tree::new();
@@ -649,4 +969,30 @@ mod tests {
             assert_eq!(*root, true);
         }
     }
+
+    #[test]
+    fn test_copy_subtree() {
+        let tree = Tree::new();
+        let root = tree.add_root(0);
+        tree.new_child(1, root);
+        let child = tree.child_keys_of(root).next().unwrap();
+        tree.new_child(2, child);
+
+        let pairs = tree.copy_subtree(&tree, child, Some(root)).unwrap();
+        assert_eq!(tree.len(), 5);
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(pairs[0].0, child);
+        assert_eq!(tree.parent_key_of(pairs[0].1), Some(root));
+        assert_eq!(tree.parent_key_of(pairs[1].1), Some(pairs[0].1));
+        assert_eq!(*tree.try_get(pairs[1].1).unwrap(), 2);
+
+        let other = tree.clone();
+        *tree.try_get_mut(root).unwrap() = 10;
+        assert_eq!(*other.try_get(root).unwrap(), 0);
+
+        let pairs = other.copy_subtree(&tree, root, None).unwrap();
+        assert_eq!(other.len(), 10);
+        assert_eq!(*other.try_get(pairs[0].1).unwrap(), 10);
+        assert!(other.copy_subtree(&tree, pairs[0].1, None).is_err());
+    }
 }